@@ -0,0 +1,310 @@
+use std::{fmt, path::PathBuf, str::FromStr, time::Duration};
+
+use crate::{error::ErrorFormat, interpreter::{limits::Limits, sandbox::Sandbox}};
+
+/// Action requested on the command line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command
+{
+   /// Starts an interactive session.
+   Repl,
+   /// Runs a script passing it the supplied arguments (`rlox <file> [args...]`, `rlox - [args...]` or `rlox -e <code> [args...]`).
+   Run(Source, Vec<String>),
+   /// Prints the output of a stage of the front end instead of running the script (`rlox --tokens <file>`).
+   Dump(Dump, Source),
+   /// Runs the benchmarks the given number of times (`rlox --bench [--iterations <n>]`).
+   Bench(usize),
+   Version,
+   Help
+}
+
+/// Parsed command line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Arguments
+{
+   pub command: Command,
+   /// Format of the errors (`--error-format=human|json`).
+   pub error_format: ErrorFormat,
+   /// Resource limits of the script (`--max-call-depth`, `--max-instructions`, `--timeout` and `--max-memory`).
+   pub limits: Limits,
+   /// Native functions available to the script (`--sandbox pure|standard|read-only:DIR|full`).
+   pub sandbox: Sandbox
+}
+
+/// Where the code of a script comes from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Source
+{
+   File(String),
+   Stdin,
+   Eval(String)
+}
+
+/// Debugging output that can be requested instead of running a script.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dump
+{
+   /// `--tokens`: the tokens produced by the lexer, with their positions.
+   Tokens,
+   /// `--cst`: the lossless concrete syntax tree, with the whitespace and the comments attached to the tokens.
+   Cst,
+   /// `--ast`: the syntax tree produced by the parser, as S-expressions.
+   Ast,
+   /// `--resolve`: the variable expressions with the scope distance found by the resolver.
+   Resolve
+}
+
+/// Error in the command line arguments.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UsageError
+{
+   pub message: String,
+   /// Format of the errors requested before the wrong argument, used to report this error too.
+   pub error_format: ErrorFormat
+}
+
+impl UsageError
+{
+   fn new(message: String) -> Self
+   {
+      Self { message, error_format: ErrorFormat::Human }
+   }
+}
+
+impl fmt::Display for UsageError
+{
+   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+   {
+      write!(f, "{}", self.message)
+   }
+}
+
+/// Parses the command line arguments, excluding the program name.
+///
+/// Everything following the script (a path, `-` or `-e <code>`) is passed through to the script untouched, as is everything after `--`.
+pub fn parse_args(args: &[String]) -> Result<Arguments, UsageError>
+{
+   let mut error_format: ErrorFormat = ErrorFormat::Human;
+   let mut limits: Limits = Limits::default();
+   let mut sandbox: Sandbox = Sandbox::default();
+   let command = parse_command(args, &mut error_format, &mut limits, &mut sandbox).map_err(|error| UsageError { error_format, ..error })?;
+   Ok(Arguments { command, error_format, limits, sandbox })
+}
+
+fn parse_command(args: &[String], error_format: &mut ErrorFormat, limits: &mut Limits, sandbox: &mut Sandbox) -> Result<Command, UsageError>
+{
+   let mut bench: bool = false;
+   let mut opt_iterations: Option<usize> = None;
+   let mut opt_dump: Option<Dump> = None;
+   let mut iter = args.iter();
+   while let Some(arg) = iter.next() {
+      match arg.as_str() {
+         "-h" | "--help" => {
+            return Ok(Command::Help);
+         },
+         "-V" | "--version" => {
+            return Ok(Command::Version);
+         },
+         "--bench" => {
+            bench = true;
+         },
+         "--tokens" | "--cst" | "--ast" | "--resolve" => {
+            if opt_dump.is_some() {
+               return Err(UsageError::new("Only one of '--tokens', '--cst', '--ast' and '--resolve' can be used.".to_owned()));
+            }
+            opt_dump = Some(match arg.as_str() {
+               "--tokens" => Dump::Tokens,
+               "--cst"    => Dump::Cst,
+               "--ast"    => Dump::Ast,
+               _          => Dump::Resolve,
+            });
+         },
+         "--error-format" => {
+            let value = iter.next().ok_or_else(|| UsageError::new("Missing value for '--error-format'.".to_owned()))?;
+            *error_format = parse_error_format(value)?;
+         },
+         _ if arg.starts_with("--error-format=") => {
+            *error_format = parse_error_format(&arg["--error-format=".len()..])?;
+         },
+         "--sandbox" => {
+            let value = iter.next().ok_or_else(|| UsageError::new("Missing value for '--sandbox'.".to_owned()))?;
+            *sandbox = parse_sandbox(value)?;
+         },
+         "--max-call-depth" => {
+            limits.max_call_depth = parse_positive(arg, iter.next())?;
+         },
+         "--max-instructions" => {
+            limits.max_instructions = Some(parse_positive(arg, iter.next())?);
+         },
+         "--timeout" => {
+            limits.timeout = Some(Duration::from_millis(parse_positive(arg, iter.next())?));
+         },
+         "--max-memory" => {
+            limits.max_allocated_bytes = Some(parse_positive(arg, iter.next())?);
+         },
+         "--iterations" => {
+            let value = iter.next().ok_or_else(|| UsageError::new("Missing value for '--iterations'.".to_owned()))?;
+            match value.parse::<usize>() {
+               Ok(iterations) if iterations > 0 => { opt_iterations = Some(iterations); },
+               _ => { return Err(UsageError::new(format!("Invalid value '{}' for '--iterations': expected a positive integer.", value))); }
+            }
+         },
+         "-e" | "--eval" => {
+            let code = iter.next().ok_or_else(|| UsageError::new(format!("Missing code for '{}'.", arg)))?;
+            return script(Source::Eval(code.to_owned()), iter.cloned().collect(), bench, opt_dump);
+         },
+         "-" => {
+            return script(Source::Stdin, iter.cloned().collect(), bench, opt_dump);
+         },
+         "--" => {
+            let path = iter.next().ok_or_else(|| UsageError::new("Missing script after '--'.".to_owned()))?;
+            return script(Source::File(path.to_owned()), iter.cloned().collect(), bench, opt_dump);
+         },
+         _ if arg.starts_with('-') => {
+            return Err(UsageError::new(format!("Unknown option '{}'.", arg)));
+         },
+         _ => {
+            return script(Source::File(arg.to_owned()), iter.cloned().collect(), bench, opt_dump);
+         }
+      }
+   }
+   if opt_dump.is_some() {
+      return Err(UsageError::new("Missing script to dump.".to_owned()));
+   }
+   match (bench, opt_iterations) {
+      (true, iterations) => Ok(Command::Bench(iterations.unwrap_or(1))),
+      (false, Some(_))   => Err(UsageError::new("'--iterations' can only be used with '--bench'.".to_owned())),
+      (false, None)      => Ok(Command::Repl),
+   }
+}
+
+fn parse_error_format(value: &str) -> Result<ErrorFormat, UsageError>
+{
+   match value {
+      "human" => Ok(ErrorFormat::Human),
+      "json"  => Ok(ErrorFormat::Json),
+      _       => Err(UsageError::new(format!("Invalid value '{}' for '--error-format': expected 'human' or 'json'.", value))),
+   }
+}
+
+fn parse_sandbox(value: &str) -> Result<Sandbox, UsageError>
+{
+   match value {
+      "pure" => Ok(Sandbox::Pure),
+      "standard" => Ok(Sandbox::Standard),
+      "full" => Ok(Sandbox::Full),
+      _ if value.starts_with("read-only:") && value.len() > "read-only:".len() => Ok(Sandbox::ReadOnly(PathBuf::from(&value["read-only:".len()..]))),
+      _ => Err(UsageError::new(format!("Invalid value '{}' for '--sandbox': expected 'pure', 'standard', 'read-only:DIR' or 'full'.", value))),
+   }
+}
+
+fn parse_positive<T: FromStr + Default + PartialOrd>(option: &str, opt_value: Option<&String>) -> Result<T, UsageError>
+{
+   let value = opt_value.ok_or_else(|| UsageError::new(format!("Missing value for '{}'.", option)))?;
+   match value.parse::<T>() {
+      Ok(number) if number > T::default() => Ok(number),
+      _ => Err(UsageError::new(format!("Invalid value '{}' for '{}': expected a positive integer.", value, option))),
+   }
+}
+
+fn script(source: Source, script_args: Vec<String>, bench: bool, opt_dump: Option<Dump>) -> Result<Command, UsageError>
+{
+   if bench {
+      return Err(UsageError::new("'--bench' cannot be combined with a script.".to_owned()));
+   }
+   match opt_dump {
+      Some(dump) => Ok(Command::Dump(dump, source)),
+      None       => Ok(Command::Run(source, script_args)),
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use std::{path::PathBuf, time::Duration};
+
+   use crate::{error::ErrorFormat, interpreter::{limits::Limits, sandbox::Sandbox}};
+
+   use super::{parse_args, Arguments, Command, Dump, Source, UsageError};
+
+   fn parse(args: &[&str]) -> Result<Command, UsageError>
+   {
+      parse_all(args).map(|arguments| arguments.command)
+   }
+
+   fn parse_all(args: &[&str]) -> Result<Arguments, UsageError>
+   {
+      let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+      parse_args(&args)
+   }
+
+   fn strings(args: &[&str]) -> Vec<String>
+   {
+      args.iter().map(|arg| arg.to_string()).collect()
+   }
+
+   #[test]
+   fn test_parse_args() {
+      assert_eq!(parse(&[]), Ok(Command::Repl));
+      assert_eq!(parse(&["--help"]), Ok(Command::Help));
+      assert_eq!(parse(&["-V"]), Ok(Command::Version));
+      assert_eq!(parse(&["script.lox"]), Ok(Command::Run(Source::File("script.lox".to_owned()), vec![])));
+      assert_eq!(parse(&["script.lox", "a", "--help"]), Ok(Command::Run(Source::File("script.lox".to_owned()), strings(&["a", "--help"]))));
+      assert_eq!(parse(&["--", "-script.lox", "a"]), Ok(Command::Run(Source::File("-script.lox".to_owned()), strings(&["a"]))));
+      assert_eq!(parse(&["-", "a", "b"]), Ok(Command::Run(Source::Stdin, strings(&["a", "b"]))));
+      assert_eq!(parse(&["-e", "print 1;"]), Ok(Command::Run(Source::Eval("print 1;".to_owned()), vec![])));
+      assert_eq!(parse(&["--eval", "print args(0);", "x"]), Ok(Command::Run(Source::Eval("print args(0);".to_owned()), strings(&["x"]))));
+      assert_eq!(parse(&["--bench"]), Ok(Command::Bench(1)));
+      assert_eq!(parse(&["--bench", "--iterations", "5"]), Ok(Command::Bench(5)));
+      assert_eq!(parse(&["--iterations", "5", "--bench"]), Ok(Command::Bench(5)));
+      assert_eq!(parse(&["--tokens", "script.lox"]), Ok(Command::Dump(Dump::Tokens, Source::File("script.lox".to_owned()))));
+      assert_eq!(parse(&["--cst", "script.lox"]), Ok(Command::Dump(Dump::Cst, Source::File("script.lox".to_owned()))));
+      assert_eq!(parse(&["--ast", "-e", "print 1;"]), Ok(Command::Dump(Dump::Ast, Source::Eval("print 1;".to_owned()))));
+      assert_eq!(parse(&["--resolve", "-"]), Ok(Command::Dump(Dump::Resolve, Source::Stdin)));
+   }
+
+   #[test]
+   fn test_parse_args_errors() {
+      assert!(parse(&["--unknown"]).is_err());
+      assert!(parse(&["-e"]).is_err());
+      assert!(parse(&["--iterations", "5"]).is_err());
+      assert!(parse(&["--bench", "--iterations", "0"]).is_err());
+      assert!(parse(&["--bench", "--iterations"]).is_err());
+      assert!(parse(&["--bench", "script.lox"]).is_err());
+      assert!(parse(&["--tokens"]).is_err());
+      assert!(parse(&["--tokens", "--ast", "script.lox"]).is_err());
+      assert!(parse(&["--cst", "--resolve", "script.lox"]).is_err());
+      assert!(parse(&["--error-format=xml", "script.lox"]).is_err());
+      assert!(parse(&["--error-format"]).is_err());
+   }
+
+   #[test]
+   fn test_parse_error_format() {
+      assert_eq!(parse_all(&["script.lox"]).unwrap().error_format, ErrorFormat::Human);
+      assert_eq!(parse_all(&["--error-format=json", "script.lox"]).unwrap().error_format, ErrorFormat::Json);
+      assert_eq!(parse_all(&["--error-format", "json", "--ast", "-"]), Ok(Arguments { command: Command::Dump(Dump::Ast, Source::Stdin), error_format: ErrorFormat::Json, limits: Limits::default(), sandbox: Sandbox::Standard }));
+      assert_eq!(parse_all(&["script.lox", "--error-format=json"]).unwrap().error_format, ErrorFormat::Human);
+      assert_eq!(parse_all(&["--error-format=json", "--unknown"]).unwrap_err().error_format, ErrorFormat::Json);
+      assert_eq!(parse_all(&["--unknown", "--error-format=json"]).unwrap_err().error_format, ErrorFormat::Human);
+   }
+
+   #[test]
+   fn test_parse_limits() {
+      assert_eq!(parse_all(&["script.lox"]).unwrap().limits, Limits::default());
+      let limits = parse_all(&["--max-call-depth", "10", "--max-instructions", "1000", "--timeout", "500", "--max-memory", "4096", "script.lox"]).unwrap().limits;
+      assert_eq!(limits, Limits { max_call_depth: 10, max_instructions: Some(1000), timeout: Some(Duration::from_millis(500)), max_allocated_bytes: Some(4096) });
+      assert!(parse(&["--timeout", "0", "script.lox"]).is_err());
+      assert!(parse(&["--max-memory", "lots", "script.lox"]).is_err());
+      assert!(parse(&["--max-instructions"]).is_err());
+   }
+
+   #[test]
+   fn test_parse_sandbox() {
+      assert_eq!(parse_all(&["script.lox"]).unwrap().sandbox, Sandbox::Standard);
+      assert_eq!(parse_all(&["--sandbox", "pure", "script.lox"]).unwrap().sandbox, Sandbox::Pure);
+      assert_eq!(parse_all(&["--sandbox", "full", "script.lox"]).unwrap().sandbox, Sandbox::Full);
+      assert_eq!(parse_all(&["--sandbox", "read-only:data", "script.lox"]).unwrap().sandbox, Sandbox::ReadOnly(PathBuf::from("data")));
+      assert!(parse(&["--sandbox", "read-only:", "script.lox"]).is_err());
+      assert!(parse(&["--sandbox", "none", "script.lox"]).is_err());
+      assert!(parse(&["--sandbox"]).is_err());
+   }
+}
@@ -1,47 +1,187 @@
 use std::error::Error;
 use std::fmt;
 
-use crate::parser::position::Position;
+use crate::parser::{position::Span, source_map::line_text};
+
+/// Number of innermost and outermost stack frames shown when rendering a long stack trace.
+const RENDERED_FRAMES: usize = 8;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct LoxError
 {
     pub kind: LoxErrorKind,
-    pub position: Option<Position>
+    /// The source code that caused the error.
+    pub span: Option<Span>,
+    /// An optional hint on how to fix the error, eg. "did you mean `init`?".
+    pub help: Option<String>,
+    /// The Lox functions active when a runtime error was raised, from the innermost to the outermost one.
+    pub stack_trace: Vec<StackFrame>
+}
+
+/// A function active when a runtime error was raised, along with the code it was executing.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StackFrame
+{
+    /// The name of the function, `None` for the top level code of the script.
+    pub function: Option<String>,
+    pub span: Span
+}
+
+impl fmt::Display for StackFrame
+{
+    /// Formats the frame like clox does, eg. `[line 3] in foo()` or `[line 7] in script`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match &self.function {
+            Some(function) => write!(f, "[line {}] in {}()", self.span.start.line, function),
+            None           => write!(f, "[line {}] in script", self.span.start.line),
+        }
+    }
 }
 
 impl LoxError
 {
     pub const fn internal_error(kind: InternalErrorKind) -> Self
     {
-        Self { kind: LoxErrorKind::Internal(kind), position: None }
+        Self { kind: LoxErrorKind::Internal(kind), span: None, help: None, stack_trace: Vec::new() }
+    }
+
+    pub const fn parser_error(kind: ParserErrorKind, span: Span) -> Self
+    {
+        Self { kind: LoxErrorKind::Parser(kind), span: Some(span), help: None, stack_trace: Vec::new() }
+    }
+
+    pub const fn resolver_error(kind: ResolverErrorKind, span: Span) -> Self
+    {
+        Self { kind: LoxErrorKind::Resolver(kind), span: Some(span), help: None, stack_trace: Vec::new() }
+    }
+
+    pub const fn interpreter_error(kind: InterpreterErrorKind, span: Span) -> Self
+    {
+        Self { kind: LoxErrorKind::Interpreter(kind), span: Some(span), help: None, stack_trace: Vec::new() }
+    }
+
+    pub const fn input_error(kind: InputErrorKind) -> Self
+    {
+        Self { kind: LoxErrorKind::Input(kind), span: None, help: None, stack_trace: Vec::new() }
     }
 
-    pub const fn parser_error(kind: ParserErrorKind, position: Position) -> Self
+    /// Attaches a help note to the error.
+    pub fn with_help(mut self, help: impl Into<String>) -> Self
     {
-        Self { kind: LoxErrorKind::Parser(kind), position: Some(position) }
+        self.help = Some(help.into());
+        self
     }
 
-    pub const fn resolver_error(kind: ResolverErrorKind, position: Position) -> Self
+    /// Returns the error as a single line JSON object, eg.
+    /// `{"stage":"parser","code":"E0106","message":"Expect expression","line":1,"column":7,"span":{"start":{"line":1,"column":7,"offset":6},"end":{"line":1,"column":8,"offset":7}},"help":null,"stack_trace":[]}`.
+    ///
+    /// The `offset` of the span positions is their byte offset in the `SourceMap` of the session.
+    /// `line`, `column` and `span` are `null` when the error has no position. Each frame of `stack_trace` is an object like `{"function":"foo","line":3,"column":5}`, with a `null` function for the top level code.
+    pub fn to_json(&self) -> String
     {
-        Self { kind: LoxErrorKind::Resolver(kind), position: Some(position) }
+        let location = match self.span {
+            Some(span) => {
+                format!("\"line\":{},\"column\":{},\"span\":{{\"start\":{{\"line\":{},\"column\":{},\"offset\":{}}},\"end\":{{\"line\":{},\"column\":{},\"offset\":{}}}}}",
+                    span.start.line, span.start.column, span.start.line, span.start.column, span.start.offset, span.end.line, span.end.column, span.end.offset)
+            },
+            None => {
+                "\"line\":null,\"column\":null,\"span\":null".to_string()
+            }
+        };
+        let help = self.help.as_deref().map_or_else(|| "null".to_string(), json_string);
+        let stack_trace: Vec<String> = self.stack_trace.iter().map(|frame| {
+            let function = frame.function.as_deref().map_or_else(|| "null".to_string(), json_string);
+            format!("{{\"function\":{},\"line\":{},\"column\":{}}}", function, frame.span.start.line, frame.span.start.column)
+        }).collect();
+        format!("{{\"stage\":\"{}\",\"code\":\"{}\",\"message\":{},{},\"help\":{},\"stack_trace\":[{}]}}",
+            self.kind.stage(), self.kind.code(), json_string(&self.kind.message()), location, help, stack_trace.join(","))
     }
 
-    pub const fn interpreter_error(kind: InterpreterErrorKind, position: Position) -> Self
+    /// Returns a multi line description of the error that shows the offending line of `source` with the span underlined, eg.
+    /// ```text
+    /// error[E0106]: Expect expression
+    ///  --> 1:10
+    ///   |
+    /// 1 | print 1 +;
+    ///   |          ^
+    /// ```
+    /// followed by `  = help: ...` when the error has a help note and by the stack trace of runtime errors raised inside a function, one frame per line
+    /// (the middle of very long traces is omitted).
+    /// A span covering several lines is underlined up to the end of its first line.
+    pub fn render(&self, source: &str) -> String
     {
-        Self { kind: LoxErrorKind::Interpreter(kind), position: Some(position) }
+        let mut output = format!("error[{}]: {}\n", self.kind.code(), self.kind.message());
+        let gutter = match self.span {
+            Some(span) => {
+                let line_number = span.start.line.to_string();
+                let gutter = " ".repeat(line_number.len());
+                let line = line_text(source, span.start.line).unwrap_or("");
+                let start = (span.start.column as usize).saturating_sub(1);
+                let end = if span.end.line == span.start.line { (span.end.column as usize).saturating_sub(1) } else { line.chars().count() };
+                //copy the tabs of the source line so that the underline stays aligned
+                let padding: String = line.chars().chain(std::iter::repeat(' ')).take(start).map(|ch| if ch == '\t' { '\t' } else { ' ' }).collect();
+                output.push_str(&format!("{}--> {}:{}\n", gutter, span.start.line, span.start.column));
+                output.push_str(&format!("{} |\n", gutter));
+                output.push_str(&format!("{} | {}\n", line_number, line));
+                output.push_str(&format!("{} | {}{}\n", gutter, padding, "^".repeat(end.saturating_sub(start).max(1))));
+                gutter
+            },
+            None => String::new()
+        };
+        if let Some(help) = &self.help {
+            output.push_str(&format!("{} = help: {}\n", gutter, help));
+        }
+        if self.stack_trace.iter().any(|frame| frame.function.is_some()) {
+            if self.stack_trace.len() > 2 * RENDERED_FRAMES {
+                //deep recursions (eg. a stack overflow) only show the innermost and outermost frames
+                let omitted = self.stack_trace.len() - 2 * RENDERED_FRAMES;
+                for frame in &self.stack_trace[..RENDERED_FRAMES] {
+                    output.push_str(&format!("{}\n", frame));
+                }
+                output.push_str(&format!("[... {} more frames ...]\n", omitted));
+                for frame in &self.stack_trace[self.stack_trace.len() - RENDERED_FRAMES..] {
+                    output.push_str(&format!("{}\n", frame));
+                }
+            } else {
+                for frame in &self.stack_trace {
+                    output.push_str(&format!("{}\n", frame));
+                }
+            }
+        }
+        output
     }
 }
 
+/// Quotes and escapes a string as a JSON string literal.
+fn json_string(value: &str) -> String
+{
+    let mut json = String::with_capacity(value.len() + 2);
+    json.push('"');
+    for ch in value.chars() {
+        match ch {
+            '"'  => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => json.push(ch),
+        }
+    }
+    json.push('"');
+    json
+}
+
 impl Error for LoxError {}
 
 impl fmt::Display for LoxError
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
     {
-        match self.position {
-            Some(position) => {
-                write!(f, "[line {}] {}", position.line, self.kind)
+        match self.span {
+            Some(span) => {
+                write!(f, "[line {}] {}", span.start.line, self.kind)
             },
             None => {
                 write!(f, "[line N/A] {}", self.kind)
@@ -56,7 +196,48 @@ pub enum LoxErrorKind
     Parser(ParserErrorKind),
     Interpreter(InterpreterErrorKind),
     Resolver(ResolverErrorKind),
-    Internal(InternalErrorKind)
+    Internal(InternalErrorKind),
+    Input(InputErrorKind)
+}
+
+impl LoxErrorKind
+{
+    /// Returns the name of the stage of the execution where the error was raised.
+    pub const fn stage(&self) -> &'static str
+    {
+        match self {
+            Self::Parser(_)      => "parser",
+            Self::Resolver(_)    => "resolver",
+            Self::Interpreter(_) => "runtime",
+            Self::Internal(_)    => "internal",
+            Self::Input(_)       => "input",
+        }
+    }
+
+    /// Returns a stable code identifying the error variant: `E01xx` for parser errors, `E02xx` for resolver errors,
+    /// `E03xx` for runtime errors, `E04xx` for errors reading the script and `E09xx` for internal errors. Codes are never reused.
+    pub const fn code(&self) -> &'static str
+    {
+        match self {
+            Self::Parser(kind)      => kind.code(),
+            Self::Resolver(kind)    => kind.code(),
+            Self::Interpreter(kind) => kind.code(),
+            Self::Internal(kind)    => kind.code(),
+            Self::Input(kind)       => kind.code(),
+        }
+    }
+
+    /// Returns the error message, without the stage.
+    pub fn message(&self) -> String
+    {
+        match self {
+            Self::Parser(kind)      => kind.to_string(),
+            Self::Resolver(kind)    => kind.to_string(),
+            Self::Interpreter(kind) => kind.to_string(),
+            Self::Internal(kind)    => kind.to_string(),
+            Self::Input(kind)       => kind.to_string(),
+        }
+    }
 }
 
 impl fmt::Display for LoxErrorKind
@@ -75,6 +256,9 @@ impl fmt::Display for LoxErrorKind
             },
             Self::Internal(error) => {
                 write!(f, "Internal error: {}", error)
+            },
+            Self::Input(error) => {
+                write!(f, "Input error: {}", error)
             }
         }
     }
@@ -84,7 +268,21 @@ impl fmt::Display for LoxErrorKind
 pub enum InternalErrorKind
 {
     ExpectedBlock,
-    ExpectToken
+    ExpectToken,
+    /// The code run by a session no longer fits in the offsets of its `SourceMap`.
+    SourceMapFull
+}
+
+impl InternalErrorKind
+{
+    pub const fn code(&self) -> &'static str
+    {
+        match self {
+            Self::ExpectedBlock => "E0901",
+            Self::ExpectToken   => "E0902",
+            Self::SourceMapFull => "E0903",
+        }
+    }
 }
 
 impl fmt::Display for InternalErrorKind
@@ -94,6 +292,41 @@ impl fmt::Display for InternalErrorKind
         match self {
             Self::ExpectedBlock => write!(f, "Expected block found something else."),
             Self::ExpectToken   => write!(f, "Expected token."),
+            Self::SourceMapFull => write!(f, "Too much code for the source map of the session."),
+        }
+    }
+}
+
+/// Error reading the code of a script, before running it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InputErrorKind
+{
+    CannotReadFile(String),
+    CannotReadInput(String),
+    /// Wrong command line arguments, reported before running anything.
+    InvalidUsage(String)
+}
+
+impl InputErrorKind
+{
+    pub const fn code(&self) -> &'static str
+    {
+        match self {
+            Self::CannotReadFile(_)  => "E0401",
+            Self::CannotReadInput(_) => "E0402",
+            Self::InvalidUsage(_)    => "E0403",
+        }
+    }
+}
+
+impl fmt::Display for InputErrorKind
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self {
+            Self::CannotReadFile(error)  => write!(f, "Cannot read file: {}", error),
+            Self::CannotReadInput(error) => write!(f, "Cannot read input: {}", error),
+            Self::InvalidUsage(error)    => write!(f, "{}", error),
         }
     }
 }
@@ -111,6 +344,23 @@ pub enum ResolverErrorKind
     CantUseSuperWithoutSuperClass
 }
 
+impl ResolverErrorKind
+{
+    pub const fn code(&self) -> &'static str
+    {
+        match self {
+            Self::LocalVariableNotFound(_)      => "E0201",
+            Self::VariableAlreadyExists(_)      => "E0202",
+            Self::ReturnFromTopLevelCode        => "E0203",
+            Self::InvalidThisUsage              => "E0204",
+            Self::ReturnFromInitializer         => "E0205",
+            Self::ClassCantInheritFromItslef    => "E0206",
+            Self::CantUseSuperOutsideClass      => "E0207",
+            Self::CantUseSuperWithoutSuperClass => "E0208",
+        }
+    }
+}
+
 impl fmt::Display for ResolverErrorKind
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
@@ -143,7 +393,42 @@ pub enum InterpreterErrorKind
     InvalidPlusOperands,
     OnlyInstancesHaveProperties,
     OnlyInstancesHaveFields,
-    UndefinedVariable(String)
+    UndefinedVariable(String),
+    NativeFunctionError(String),
+    StackOverflow,
+    InstructionLimitExceeded,
+    Timeout,
+    MemoryLimitExceeded,
+    Interrupted,
+    NativeNotAllowed(String, &'static str)
+}
+
+impl InterpreterErrorKind
+{
+    pub const fn code(&self) -> &'static str
+    {
+        match self {
+            Self::CheckNumberOperands           => "E0301",
+            Self::CheckNumberOperand            => "E0302",
+            Self::NotCallable                   => "E0303",
+            Self::WrongArity(_, _)              => "E0304",
+            Self::NativeClockSysTimeError       => "E0305",
+            Self::UdefinedProperty(_)           => "E0306",
+            Self::AssertionFailure              => "E0307",
+            Self::SuperclassMustBeAClass        => "E0308",
+            Self::InvalidPlusOperands           => "E0309",
+            Self::OnlyInstancesHaveProperties   => "E0310",
+            Self::OnlyInstancesHaveFields       => "E0311",
+            Self::UndefinedVariable(_)          => "E0312",
+            Self::NativeFunctionError(_)        => "E0313",
+            Self::StackOverflow                 => "E0314",
+            Self::InstructionLimitExceeded      => "E0315",
+            Self::Timeout                       => "E0316",
+            Self::MemoryLimitExceeded           => "E0317",
+            Self::Interrupted                   => "E0318",
+            Self::NativeNotAllowed(_, _)        => "E0319",
+        }
+    }
 }
 
 impl fmt::Display for InterpreterErrorKind
@@ -154,6 +439,13 @@ impl fmt::Display for InterpreterErrorKind
             //custom
             Self::NativeClockSysTimeError               => write!(f, "System time error calling clock()."),
             Self::AssertionFailure                      => write!(f, "Assertion failure."),
+            Self::NativeFunctionError(message)          => write!(f, "{}", message),
+            Self::StackOverflow                         => write!(f, "Stack overflow."),
+            Self::InstructionLimitExceeded              => write!(f, "Instruction limit exceeded."),
+            Self::Timeout                               => write!(f, "Execution timed out."),
+            Self::MemoryLimitExceeded                   => write!(f, "Memory limit exceeded."),
+            Self::Interrupted                           => write!(f, "Interrupted."),
+            Self::NativeNotAllowed(name, sandbox)       => write!(f, "Native function '{}' is not allowed in the '{}' sandbox.", name, sandbox),
             //book
             Self::SuperclassMustBeAClass                => write!(f, "Superclass must be a class."),
             Self::InvalidPlusOperands                   => write!(f, "Operands must be two numbers or two strings."),
@@ -175,8 +467,6 @@ pub enum ParserErrorKind
     UnexpectedToken(char),
     UnterminatedString,
     InvalidAssignmentTarget,
-    ParseFloatError(String),
-    InvalidEscapeCharacter,
     ExpectedExpression,
     ExpectedToken(String),
     BreakOutsideLoop,
@@ -184,6 +474,33 @@ pub enum ParserErrorKind
     ExpectedIdentifier(String),
     TooManyArguments,
     TooManyParameters,
+    UnterminatedComment,
+    InvalidNumber(String),
+    InvalidEscapeSequence(String),
+    InvalidRawString,
+}
+
+impl ParserErrorKind
+{
+    pub const fn code(&self) -> &'static str
+    {
+        match self {
+            Self::UnexpectedToken(_)        => "E0101",
+            Self::UnterminatedString        => "E0102",
+            Self::InvalidAssignmentTarget   => "E0103",
+            Self::ExpectedExpression        => "E0106",
+            Self::ExpectedToken(_)          => "E0107",
+            Self::BreakOutsideLoop          => "E0108",
+            Self::ContinueOutsideLoop       => "E0109",
+            Self::ExpectedIdentifier(_)     => "E0110",
+            Self::TooManyArguments          => "E0111",
+            Self::TooManyParameters         => "E0112",
+            Self::UnterminatedComment       => "E0114",
+            Self::InvalidNumber(_)          => "E0115",
+            Self::InvalidEscapeSequence(_)  => "E0116",
+            Self::InvalidRawString          => "E0117",
+        }
+    }
 }
 
 impl fmt::Display for ParserErrorKind
@@ -193,8 +510,11 @@ impl fmt::Display for ParserErrorKind
         match &self {
             Self::UnexpectedToken(ch)               => write!(f, "Unexpected token '{}'.", ch),
             Self::UnterminatedString                => write!(f, "Unterminated string."),
+            Self::UnterminatedComment               => write!(f, "Unterminated block comment."),
+            Self::InvalidNumber(reason)             => write!(f, "Invalid number: {}.", reason),
+            Self::InvalidEscapeSequence(reason)     => write!(f, "Invalid escape sequence: {}.", reason),
+            Self::InvalidRawString                  => write!(f, "Expect '\"' after the '#' of a raw string."),
             Self::InvalidAssignmentTarget           => write!(f, "Invalid assignment target."),
-            Self::ParseFloatError(value)            => write!(f, "Cannot parse float '{}'.", value),
             Self::ExpectedExpression                => write!(f, "Expect expression"),
             Self::ExpectedIdentifier(message)       => write!(f, "{}", message),
             Self::ExpectedToken(message)            => write!(f, "{}", message),
@@ -202,12 +522,93 @@ impl fmt::Display for ParserErrorKind
             Self::TooManyParameters                 => write!(f, "Can't have more than 255 parameters."),
             Self::BreakOutsideLoop                  => write!(f, "Can't use 'break' outside of a loop."),
             Self::ContinueOutsideLoop               => write!(f, "Can't use 'continue' outside of a loop."),
-            Self::InvalidEscapeCharacter            => write!(f, "Invalid escape character."),
         }
     }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum ExecutionResult {
-    ParserError, ResolverError, RuntimeError, CannotReadFile
- }
\ No newline at end of file
+    ParserError, ResolverError, RuntimeError, CannotReadFile, InvalidUsage
+ }
+
+/// Format used to report the errors.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ErrorFormat
+{
+    /// The offending source line with the error underlined, see `LoxError::render`.
+    #[default]
+    Human,
+    /// One JSON object per line, see `LoxError::to_json`.
+    Json
+}
+
+/// Exit code for a wrong command line usage (sysexits.h `EX_USAGE`).
+pub const EXIT_USAGE: i32 = 64;
+
+impl ExecutionResult
+{
+    /// Returns the process exit code for the failure, following the sysexits.h conventions used by the reference implementations of Lox:
+    /// 65 (`EX_DATAERR`) for compile errors, 70 (`EX_SOFTWARE`) for runtime errors, 66 (`EX_NOINPUT`) for unreadable input and 64 (`EX_USAGE`) for wrong arguments.
+    pub const fn exit_code(&self) -> i32
+    {
+        match self {
+            Self::ParserError | Self::ResolverError => 65,
+            Self::RuntimeError                      => 70,
+            Self::CannotReadFile                    => 66,
+            Self::InvalidUsage                      => EXIT_USAGE,
+        }
+    }
+}
+
+impl From<&LoxError> for ExecutionResult
+{
+    /// Maps an error to the stage of the execution where it was raised.
+    fn from(error: &LoxError) -> Self
+    {
+        match error.kind {
+            LoxErrorKind::Parser(_) | LoxErrorKind::Internal(_)  => ExecutionResult::ParserError,
+            LoxErrorKind::Resolver(_)                            => ExecutionResult::ResolverError,
+            LoxErrorKind::Interpreter(_)                         => ExecutionResult::RuntimeError,
+            LoxErrorKind::Input(InputErrorKind::InvalidUsage(_)) => ExecutionResult::InvalidUsage,
+            LoxErrorKind::Input(_)                               => ExecutionResult::CannotReadFile,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::position::{Position, Span};
+
+    use super::{InterpreterErrorKind, LoxError, LoxErrorKind, ParserErrorKind, StackFrame};
+
+    #[test]
+    fn test_to_json() {
+        let span = Span::new(Position { line: 2, column: 3, offset: 14 }, Position { line: 2, column: 6, offset: 17 });
+        let error = LoxError::interpreter_error(InterpreterErrorKind::NativeFunctionError("a \"quoted\"\\path\n".to_owned()), span);
+        assert_eq!(error.to_json(), r#"{"stage":"runtime","code":"E0313","message":"a \"quoted\"\\path\n","line":2,"column":3,"span":{"start":{"line":2,"column":3,"offset":14},"end":{"line":2,"column":6,"offset":17}},"help":null,"stack_trace":[]}"#);
+        let stack_trace = vec![StackFrame { function: Some("f".to_owned()), span }, StackFrame { function: None, span }];
+        let error = LoxError { kind: LoxErrorKind::Interpreter(InterpreterErrorKind::NotCallable), span: None, help: Some("try `f()`".to_owned()), stack_trace };
+        assert_eq!(error.to_json(), r#"{"stage":"runtime","code":"E0303","message":"Can only call functions and classes.","line":null,"column":null,"span":null,"help":"try `f()`","stack_trace":[{"function":"f","line":2,"column":3},{"function":null,"line":2,"column":3}]}"#);
+    }
+
+    #[test]
+    fn test_render() {
+        let source = "var a = 1;\n\tprint a +\n  b;";
+        let span = Span::new(Position { line: 2, column: 8, offset: 18 }, Position { line: 3, column: 4, offset: 25 });
+        let error = LoxError::interpreter_error(InterpreterErrorKind::InvalidPlusOperands, span).with_help("use `str()`");
+        assert_eq!(error.render(source), "error[E0309]: Operands must be two numbers or two strings.\n --> 2:8\n  |\n2 | \tprint a +\n  | \t      ^^^\n  = help: use `str()`\n");
+        let span = Span::new(Position { line: 1, column: 9, offset: 8 }, Position { line: 1, column: 9, offset: 8 });
+        let error = LoxError::parser_error(ParserErrorKind::ExpectedExpression, span);
+        assert_eq!(error.render("print 1 "), "error[E0106]: Expect expression\n --> 1:9\n  |\n1 | print 1 \n  |         ^\n");
+        let error = LoxError { kind: LoxErrorKind::Interpreter(InterpreterErrorKind::NotCallable), span: None, help: None, stack_trace: Vec::new() };
+        assert_eq!(error.render(source), "error[E0303]: Can only call functions and classes.\n");
+        //a lone '\r' ends a line too
+        let span = Span::new(Position { line: 2, column: 7, offset: 17 }, Position { line: 2, column: 8, offset: 18 });
+        let error = LoxError::parser_error(ParserErrorKind::ExpectedExpression, span);
+        assert_eq!(error.render("var a;\rprint +;\r\n"), "error[E0106]: Expect expression\n --> 2:7\n  |\n2 | print +;\n  |       ^\n");
+        //a zero column does not underflow
+        let span = Span::new(Position { line: 1, column: 0, offset: 0 }, Position { line: 1, column: 0, offset: 0 });
+        let error = LoxError::parser_error(ParserErrorKind::ExpectedExpression, span);
+        assert_eq!(error.render("print +;"), "error[E0106]: Expect expression\n --> 1:0\n  |\n1 | print +;\n  | ^\n");
+    }
+}
@@ -56,11 +56,27 @@ impl Environment {
         }
     }
 
+    /// Returns the names of all the variables visible from this environment.
+    pub fn names(&self) -> Vec<IdentifierSymbol>
+    {
+        let mut names: Vec<IdentifierSymbol> = self.scope.keys().copied().collect();
+        if let Some(enclosing) = &self.opt_enclosing {
+            names.extend(enclosing.borrow().names());
+        }
+        names
+    }
+
     pub fn define_variable(&mut self, name: IdentifierSymbol, value: Value)
     {
         self.scope.insert(name, value);
     }
 
+    /// Removes a variable from this scope only, returning its value.
+    pub fn remove(&mut self, name: &IdentifierSymbol) -> Option<Value>
+    {
+        self.scope.remove(name)
+    }
+
     fn ancestor(&self, distance: usize) -> Rc<RefCell<Self>> {
         let mut environment: Rc<RefCell<Environment>> = self.opt_enclosing.as_ref().map(Rc::clone).expect("Initial environment must have an enclosing");
         for _ in 1..distance {
@@ -93,6 +109,7 @@ impl Environment {
 }
 
 #[cfg(test)]
+#[allow(clippy::items_after_test_module)]
 mod tests {
     use std::rc::Rc;
 
@@ -165,6 +182,7 @@ pub fn _count_enclosing(env: &Environment) -> usize {
 
 }
 
+#[allow(clippy::single_match)]
 pub fn _print_env(env: &Rc<RefCell<Environment>>, string_interner: &StringInterner) -> usize {
     let mut count = 0;
     let mut current = env.borrow().opt_enclosing.as_ref().map(Rc::clone);
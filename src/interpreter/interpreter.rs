@@ -1,71 +1,290 @@
-use std::{fmt::Debug, rc::Rc, cell::RefCell, io::Write};
+use std::{fmt::Debug, rc::Rc, cell::RefCell, io::Write, mem::size_of, sync::{atomic::{AtomicBool, Ordering}, Arc}};
 
 use rustc_hash::FxHashMap;
 use string_interner::StringInterner;
 
-use crate::{alias::{ExprId, IdentifierSymbol, SideTable}, error::{ExecutionResult, InterpreterErrorKind, LoxError}, parser::{position::Position, types::{BinaryOperatorKind, Expr, ExprKind, Literal, LogicalOperatorKind, Stmt, UnaryOperatorKind}}};
+use crate::{alias::{ExprId, IdentifierSymbol, SideTable}, error::{InterpreterErrorKind, LoxError, LoxErrorKind, StackFrame}, parser::{position::Span, types::{BinaryOperatorKind, Expr, ExprKind, Literal, LogicalOperatorKind, Stmt, StmtKind, UnaryOperatorKind}}, utils::{native_stack::ensure_sufficient_stack, suggest::did_you_mean}};
 
-use super::{environment::Environment, native_functions::{assert_eq, clock}, types::{LoxClass, LoxFunction, LoxInstance, Value}};
+use super::{environment::Environment, limits::{Limits, Usage}, native_functions::{builtins, Arity, NativeContext, NativeFunction}, sandbox::Sandbox, types::{LoxClass, LoxFunction, LoxInstance, Value}};
 
-pub struct Interpreter<'a, T:Write>
+/// Runtime errors are boxed while they are propagated, so that the results passed around by the interpreter stay small.
+type RuntimeResult<V> = Result<V, Box<LoxError>>;
+
+/// Number of nested statements and expressions between two checks of the native stack (see `ensure_sufficient_stack`).
+const STACK_CHECK_INTERVAL: usize = 16;
+
+pub struct Interpreter<T:Write>
 {
-    string_interner:   &'a mut StringInterner,
+    string_interner:   StringInterner,
     side_table:        SideTable,
     global_scope:      Rc<RefCell<Environment>>,
     this_symbol:       IdentifierSymbol,
     init_symbol:       IdentifierSymbol,
     super_symbol:      IdentifierSymbol,
-    writer:            Rc<RefCell<T>>
+    writer:            Rc<RefCell<T>>,
+    frames:            Vec<CallFrame>,
+    /// Number of statements and expressions being evaluated, one inside the other.
+    nesting:           usize,
+    limits:            Limits,
+    usage:             Usage,
+    interrupt:         Arc<AtomicBool>,
+    sandbox:           Sandbox,
+    /// Native functions hidden by the sandbox, kept in case a later sandbox allows them.
+    denied_natives:    FxHashMap<IdentifierSymbol, Rc<dyn NativeFunction>>
+}
+
+/// A call to a Lox function that has not returned yet.
+struct CallFrame
+{
+    function: IdentifierSymbol,
+    /// The span of the call expression, `None` for calls made by the host application.
+    call_span: Option<Span>
 }
 
-impl <'a, T:Write> Interpreter<'a, T>
+impl <T:Write> Interpreter<T>
 {
-    pub fn new_with_writer(string_interner: &'a mut StringInterner, side_table: SideTable, writer: Rc<RefCell<T>>) -> Self
+    /// Creates a new interpreter taking ownership of the string interner and defines the native functions in the global scope.
+    pub fn new_with_writer(string_interner: StringInterner, side_table: SideTable, writer: Rc<RefCell<T>>) -> Self
     {
         let this_symbol  = string_interner.get("this").unwrap();
         let init_symbol  = string_interner.get("init").unwrap();
         let super_symbol = string_interner.get("super").unwrap();
-        Interpreter {
+        let mut interpreter = Interpreter {
             string_interner,
             side_table,
             global_scope: Environment::default(),
             this_symbol,
             init_symbol,
             super_symbol,
-            writer
+            writer,
+            frames: Vec::new(),
+            nesting: 0,
+            limits: Limits::default(),
+            usage: Usage::default(),
+            interrupt: Arc::new(AtomicBool::new(false)),
+            sandbox: Sandbox::default(),
+            denied_natives: FxHashMap::default()
+        };
+        interpreter.define_native_functions();
+        interpreter
+    }
+
+    /// Gives access to the string interner owned by the interpreter, so that new source code can be parsed against the same symbols.
+    pub fn string_interner_mut(&mut self) -> &mut StringInterner
+    {
+        &mut self.string_interner
+    }
+
+    pub fn string_interner(&self) -> &StringInterner
+    {
+        &self.string_interner
+    }
+
+    /// Merges the scope distances of newly resolved code into the side table used by the interpreter.
+    pub fn extend_side_table(&mut self, side_table: SideTable)
+    {
+        self.side_table.extend(side_table);
+    }
+
+    /// Sets the resource limits enforced while running code.
+    pub fn set_limits(&mut self, limits: Limits)
+    {
+        self.limits = limits;
+    }
+
+    /// Sets the profile deciding which native functions are available, then checks the native functions against it again.
+    ///
+    /// The natives the new sandbox denies are hidden, the ones hidden earlier and now allowed are restored unless the code has defined the same name meanwhile.
+    /// Any other global variable is left alone.
+    pub fn set_sandbox(&mut self, sandbox: Sandbox)
+    {
+        self.sandbox = sandbox;
+        let hidden: Vec<(IdentifierSymbol, Rc<dyn NativeFunction>)> = self.denied_natives.drain().collect();
+        let mut denied: Vec<IdentifierSymbol> = Vec::new();
+        {
+            let global_scope = self.global_scope.borrow();
+            for name in global_scope.names() {
+                if let Some(Value::Callable(Callable::Native(native))) = global_scope.get(&name) {
+                    // only the natives bound to their own name, a script can keep a native in a variable of its own
+                    if !self.sandbox.allows(native.capability()) && self.string_interner.get(native.name()) == Some(name) {
+                        denied.push(name);
+                    }
+                }
+            }
         }
+        for name in denied {
+            if let Some(Value::Callable(Callable::Native(native))) = self.global_scope.borrow_mut().remove(&name) {
+                self.denied_natives.insert(name, native);
+            }
+        }
+        for (name, native) in hidden {
+            if !self.sandbox.allows(native.capability()) {
+                self.denied_natives.insert(name, native);
+            } else if self.global_scope.borrow().get(&name).is_none() {
+                self.global_scope.borrow_mut().define_variable(name, Value::Callable(Callable::Native(native)));
+            }
+        }
+    }
+
+    /// Returns the flag that stops the running code when set, eg. from another thread or a Ctrl-C handler.
+    pub fn interrupt_handle(&self) -> Arc<AtomicBool>
+    {
+        Arc::clone(&self.interrupt)
+    }
+
+    /// Replaces the flag checked for interruptions, so that the same flag can be shared by more interpreters.
+    pub fn set_interrupt_handle(&mut self, interrupt: Arc<AtomicBool>)
+    {
+        self.interrupt = interrupt;
+    }
+
+    /// Raises an `Interrupted` runtime error if the interrupt flag is set, clearing it. Checked at loop back-edges and on each call.
+    #[inline]
+    fn check_interrupt(&self, span: impl Into<Option<Span>>) -> RuntimeResult<()>
+    {
+        if self.interrupt.load(Ordering::Relaxed) {
+            self.interrupt.store(false, Ordering::Relaxed);
+            return self.runtime_error(InterpreterErrorKind::Interrupted, span);
+        }
+        Ok(())
+    }
+
+    /// Defines (or redefines) a variable in the global scope.
+    pub fn define_global(&mut self, name: &str, value: Value)
+    {
+        let symbol = self.string_interner.get_or_intern(name);
+        self.global_scope.borrow_mut().define_variable(symbol, value);
+    }
+
+    /// Returns the value of a variable defined in the global scope.
+    pub fn get_global(&self, name: &str) -> Option<Value>
+    {
+        let symbol = self.string_interner.get(name)?;
+        self.global_scope.borrow().get(&symbol)
+    }
+
+    /// Calls a function, a class or a native function with arguments supplied by the host application.
+    ///
+    /// Errors raised outside of Lox code (eg. wrong number of arguments) carry no span.
+    pub fn call(&mut self, callee: &Value, args: Vec<Value>) -> Result<Value, LoxError>
+    {
+        self.usage = Usage::start(&self.limits);
+        let result = match callee
+        {
+            Value::Callable(callable) =>
+            {
+                match callable.arity(self.init_symbol) {
+                    Arity::Fixed(expected) if expected != args.len() => self.runtime_error(InterpreterErrorKind::WrongArity(expected, args.len()), None),
+                    _ => callable.call(self, args, None),
+                }
+            },
+            _ => {
+                self.runtime_error(InterpreterErrorKind::NotCallable, None)
+            }
+        };
+        result.map_err(|error| *error)
     }
 
     fn define_native_functions(&mut self) {
-        let clock_symbol     = self.string_interner.get_or_intern_static("clock");
-        let assert_eq_symbol = self.string_interner.get_or_intern_static("assertEq");
-        let str_symbol       = self.string_interner.get_or_intern_static("str");
-        self.global_scope.borrow_mut().define_variable(clock_symbol, Value::Callable(Callable::Clock));
-        self.global_scope.borrow_mut().define_variable(assert_eq_symbol, Value::Callable(Callable::AssertEq));
-        self.global_scope.borrow_mut().define_variable(str_symbol, Value::Callable(Callable::Str));
+        for native in builtins() {
+            self.define_native(native);
+        }
     }
 
-    /// Interpreter's entry point for running a program.
+    /// Defines a native function as a global variable named after it, if its capability is allowed by the sandbox.
     ///
-    /// Defines native functions and delegates the execution of all the statements to `execute_stmts`.
-    pub fn execute(&mut self, stmts: &[Stmt]) -> Result<(), ExecutionResult>
+    /// Otherwise a native function previously defined with the same name is removed and the name is remembered, so that using it raises a `NativeNotAllowed` error.
+    pub fn define_native(&mut self, native: Rc<dyn NativeFunction>)
     {
-        self.define_native_functions();
+        let symbol = self.string_interner.get_or_intern(native.name());
+        if self.sandbox.allows(native.capability()) {
+            self.denied_natives.remove(&symbol);
+            self.global_scope.borrow_mut().define_variable(symbol, Value::Callable(Callable::Native(native)));
+        } else {
+            self.denied_natives.insert(symbol, native);
+            let mut global_scope = self.global_scope.borrow_mut();
+            if let Some(Value::Callable(Callable::Native(_))) = global_scope.get(&symbol) {
+                global_scope.remove(&symbol);
+            }
+        }
+    }
 
+    /// Interpreter's entry point for running a program.
+    ///
+    /// Delegates the execution of all the statements to `execute_stmts`. Can be called more than once: the global scope is preserved between calls.
+    pub fn execute(&mut self, stmts: &[Stmt]) -> Result<(), LoxError>
+    {
         let environment = Rc::clone(&self.global_scope);
+        self.usage = Usage::start(&self.limits);
+        self.execute_stmts(stmts, &environment).map_err(|error| *error)?;
+        Ok(())
+    }
 
-        match self.execute_stmts(stmts, &environment)
+    /// Same as `execute`, but also prints the value of each top level expression statement, like a REPL does.
+    ///
+    /// Assignments are not echoed, nor are the calls returning `nil` (eg. `print`-like functions), so that only meaningful values show up.
+    pub fn execute_echoing(&mut self, stmts: &[Stmt]) -> Result<(), LoxError>
+    {
+        let environment = Rc::clone(&self.global_scope);
+        self.usage = Usage::start(&self.limits);
+        for stmt in stmts
         {
-            Ok(_) => {}
-            Err(_) => {
-                return Err(ExecutionResult::RuntimeError);
-            },
+            match &stmt.kind
+            {
+                StmtKind::Expr(expr) if !matches!(expr.kind, ExprKind::Assign(_) | ExprKind::Set(_)) =>
+                {
+                    self.usage.statement();
+                    let value = self.evaluate(expr, &environment).map_err(|error| *error)?;
+                    if !(matches!(expr.kind, ExprKind::Call(_)) && matches!(value, Value::Nil)) {
+                        self.write_line(&value.to_string(&self.string_interner));
+                    }
+                },
+                _ =>
+                {
+                    self.execute_stmt(stmt, &environment).map_err(|error| *error)?;
+                }
+            }
         }
         Ok(())
     }
 
+    /// Builds the runtime error that stops the execution, along with its stack trace. The error is propagated up to the caller of `execute`.
+    #[cold]
+    #[inline(never)]
+    fn runtime_error<V>(&self, kind: InterpreterErrorKind, span: impl Into<Option<Span>>) -> RuntimeResult<V>
+    {
+        self.runtime_error_with_help(kind, span, None)
+    }
+
+    /// Same as `runtime_error`, attaching a help note to the error.
+    #[cold]
+    #[inline(never)]
+    fn runtime_error_with_help<V>(&self, kind: InterpreterErrorKind, span: impl Into<Option<Span>>, help: Option<String>) -> RuntimeResult<V>
+    {
+        let span = span.into();
+        Err(Box::new(LoxError { kind: LoxErrorKind::Interpreter(kind), span, help, stack_trace: self.stack_trace(span) }))
+    }
+
+    /// Builds the stack trace of an error raised at `span`: each active call frame reports the code it is executing,
+    /// that is the error itself for the innermost frame and the call to the next frame for the others.
+    fn stack_trace(&self, span: Option<Span>) -> Vec<StackFrame>
+    {
+        let mut stack_trace = Vec::with_capacity(self.frames.len() + 1);
+        let mut opt_span = span;
+        for frame in self.frames.iter().rev() {
+            if let Some(span) = opt_span {
+                stack_trace.push(StackFrame { function: self.string_interner.resolve(frame.function).map(str::to_owned), span });
+            }
+            opt_span = frame.call_span;
+        }
+        if let Some(span) = opt_span {
+            stack_trace.push(StackFrame { function: None, span });
+        }
+        stack_trace
+    }
+
     /// Loops though all the statements and executes them one by one.
-    fn execute_stmts(&mut self, stmts: &[Stmt], environment: &Rc<RefCell<Environment>>) -> Result<State, ()>
+    fn execute_stmts(&mut self, stmts: &[Stmt], environment: &Rc<RefCell<Environment>>) -> RuntimeResult<State>
     {
         for stmt in stmts
         {
@@ -93,22 +312,35 @@ impl <'a, T:Write> Interpreter<'a, T>
 
     /// Executes a single statement.
     ///
-    fn execute_stmt(&mut self, stmt: &Stmt, environment: &Rc<RefCell<Environment>>) -> Result<State, ()>
+    fn execute_stmt(&mut self, stmt: &Stmt, environment: &Rc<RefCell<Environment>>) -> RuntimeResult<State>
     {
-        match stmt
+        self.nesting += 1;
+        let result = if self.nesting.is_multiple_of(STACK_CHECK_INTERVAL) {
+            ensure_sufficient_stack(|| self.execute_stmt_kind(stmt, environment))
+        } else {
+            self.execute_stmt_kind(stmt, environment)
+        };
+        self.nesting -= 1;
+        result
+    }
+
+    fn execute_stmt_kind(&mut self, stmt: &Stmt, environment: &Rc<RefCell<Environment>>) -> RuntimeResult<State>
+    {
+        self.usage.statement();
+        match &stmt.kind
         {
-            Stmt::Print(expr) =>
+            StmtKind::Print(expr) =>
             {
                 let val = self.evaluate(expr, environment)?;
-                self.write_line(&val.to_string(self.string_interner));
+                self.write_line(&val.to_string(&self.string_interner));
                 Ok(State::Normal)
             },
-            Stmt::Expr(expr) =>
+            StmtKind::Expr(expr) =>
             {
                 self.evaluate(expr, environment)?;
                 Ok(State::Normal)
             }
-            Stmt::Var(identifier, opt_expr) =>
+            StmtKind::Var(identifier, opt_expr) =>
             {
                 match opt_expr
                 {
@@ -124,7 +356,7 @@ impl <'a, T:Write> Interpreter<'a, T>
                 }
                 Ok(State::Normal)
             }
-            Stmt::Block(statements) =>
+            StmtKind::Block(statements) =>
             {
 
                 let new_env = Environment::new(environment);
@@ -146,7 +378,7 @@ impl <'a, T:Write> Interpreter<'a, T>
                 }
                 Ok(State::Normal)
             },
-            Stmt::If(if_stmt) =>
+            StmtKind::If(if_stmt) =>
             {
                 let condition_value = self.evaluate(&if_stmt.condition, environment)?;
                 if condition_value.is_truthy() {
@@ -155,7 +387,7 @@ impl <'a, T:Write> Interpreter<'a, T>
                     Ok(State::Normal)
                 }
             },
-            Stmt::IfElse(if_else_stmt) =>
+            StmtKind::IfElse(if_else_stmt) =>
             {
                 let condition_value = self.evaluate(&if_else_stmt.condition, environment)?;
                 if condition_value.is_truthy() {
@@ -164,7 +396,7 @@ impl <'a, T:Write> Interpreter<'a, T>
                     self.execute_stmt(&if_else_stmt.else_stmt, environment)
                 }
             },
-            Stmt::While(while_stmt) =>
+            StmtKind::While(while_stmt) =>
             {
                 while self.evaluate(&while_stmt.condition, environment)?.is_truthy()
                 {
@@ -173,6 +405,7 @@ impl <'a, T:Write> Interpreter<'a, T>
                     {
                         State::Normal  | State::Continue =>
                         {
+                            self.check_interrupt(while_stmt.condition.span)?;
                             continue;
                         },
                         State::Break =>
@@ -184,21 +417,21 @@ impl <'a, T:Write> Interpreter<'a, T>
                 }
                 Ok(State::Normal)
             },
-            Stmt::Break => {
+            StmtKind::Break => {
                 Ok(State::Break)
             },
-            Stmt::Continue => {
+            StmtKind::Continue => {
                 Ok(State::Continue)
             },
             //Interpret a function declariation (fun my_function(...) {...}) by converting its compile time represtation 'FunctionDeclaration' to its runtime representation 'Callable::Function'
-            Stmt::FunctionDeclaration(function_declaration) =>
+            StmtKind::FunctionDeclaration(function_declaration) =>
             {
                 let lox_function = LoxFunction { declaration: Rc::clone(function_declaration), closure: Rc::clone(environment) };
                 let function = Callable::Function(Rc::new(RefCell::new(lox_function)));
                 environment.borrow_mut().define_variable(function_declaration.identifier.name, Value::Callable(function));
                 Ok(State::Normal)
             },
-            Stmt::ClassDeclaration(class_stmt) =>
+            StmtKind::ClassDeclaration(class_stmt) =>
             {
                 let opt_superclass: Option<Rc<LoxClass>> =
                     match &class_stmt.superclass_expr
@@ -216,8 +449,7 @@ impl <'a, T:Write> Interpreter<'a, T>
                                     Some(Rc::clone(rc_lox_class))
                                 },
                                 _ => {
-                                    let _ = writeln!(self.writer.borrow_mut(), "{}", LoxError::interpreter_error(InterpreterErrorKind::SuperclassMustBeAClass, class_stmt.identifier.position));
-                                    return Err(());
+                                    return self.runtime_error(InterpreterErrorKind::SuperclassMustBeAClass, class_stmt.identifier.span);
                                 }
                             }
                         },
@@ -252,7 +484,7 @@ impl <'a, T:Write> Interpreter<'a, T>
                 environment.borrow_mut().define_variable(class_stmt.identifier.name, Value::Callable(callable));
                 Ok(State::Normal)
             },
-            Stmt::Return(opt_expr, _) =>
+            StmtKind::Return(opt_expr, _) =>
             {
                 let value =
                     match opt_expr {
@@ -268,9 +500,37 @@ impl <'a, T:Write> Interpreter<'a, T>
     ///
     /// Recursivly evaluates an expression.
     ///
-    fn evaluate(&mut self, expr: &Expr, environment: &Rc<RefCell<Environment>>) -> Result<Value, ()>
+    fn evaluate(&mut self, expr: &Expr, environment: &Rc<RefCell<Environment>>) -> RuntimeResult<Value>
     {
+        self.nesting += 1;
+        let result = if self.nesting.is_multiple_of(STACK_CHECK_INTERVAL) {
+            ensure_sufficient_stack(|| self.evaluate_kind(expr, environment))
+        } else {
+            self.evaluate_kind(expr, environment)
+        };
+        self.nesting -= 1;
+        result
+    }
+
+    fn evaluate_kind(&mut self, expr: &Expr, environment: &Rc<RefCell<Environment>>) -> RuntimeResult<Value>
+    {
+        if let Err(kind) = self.usage.instruction(&self.limits) {
+            return self.runtime_error(kind, expr.span);
+        }
         match &expr.kind {
+            ExprKind::Interpolation(parts) =>
+            {
+                //each part is converted the same way as the 'str' native function does
+                let mut string = String::new();
+                for part in parts {
+                    let value = self.evaluate(part, environment)?;
+                    string.push_str(&value.to_string(&self.string_interner));
+                }
+                if let Err(kind) = self.usage.allocation(&self.limits, string.len()) {
+                    return self.runtime_error(kind, expr.span);
+                }
+                Ok(Value::String(Rc::new(string)))
+            },
             ExprKind::Literal(literal) =>
             {
                 match literal {
@@ -306,8 +566,7 @@ impl <'a, T:Write> Interpreter<'a, T>
                                 Ok(Value::Number(-num))
                             },
                             _ => {
-                                let _ = writeln!(self.writer.borrow_mut(), "{}", LoxError::interpreter_error(InterpreterErrorKind::CheckNumberOperand, unary_expr.operator.position));
-                                Err(())
+                                self.runtime_error(InterpreterErrorKind::CheckNumberOperand, expr.span)
                             }
                         }
                     },
@@ -334,8 +593,7 @@ impl <'a, T:Write> Interpreter<'a, T>
                                 Ok(Value::Number(num_left - num_right))
                             },
                             _ => {
-                                let _ = writeln!(self.writer.borrow_mut(), "{}", LoxError::interpreter_error(InterpreterErrorKind::CheckNumberOperands, binary_expr.operator.position));
-                                Err(())
+                                self.runtime_error(InterpreterErrorKind::CheckNumberOperands, expr.span)
                             }
                         }
                     },
@@ -346,11 +604,16 @@ impl <'a, T:Write> Interpreter<'a, T>
                                 Ok(Value::Number(num_left + num_right))
                             },
                             (Value::String(str_left), Value::String(str_right)) => {
+                                if let Err(kind) = self.usage.allocation(&self.limits, str_left.len() + str_right.len()) {
+                                    return self.runtime_error(kind, expr.span);
+                                }
                                 Ok(Value::String(Rc::new(format!("{}{}", str_left, str_right))))
                             },
+                            (Value::String(_), _) | (_, Value::String(_)) => {
+                                self.runtime_error_with_help(InterpreterErrorKind::InvalidPlusOperands, expr.span, Some("use `str()` to convert the other operand to a string".to_owned()))
+                            },
                             _ => {
-                                let _ = writeln!(self.writer.borrow_mut(), "{}", LoxError::interpreter_error(InterpreterErrorKind::InvalidPlusOperands, binary_expr.operator.position));
-                                Err(())
+                                self.runtime_error(InterpreterErrorKind::InvalidPlusOperands, expr.span)
                             }
                         }
                     },
@@ -361,8 +624,7 @@ impl <'a, T:Write> Interpreter<'a, T>
                                 Ok(Value::Number(num_left / num_right))
                             },
                             _ => {
-                                let _ = writeln!(self.writer.borrow_mut(), "{}", LoxError::interpreter_error(InterpreterErrorKind::CheckNumberOperands, binary_expr.operator.position));
-                                Err(())
+                                self.runtime_error(InterpreterErrorKind::CheckNumberOperands, expr.span)
                             }
                         }
                     },
@@ -373,8 +635,7 @@ impl <'a, T:Write> Interpreter<'a, T>
                                 Ok(Value::Number(num_left * num_right))
                             },
                             _ => {
-                                let _ = writeln!(self.writer.borrow_mut(), "{}", LoxError::interpreter_error(InterpreterErrorKind::CheckNumberOperands, binary_expr.operator.position));
-                                Err(())
+                                self.runtime_error(InterpreterErrorKind::CheckNumberOperands, expr.span)
                             }
                         }
                     },
@@ -385,8 +646,7 @@ impl <'a, T:Write> Interpreter<'a, T>
                                 Ok(Value::Bool(num_left > num_right))
                             },
                             _ => {
-                                let _ = writeln!(self.writer.borrow_mut(), "{}", LoxError::interpreter_error(InterpreterErrorKind::CheckNumberOperands, binary_expr.operator.position));
-                                Err(())
+                                self.runtime_error(InterpreterErrorKind::CheckNumberOperands, expr.span)
                             }
                         }
                     },
@@ -397,8 +657,7 @@ impl <'a, T:Write> Interpreter<'a, T>
                                 Ok(Value::Bool(num_left >= num_right))
                             },
                             _ => {
-                                let _ = writeln!(self.writer.borrow_mut(), "{}", LoxError::interpreter_error(InterpreterErrorKind::CheckNumberOperands, binary_expr.operator.position));
-                                Err(())
+                                self.runtime_error(InterpreterErrorKind::CheckNumberOperands, expr.span)
                             }
                         }
                     },
@@ -408,8 +667,7 @@ impl <'a, T:Write> Interpreter<'a, T>
                                 Ok(Value::Bool(num_left < num_right))
                             },
                             _ => {
-                                let _ = writeln!(self.writer.borrow_mut(), "{}", LoxError::interpreter_error(InterpreterErrorKind::CheckNumberOperands, binary_expr.operator.position));
-                                Err(())
+                                self.runtime_error(InterpreterErrorKind::CheckNumberOperands, expr.span)
                             }
                         }
                     },
@@ -420,8 +678,7 @@ impl <'a, T:Write> Interpreter<'a, T>
                                 Ok(Value::Bool(num_left <= num_right))
                             },
                             _ => {
-                                let _ = writeln!(self.writer.borrow_mut(), "{}", LoxError::interpreter_error(InterpreterErrorKind::CheckNumberOperands, binary_expr.operator.position));
-                                Err(())
+                                self.runtime_error(InterpreterErrorKind::CheckNumberOperands, expr.span)
                             }
                         }
                     },
@@ -442,8 +699,13 @@ impl <'a, T:Write> Interpreter<'a, T>
                         Ok(variable)
                     },
                     None => {
-                        let _ = writeln!(self.writer.borrow_mut(), "{}", LoxError::interpreter_error(InterpreterErrorKind::UndefinedVariable(self.string_interner.resolve(identifier.name).unwrap().to_owned()), identifier.position));
-                        Err(())
+                        if let Some(native) = self.denied_natives.get(&identifier.name) {
+                            let name = self.string_interner.resolve(identifier.name).unwrap().to_owned();
+                            let help = format!("`{}` needs access to {}", name, native.capability());
+                            return self.runtime_error_with_help(InterpreterErrorKind::NativeNotAllowed(name, self.sandbox.name()), identifier.span, Some(help));
+                        }
+                        let help = self.suggest_variable(environment, identifier.name);
+                        self.runtime_error_with_help(InterpreterErrorKind::UndefinedVariable(self.string_interner.resolve(identifier.name).unwrap().to_owned()), identifier.span, help)
                     },
                 }
             },
@@ -456,8 +718,8 @@ impl <'a, T:Write> Interpreter<'a, T>
                         Ok(value)
                     },
                     Err(_) => {
-                        let _ = writeln!(self.writer.borrow_mut(), "{}", LoxError::interpreter_error(InterpreterErrorKind::UndefinedVariable(self.string_interner.resolve(assign_expr.identifier.name).unwrap().to_owned()), assign_expr.identifier.position));
-                        Err(())
+                        let help = self.suggest_variable(environment, assign_expr.identifier.name);
+                        self.runtime_error_with_help(InterpreterErrorKind::UndefinedVariable(self.string_interner.resolve(assign_expr.identifier.name).unwrap().to_owned()), assign_expr.identifier.span, help)
                     },
                 }
             },
@@ -486,21 +748,25 @@ impl <'a, T:Write> Interpreter<'a, T>
             {
                 match self.evaluate(&call_expr.callee, environment)?
                 {
-                    Value::Callable(mut function) =>
+                    Value::Callable(function) =>
                     {
-                        if function.arity(self.init_symbol) == call_expr.arguments.len()
+                        let mut args: Vec<Value> = Vec::with_capacity(call_expr.arguments.len());
+                        for arg_expr in call_expr.arguments.iter()
                         {
-                            function.call(self, environment, &call_expr.arguments, &call_expr.position)
+                            args.push(self.evaluate(arg_expr, environment)?);
                         }
-                        else
+                        match function.arity(self.init_symbol)
                         {
-                            let _ = writeln!(self.writer.borrow_mut(), "{}", LoxError::interpreter_error(InterpreterErrorKind::WrongArity(function.arity(self.init_symbol), call_expr.arguments.len()), call_expr.position));
-                            Err(())
+                            Arity::Fixed(expected) if expected != args.len() => {
+                                self.runtime_error(InterpreterErrorKind::WrongArity(expected, args.len()), expr.span)
+                            },
+                            _ => {
+                                function.call(self, args, Some(expr.span))
+                            }
                         }
                     },
                     _ => {
-                        let _ = writeln!(self.writer.borrow_mut(), "{}", LoxError::interpreter_error(InterpreterErrorKind::NotCallable, call_expr.position));
-                        Err(())
+                        self.runtime_error(InterpreterErrorKind::NotCallable, call_expr.callee.span)
                     }
                 }
             },
@@ -526,13 +792,14 @@ impl <'a, T:Write> Interpreter<'a, T>
                             return Ok(Value::Callable(callable));
                         }
 
-                        let _ = writeln!(self.writer.borrow_mut(), "{}", LoxError::interpreter_error(InterpreterErrorKind::UdefinedProperty(self.string_interner.resolve(get_expr.identifier.name).unwrap().to_owned()), get_expr.identifier.position));
-                        return Err(());
+                        let mut candidates: Vec<IdentifierSymbol> = class_instance.attributes.borrow().keys().copied().collect();
+                        candidates.extend(class_instance.declaration.method_names());
+                        let help = self.suggest(get_expr.identifier.name, candidates);
+                        self.runtime_error_with_help(InterpreterErrorKind::UdefinedProperty(self.string_interner.resolve(get_expr.identifier.name).unwrap().to_owned()), get_expr.identifier.span, help)
                     },
                     _ =>
                     {
-                        let _ = writeln!(self.writer.borrow_mut(), "{}", LoxError::interpreter_error(InterpreterErrorKind::OnlyInstancesHaveProperties, get_expr.identifier.position));
-                        return Err(());
+                        self.runtime_error(InterpreterErrorKind::OnlyInstancesHaveProperties, get_expr.identifier.span)
                     }
                 }
             },
@@ -543,16 +810,19 @@ impl <'a, T:Write> Interpreter<'a, T>
                     Value::ClassInstance(class_instance) =>
                     {
                         let value = self.evaluate(&set_expr.value, environment)?;
-                        class_instance.attributes.borrow_mut().insert(set_expr.identifier.name, value.clone());
+                        if class_instance.attributes.borrow_mut().insert(set_expr.identifier.name, value.clone()).is_none() {
+                            if let Err(kind) = self.usage.allocation(&self.limits, size_of::<(IdentifierSymbol, Value)>()) {
+                                return self.runtime_error(kind, expr.span);
+                            }
+                        }
                         Ok(value)
                     },
                     _ => {
-                        let _ = writeln!(self.writer.borrow_mut(), "{}", LoxError::interpreter_error(InterpreterErrorKind::OnlyInstancesHaveFields, set_expr.identifier.position));
-                        Err(())
+                        self.runtime_error(InterpreterErrorKind::OnlyInstancesHaveFields, set_expr.identifier.span)
                     }
                 }
             },
-            ExprKind::This(position) =>
+            ExprKind::This(span) =>
             {
                 match self.lookup_variable(environment, self.this_symbol, expr.id)
                 {
@@ -560,8 +830,7 @@ impl <'a, T:Write> Interpreter<'a, T>
                         Ok(variable)
                     },
                     None => {
-                        let _ = writeln!(self.writer.borrow_mut(), "{}", LoxError::interpreter_error(InterpreterErrorKind::UndefinedVariable(self.string_interner.resolve(self.this_symbol).unwrap().to_owned()), *position));
-                        Err(())
+                        self.runtime_error(InterpreterErrorKind::UndefinedVariable(self.string_interner.resolve(self.this_symbol).unwrap().to_owned()), *span)
                     },
                 }
             },
@@ -587,8 +856,8 @@ impl <'a, T:Write> Interpreter<'a, T>
                             },
                             None =>
                             {
-                                let _ = writeln!(self.writer.borrow_mut(), "{}", LoxError::interpreter_error(InterpreterErrorKind::UdefinedProperty(self.string_interner.resolve(identifier.name).unwrap().to_owned()), identifier.position));
-                                Err(())
+                                let help = self.suggest(identifier.name, lox_superclass.method_names());
+                                self.runtime_error_with_help(InterpreterErrorKind::UdefinedProperty(self.string_interner.resolve(identifier.name).unwrap().to_owned()), identifier.span, help)
                             },
                         }
                     },
@@ -600,6 +869,24 @@ impl <'a, T:Write> Interpreter<'a, T>
         }
     }
 
+    /// Looks for a variable visible from `environment` with a name similar to `name`, returning a "did you mean" help note.
+    #[cold]
+    #[inline(never)]
+    fn suggest_variable(&self, environment: &Rc<RefCell<Environment>>, name: IdentifierSymbol) -> Option<String>
+    {
+        self.suggest(name, environment.borrow().names())
+    }
+
+    /// Returns a "did you mean" help note if one of the `candidates` is a likely misspelling of `name`.
+    #[cold]
+    #[inline(never)]
+    fn suggest(&self, name: IdentifierSymbol, candidates: Vec<IdentifierSymbol>) -> Option<String>
+    {
+        let name = self.string_interner.resolve(name)?;
+        let candidates = candidates.into_iter().filter_map(|candidate| self.string_interner.resolve(candidate));
+        did_you_mean(name, candidates)
+    }
+
     #[inline]
     fn lookup_variable(&self, environment: &Rc<RefCell<Environment>>, name: IdentifierSymbol, expr_id: ExprId) -> Option<Value>
     {
@@ -640,45 +927,42 @@ pub enum Callable
 {
     Function(Rc<RefCell<LoxFunction>>),
     Class(Rc<LoxClass>),
-    Clock,
-    AssertEq,
-    Str
+    Native(Rc<dyn NativeFunction>)
 }
 
 impl Callable
 {
     #[inline]
     /// Returns the number of parameters of the callable
-    fn arity(&self, init_symbol: IdentifierSymbol) -> usize
+    fn arity(&self, init_symbol: IdentifierSymbol) -> Arity
     {
         match self {
             Self::Function(function) =>
             {
-                function.borrow().declaration.parameters.len()
+                Arity::Fixed(function.borrow().declaration.parameters.len())
             },
             Self::Class(class) =>
             {
                 //If class has an initializer determine the number of parameters of the initializer to be passed to the class contructor
                 match class.find_method(&init_symbol) {
-                    Some(init) => { init.declaration.parameters.len() },
-                    None => 0,
+                    Some(init) => { Arity::Fixed(init.declaration.parameters.len()) },
+                    None => Arity::Fixed(0),
                 }
             },
-            Self::Clock => { 0 }
-            Self::AssertEq => { 2 },
-            Self::Str => { 1 },
+            Self::Native(native) => { native.arity() },
         }
     }
 
     #[inline]
-    /// Executes a callable instance and returns its Value or an error.
+    /// Executes a callable instance with the already evaluated arguments and returns its Value or an error.
+    ///
+    /// `span` is the span of the call expression, if any (calls made by the host application have none).
     fn call<T:Write>(
-        &mut self,
-        interpreter:                &mut Interpreter<T>,
-        interpreter_environment:    &Rc<RefCell<Environment>>,
-        args_expr:                  &[Expr],
-        position:                   &Position
-    ) -> Result<Value, ()>
+        &self,
+        interpreter:    &mut Interpreter<T>,
+        args:           Vec<Value>,
+        span:           Option<Span>
+    ) -> RuntimeResult<Value>
     {
         match self
         {
@@ -689,17 +973,22 @@ impl Callable
 
                     let rc_scope = Environment::new(enclosing);
 
-                    for (name, arg_expr) in function.borrow().declaration.parameters.iter().zip(args_expr.iter())
+                    for (name, value) in function.borrow().declaration.parameters.iter().zip(args)
                     {
-                        //do not inline value
-                        let value = interpreter.evaluate(arg_expr, interpreter_environment)?;
                         rc_scope.borrow_mut().define_variable(*name, value);
                     }
 
-                    interpreter.execute_stmts(
+                    interpreter.check_interrupt(span)?;
+                    if interpreter.frames.len() >= interpreter.limits.max_call_depth {
+                        return interpreter.runtime_error(InterpreterErrorKind::StackOverflow, span);
+                    }
+                    interpreter.frames.push(CallFrame { function: function.borrow().declaration.identifier.name, call_span: span });
+                    let result = interpreter.execute_stmts(
                         &function.borrow().declaration.body,
                         &rc_scope
-                    )?
+                    );
+                    interpreter.frames.pop();
+                    result?
                 };
 
                 // non spostare da qui! (init ritorna 'this' anche se non presente un return al suo interno)
@@ -720,6 +1009,9 @@ impl Callable
             // Construct a new class instance. Calls on class identifier construct a new instance of the given class (there is no 'new' keyword in Lox)
             Self::Class(lox_class) =>
             {
+                if let Err(kind) = interpreter.usage.allocation(&interpreter.limits, size_of::<LoxInstance>()) {
+                    return interpreter.runtime_error(kind, span);
+                }
                 //Create the new instance Value
                 let instance = Value::ClassInstance(
                     Rc::new(
@@ -733,42 +1025,22 @@ impl Callable
                 // Call the init method (if it exists)
                 if let Some(init) = lox_class.find_method(&interpreter.init_symbol)
                 {
-                    let mut callable = init.bind(instance.clone(), interpreter.this_symbol);
-                    let _ = callable.call(interpreter, interpreter_environment, args_expr, &lox_class.identifier.position)?;
+                    let callable = init.bind(instance.clone(), interpreter.this_symbol);
+                    let _ = callable.call(interpreter, args, span)?;
                 }
                 Ok(instance)
             },
-            Self::Clock =>
+            Self::Native(native) =>
             {
-                match clock()
+                let result = native.call(&NativeContext::new(&interpreter.string_interner, interpreter.sandbox.file_root(), &interpreter.limits, &mut interpreter.usage), &args);
+                match result
                 {
                     Ok(value) => Ok(value),
-                    Err(_) => {
-                        let _ = writeln!(interpreter.writer.borrow_mut(), "{}", LoxError::interpreter_error(InterpreterErrorKind::NativeClockSysTimeError, *position));
-                        return Err(());
+                    Err(kind) => {
+                        interpreter.runtime_error(kind, span)
                     }
                 }
             },
-            Self::AssertEq =>
-            {
-                let actual   = interpreter.evaluate(&args_expr[0], interpreter_environment)?;
-                let expected = interpreter.evaluate(&args_expr[1], interpreter_environment)?;
-                match assert_eq(actual, expected)
-                {
-                    Ok(_) => {
-                        Ok(Value::Nil)
-                    },
-                    Err(_) => {
-                        let _ = writeln!(interpreter.writer.borrow_mut(), "{}", LoxError::interpreter_error(InterpreterErrorKind::AssertionFailure, *position));
-                        return Err(());
-                    }
-                }
-            },
-            Self::Str =>
-            {
-                let value = interpreter.evaluate(&args_expr[0], interpreter_environment)?;
-                Ok(Value::String(Rc::new(value.to_string(interpreter.string_interner))))
-            },
         }
     }
 }
@@ -1361,7 +1633,6 @@ mod tests {
             test("./lox_test/limit/no_reuse_constants.lox");
         }
 
-        #[ignore]
         #[test]
         fn stack_overflow() {
             test("./lox_test/limit/stack_overflow.lox");
@@ -1904,6 +2175,74 @@ mod tests {
         }
     }
 
+    mod unicode_identifiers {
+        use super::test;
+        #[test]
+        fn unicode() {
+            test("./lox_test_mine/variable/unicode.lox");
+        }
+    }
+
+    mod string_interpolation {
+        use super::test;
+        #[test]
+        fn interpolation() {
+            test("./lox_test_mine/string/interpolation.lox");
+        }
+        #[test]
+        fn escapes() {
+            test("./lox_test_mine/string/escapes.lox");
+        }
+        #[test]
+        fn invalid_unicode_escape() {
+            test("./lox_test_mine/string/invalid_unicode_escape.lox");
+        }
+        #[test]
+        fn unclosed_interpolation() {
+            test("./lox_test_mine/string/unclosed_interpolation.lox");
+        }
+        #[test]
+        fn raw() {
+            test("./lox_test_mine/string/raw.lox");
+        }
+        #[test]
+        fn invalid_raw() {
+            test("./lox_test_mine/string/invalid_raw.lox");
+        }
+        #[test]
+        fn multiline() {
+            test("./lox_test_mine/string/multiline_dedent.lox");
+        }
+    }
+
+    mod number_literals {
+        use super::test;
+        #[test]
+        fn literals() {
+            test("./lox_test_mine/number/literals.lox");
+        }
+        #[test]
+        fn invalid_binary() {
+            test("./lox_test_mine/number/invalid_binary.lox");
+        }
+    }
+
+    mod block_comments {
+        use super::test;
+        #[test]
+        fn block() {
+            test("./lox_test_mine/comments/block.lox");
+        }
+        #[test]
+        fn nested_block() {
+            test("./lox_test_mine/comments/nested_block.lox");
+        }
+        #[test]
+        fn unterminated_block() {
+            test("./lox_test_mine/comments/unterminated_block.lox");
+        }
+    }
+
     enum Expect {
         Output(Vec<String>), RuntimeError(Vec<String>), ErrorAt, Nothing
     }
@@ -1946,17 +2285,18 @@ mod tests {
             return Expect::Nothing;
         }
 
-        return Expect::Output(vec);
+        Expect::Output(vec)
     }
 
     fn test(file_path: &str)
     {
         let mut buf_output = Vec::<u8>::new();
+        let mut buf_error = Vec::<u8>::new();
         match expected_result(file_path)
         {
             Expect::Output(buf_expected) =>
             {
-                run::run_file(file_path, &mut buf_output).expect(&format!("Expected test to be Ok (1) but got Err at file: '{}'", file_path));
+                run::run_file(file_path, &mut buf_output, &mut buf_error).unwrap_or_else(|_| panic!("Expected test to be Ok (1) but got Err at file: '{}'", file_path));
                 let lines = std::str::from_utf8(&buf_output).unwrap().lines();
                 if buf_expected.is_empty() {
                     panic!("test buf_expected should not be empty");
@@ -1971,7 +2311,8 @@ mod tests {
             },
             Expect::RuntimeError(buf_expected) =>
             {
-                run::run_file(file_path, &mut buf_output).expect_err(&format!("Expected test to be Err but got Ok at file: '{}'", file_path));
+                run::run_file(file_path, &mut buf_output, &mut buf_error).expect_err(&format!("Expected test to be Err but got Ok at file: '{}'", file_path));
+                assert!(!buf_error.is_empty(), "Expected an error message at file: '{}'", file_path);
                 let lines = std::str::from_utf8(&buf_output).unwrap().lines();
                 for (expected_value, actual_value) in buf_expected.iter().zip(lines)
                 {
@@ -1980,11 +2321,12 @@ mod tests {
             },
             Expect::ErrorAt =>
             {
-                run::run_file(file_path, &mut buf_output).expect_err(&format!("Expected test to be Err but got Ok at file: '{}'", file_path));
+                run::run_file(file_path, &mut buf_output, &mut buf_error).expect_err(&format!("Expected test to be Err but got Ok at file: '{}'", file_path));
+                assert!(!buf_error.is_empty(), "Expected an error message at file: '{}'", file_path);
             },
             Expect::Nothing =>
             {
-                run::run_file(file_path, &mut buf_output).expect(&format!("Expected test to be Ok (2) but got Err at file: '{}'", file_path));
+                run::run_file(file_path, &mut buf_output, &mut buf_error).unwrap_or_else(|_| panic!("Expected test to be Ok (2) but got Err at file: '{}'", file_path));
             },
         }
     }
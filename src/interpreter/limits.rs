@@ -0,0 +1,104 @@
+use std::time::{Duration, Instant};
+
+use crate::error::InterpreterErrorKind;
+
+/// Default maximum number of nested Lox calls.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1024;
+
+/// Number of instructions executed between two checks of the deadline.
+const DEADLINE_CHECK_INTERVAL: u64 = 1024;
+
+/// Resource limits enforced by the interpreter while running a script.
+///
+/// The counters are reset every time the host starts running code (eg. on each `Session::run`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Limits
+{
+    /// Maximum number of Lox functions, methods and initializers that can be running at the same time.
+    /// A deeper recursion raises a `StackOverflow` runtime error.
+    pub max_call_depth: usize,
+    /// Maximum number of statements and expressions that can be evaluated, `None` for no limit.
+    pub max_instructions: Option<u64>,
+    /// Maximum running time, `None` for no limit. The clock is checked every 1024 instructions.
+    pub timeout: Option<Duration>,
+    /// Maximum number of bytes that can be allocated for new strings and class instances, `None` for no limit.
+    /// Memory released during the execution is not given back to the budget.
+    pub max_allocated_bytes: Option<usize>
+}
+
+impl Default for Limits
+{
+    fn default() -> Self
+    {
+        Self { max_call_depth: DEFAULT_MAX_CALL_DEPTH, max_instructions: None, timeout: None, max_allocated_bytes: None }
+    }
+}
+
+/// Resources used since the host started running code, checked against the `Limits`.
+#[derive(Debug, Default)]
+pub(crate) struct Usage
+{
+    /// Whether instructions are counted, only when there is an instruction budget or a deadline.
+    counting: bool,
+    instructions: u64,
+    allocated_bytes: usize,
+    deadline: Option<Instant>,
+    /// Number of instructions at which the deadline is checked next. Statements are counted too, so the counter can step over any given value.
+    next_deadline_check: u64
+}
+
+impl Usage
+{
+    /// Starts counting from zero, computing the deadline from now.
+    pub fn start(limits: &Limits) -> Self
+    {
+        let counting = limits.max_instructions.is_some() || limits.timeout.is_some();
+        Self { counting, instructions: 0, allocated_bytes: 0, deadline: limits.timeout.map(|timeout| Instant::now() + timeout), next_deadline_check: DEADLINE_CHECK_INTERVAL }
+    }
+
+    /// Counts an executed statement. The limits are checked by the next expression.
+    #[inline]
+    pub fn statement(&mut self)
+    {
+        if self.counting {
+            self.instructions += 1;
+        }
+    }
+
+    /// Counts an evaluated expression and checks the instruction budget and the deadline.
+    #[inline]
+    pub fn instruction(&mut self, limits: &Limits) -> Result<(), InterpreterErrorKind>
+    {
+        if !self.counting {
+            return Ok(());
+        }
+        self.instructions += 1;
+        if let Some(max_instructions) = limits.max_instructions {
+            if self.instructions > max_instructions {
+                return Err(InterpreterErrorKind::InstructionLimitExceeded);
+            }
+        }
+        if let Some(deadline) = self.deadline {
+            if self.instructions >= self.next_deadline_check {
+                self.next_deadline_check = self.instructions + DEADLINE_CHECK_INTERVAL;
+                if Instant::now() >= deadline {
+                    return Err(InterpreterErrorKind::Timeout);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Counts `bytes` newly allocated bytes, if the allocations are capped.
+    #[inline]
+    pub fn allocation(&mut self, limits: &Limits, bytes: usize) -> Result<(), InterpreterErrorKind>
+    {
+        match limits.max_allocated_bytes {
+            Some(max_allocated_bytes) => {
+                self.allocated_bytes = self.allocated_bytes.saturating_add(bytes);
+                if self.allocated_bytes > max_allocated_bytes { Err(InterpreterErrorKind::MemoryLimitExceeded) } else { Ok(()) }
+            },
+            None => Ok(()),
+        }
+    }
+}
@@ -1,4 +1,7 @@
-mod types;
-mod native_functions;
+pub mod types;
+pub mod native_functions;
+pub mod limits;
+pub mod sandbox;
 mod environment;
+#[allow(clippy::module_inception)]
 pub mod interpreter;
\ No newline at end of file
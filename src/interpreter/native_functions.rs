@@ -1,21 +1,318 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::{cell::RefCell, fmt, fs, path::{Path, PathBuf}, rc::Rc, time::{SystemTime, UNIX_EPOCH}};
 
-use super::types::Value;
+use string_interner::StringInterner;
 
-pub fn clock() -> Result<Value, ()>
+use crate::error::InterpreterErrorKind;
+
+use super::{limits::{Limits, Usage}, sandbox::Capability, types::Value};
+
+/// Number of arguments accepted by a native function.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Arity
+{
+    Fixed(usize),
+    Variadic
+}
+
+/// Services offered by the interpreter to a native function while it is running.
+pub struct NativeContext<'a>
+{
+    string_interner: &'a StringInterner,
+    file_root: Option<&'a Path>,
+    limits: &'a Limits,
+    usage: RefCell<&'a mut Usage>
+}
+
+impl <'a> NativeContext<'a>
+{
+    pub(crate) fn new(string_interner: &'a StringInterner, file_root: Option<&'a Path>, limits: &'a Limits, usage: &'a mut Usage) -> Self
+    {
+        Self { string_interner, file_root, limits, usage: RefCell::new(usage) }
+    }
+
+    /// Returns the directory files can be read from according to the sandbox, `None` if there is no such restriction.
+    pub fn file_root(&self) -> Option<&Path>
+    {
+        self.file_root
+    }
+
+    /// Charges `bytes` newly allocated bytes (eg. the length of a returned string) to the memory cap of the script.
+    pub fn allocate(&self, bytes: usize) -> Result<(), InterpreterErrorKind>
+    {
+        self.usage.borrow_mut().allocation(self.limits, bytes)
+    }
+
+    /// Converts a value to a string the same way `print` does.
+    pub fn stringify(&self, value: &Value) -> String
+    {
+        value.to_string(self.string_interner)
+    }
+}
+
+/// A function implemented in Rust and callable from Lox code.
+///
+/// The interpreter checks the number of arguments against `arity` before invoking `call`.
+/// An `Err` returned by `call` is reported as a runtime error at the call site.
+pub trait NativeFunction
+{
+    fn name(&self) -> &str;
+
+    fn arity(&self) -> Arity;
+
+    fn call(&self, context: &NativeContext, args: &[Value]) -> Result<Value, InterpreterErrorKind>;
+
+    /// What the function needs access to. Functions whose capability is not allowed by the `Sandbox` are not defined.
+    fn capability(&self) -> Capability
+    {
+        Capability::Pure
+    }
+}
+
+impl fmt::Debug for dyn NativeFunction
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "NativeFunction {{ name: {}, arity: {:?} }}", self.name(), self.arity())
+    }
+}
+
+/// Returns the native functions available to every program.
+///
+/// The ones not allowed by the sandbox are filtered out by the interpreter: `readFile` only by the `ReadOnly` and `Full` ones.
+pub fn builtins() -> Vec<Rc<dyn NativeFunction>>
+{
+    vec![Rc::new(Clock), Rc::new(AssertEq), Rc::new(Str), Rc::new(ReadFile)]
+}
+
+/// `clock()`: returns the number of seconds elapsed since the Unix epoch.
+pub struct Clock;
+
+impl NativeFunction for Clock
+{
+    fn name(&self) -> &str
+    {
+        "clock"
+    }
+
+    fn arity(&self) -> Arity
+    {
+        Arity::Fixed(0)
+    }
+
+    fn call(&self, _context: &NativeContext, _args: &[Value]) -> Result<Value, InterpreterErrorKind>
+    {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or_else(
+            |_| Err(InterpreterErrorKind::NativeClockSysTimeError),
+            |value| Ok(Value::Number(value.as_secs_f64()))
+        )
+    }
+
+    fn capability(&self) -> Capability
+    {
+        Capability::Clock
+    }
+}
+
+/// `assertEq(actual, expected)`: raises an assertion failure if the two values are different.
+pub struct AssertEq;
+
+impl NativeFunction for AssertEq
+{
+    fn name(&self) -> &str
+    {
+        "assertEq"
+    }
+
+    fn arity(&self) -> Arity
+    {
+        Arity::Fixed(2)
+    }
+
+    fn call(&self, _context: &NativeContext, args: &[Value]) -> Result<Value, InterpreterErrorKind>
+    {
+        if args[0] == args[1] {
+            Ok(Value::Nil)
+        } else {
+            Err(InterpreterErrorKind::AssertionFailure)
+        }
+    }
+}
+
+/// `str(value)`: converts any value to a string.
+pub struct Str;
+
+impl NativeFunction for Str
 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .map_or_else(
-        |_| Err(()),
-        |value| Ok(Value::Number(value.as_secs_f64()))
-    )
+    fn name(&self) -> &str
+    {
+        "str"
+    }
+
+    fn arity(&self) -> Arity
+    {
+        Arity::Fixed(1)
+    }
+
+    fn call(&self, context: &NativeContext, args: &[Value]) -> Result<Value, InterpreterErrorKind>
+    {
+        let string = context.stringify(&args[0]);
+        context.allocate(string.len())?;
+        Ok(Value::String(Rc::new(string)))
+    }
+}
+
+/// `readFile(path)`: returns the content of a text file.
+///
+/// When the sandbox has a root directory, relative paths start from it and files outside of it cannot be read.
+pub struct ReadFile;
+
+impl ReadFile
+{
+    fn resolve(root: Option<&Path>, path: &str) -> Result<PathBuf, String>
+    {
+        match root {
+            None => Ok(PathBuf::from(path)),
+            Some(root) => {
+                let root = root.canonicalize().map_err(|error| format!("Cannot read '{}': {}", path, error))?;
+                let full_path = root.join(path).canonicalize().map_err(|error| format!("Cannot read '{}': {}", path, error))?;
+                if full_path.starts_with(&root) {
+                    Ok(full_path)
+                } else {
+                    Err(format!("Cannot read '{}': the file is outside of the sandbox directory.", path))
+                }
+            }
+        }
+    }
 }
 
-pub fn assert_eq(actual: Value, expected: Value) -> Result<(), ()> {
-    if actual == expected {
-        Ok(())
-    } else {
-        Err(())
+impl NativeFunction for ReadFile
+{
+    fn name(&self) -> &str
+    {
+        "readFile"
     }
-}
\ No newline at end of file
+
+    fn arity(&self) -> Arity
+    {
+        Arity::Fixed(1)
+    }
+
+    fn call(&self, context: &NativeContext, args: &[Value]) -> Result<Value, InterpreterErrorKind>
+    {
+        match &args[0] {
+            Value::String(path) => {
+                let content = Self::resolve(context.file_root(), path)
+                    .and_then(|full_path| fs::read_to_string(full_path).map_err(|error| format!("Cannot read '{}': {}", path, error)))
+                    .map_err(InterpreterErrorKind::NativeFunctionError)?;
+                context.allocate(content.len())?;
+                Ok(Value::String(Rc::new(content)))
+            },
+            _ => {
+                Err(InterpreterErrorKind::NativeFunctionError("Path must be a string.".to_owned()))
+            }
+        }
+    }
+
+    fn capability(&self) -> Capability
+    {
+        Capability::ReadFiles
+    }
+}
+
+/// `args()`: returns the number of arguments passed to the script, `args(index)`: returns the argument at `index` (starting from 0) or nil if out of range.
+pub struct ScriptArgs
+{
+    args: Vec<String>
+}
+
+impl ScriptArgs
+{
+    pub fn new(args: Vec<String>) -> Self
+    {
+        Self { args }
+    }
+}
+
+impl NativeFunction for ScriptArgs
+{
+    fn name(&self) -> &str
+    {
+        "args"
+    }
+
+    fn arity(&self) -> Arity
+    {
+        Arity::Variadic
+    }
+
+    fn call(&self, _context: &NativeContext, args: &[Value]) -> Result<Value, InterpreterErrorKind>
+    {
+        match args {
+            [] => {
+                Ok(Value::Number(self.args.len() as f64))
+            },
+            [Value::Number(index)] if *index >= 0.0 && index.fract() == 0.0 => {
+                Ok(self.args.get(*index as usize).map_or(Value::Nil, |arg| Value::from(arg.as_str())))
+            },
+            [_] => {
+                Err(InterpreterErrorKind::NativeFunctionError("Argument index must be a non-negative integer.".to_owned()))
+            },
+            _ => {
+                Err(InterpreterErrorKind::NativeFunctionError(format!("Expected 0 or 1 arguments but got {}.", args.len())))
+            }
+        }
+    }
+}
+
+/// Signature of the closures accepted by `NativeClosure`.
+pub type NativeFn = dyn Fn(&[Value]) -> Result<Value, String>;
+
+/// Adapts a closure to the `NativeFunction` trait. An error message returned by the closure is reported as a runtime error.
+pub struct NativeClosure
+{
+    name: String,
+    arity: Arity,
+    capability: Capability,
+    function: Box<NativeFn>
+}
+
+impl NativeClosure
+{
+    pub fn new<F>(name: &str, arity: Arity, function: F) -> Self
+        where F: Fn(&[Value]) -> Result<Value, String> + 'static
+    {
+        Self { name: name.to_owned(), arity, capability: Capability::Pure, function: Box::new(function) }
+    }
+
+    /// Declares what the closure needs access to, `Capability::Pure` by default.
+    pub fn with_capability(mut self, capability: Capability) -> Self
+    {
+        self.capability = capability;
+        self
+    }
+}
+
+impl NativeFunction for NativeClosure
+{
+    fn name(&self) -> &str
+    {
+        &self.name
+    }
+
+    fn arity(&self) -> Arity
+    {
+        self.arity
+    }
+
+    fn call(&self, _context: &NativeContext, args: &[Value]) -> Result<Value, InterpreterErrorKind>
+    {
+        (self.function)(args).map_err(InterpreterErrorKind::NativeFunctionError)
+    }
+
+    fn capability(&self) -> Capability
+    {
+        self.capability
+    }
+}
@@ -0,0 +1,82 @@
+use std::{fmt, path::{Path, PathBuf}};
+
+/// What a native function needs access to, besides its arguments.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Capability
+{
+    /// Nothing: the result only depends on the arguments.
+    Pure,
+    /// The system clock.
+    Clock,
+    /// Reading files.
+    ReadFiles
+}
+
+impl fmt::Display for Capability
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self {
+            Self::Pure      => write!(f, "nothing"),
+            Self::Clock     => write!(f, "the clock"),
+            Self::ReadFiles => write!(f, "the file system"),
+        }
+    }
+}
+
+/// Profile deciding which native functions a script can see.
+///
+/// A native function is defined in the global scope only if the profile allows its `Capability`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Sandbox
+{
+    /// Only pure native functions: no clock and no IO.
+    Pure,
+    /// Pure native functions and the clock, but no IO.
+    #[default]
+    Standard,
+    /// Clock and read-only access to the files inside the given directory.
+    ReadOnly(PathBuf),
+    /// Every native function, including reading any file the process can read.
+    Full
+}
+
+impl Sandbox
+{
+    pub fn allows(&self, capability: Capability) -> bool
+    {
+        match self {
+            Self::Pure        => matches!(capability, Capability::Pure),
+            Self::Standard    => matches!(capability, Capability::Pure | Capability::Clock),
+            Self::ReadOnly(_) => matches!(capability, Capability::Pure | Capability::Clock | Capability::ReadFiles),
+            Self::Full        => matches!(capability, Capability::Pure | Capability::Clock | Capability::ReadFiles),
+        }
+    }
+
+    pub const fn name(&self) -> &'static str
+    {
+        match self {
+            Self::Pure        => "pure",
+            Self::Standard    => "standard",
+            Self::ReadOnly(_) => "read-only",
+            Self::Full        => "full",
+        }
+    }
+
+    /// Returns the directory files are read from, `None` if any file can be read.
+    pub fn file_root(&self) -> Option<&Path>
+    {
+        match self {
+            Self::ReadOnly(root) => Some(root),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Sandbox
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "{}", self.name())
+    }
+}
@@ -60,6 +60,16 @@ impl LoxClass
 
         None
     }
+
+    /// Returns the names of the methods of the class, including the inherited ones.
+    pub fn method_names(&self) -> Vec<IdentifierSymbol>
+    {
+        let mut names: Vec<IdentifierSymbol> = self.methods.keys().copied().collect();
+        if let Some(super_class) = &self.super_class {
+            names.extend(super_class.method_names());
+        }
+        names
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -69,7 +79,6 @@ pub struct LoxInstance
     pub attributes: Rc<RefCell<FxHashMap<IdentifierSymbol, Value>>>
 }
 
-
 #[derive(Clone, Debug)]
 pub enum Value
 {
@@ -97,9 +106,7 @@ impl PartialEq for Value
                 match (left, right) {
                     (Callable::Function(l), Callable::Function(r))  => { Rc::ptr_eq(l, r) },
                     (Callable::Class(l),    Callable::Class(r))     => { Rc::ptr_eq(l, r) },
-                    (Callable::Clock,       Callable::Clock)        => { true },
-                    (Callable::AssertEq,    Callable::AssertEq)     => { true },
-                    (Callable::Str,         Callable::Str)          => { true },
+                    (Callable::Native(l),   Callable::Native(r))    => { Rc::ptr_eq(l, r) },
                     _ => false
                 }
             },
@@ -134,9 +141,7 @@ impl Value
                 match callable {
                     Callable::Function(fun_decl)    => format!("<fn {}>", string_interner.resolve(fun_decl.borrow().declaration.identifier.name).unwrap()),
                     Callable::Class(class_decl)     => string_interner.resolve(class_decl.identifier.name).unwrap().to_string(),
-                    Callable::Clock                 => "<native fn>".to_string(),
-                    Callable::AssertEq              => "<native fn>".to_string(),
-                    Callable::Str                   => "<native fn>".to_string(),
+                    Callable::Native(_)             => "<native fn>".to_string(),
                 }
             },
             Value::ClassInstance(class_instance) => {
@@ -145,3 +150,35 @@ impl Value
         }
     }
 }
+
+impl From<f64> for Value
+{
+    fn from(number: f64) -> Self
+    {
+        Value::Number(number)
+    }
+}
+
+impl From<bool> for Value
+{
+    fn from(boolean: bool) -> Self
+    {
+        Value::Bool(boolean)
+    }
+}
+
+impl From<&str> for Value
+{
+    fn from(string: &str) -> Self
+    {
+        Value::String(Rc::new(string.to_owned()))
+    }
+}
+
+impl From<String> for Value
+{
+    fn from(string: String) -> Self
+    {
+        Value::String(Rc::new(string))
+    }
+}
@@ -5,4 +5,11 @@ mod error;
 mod parser;
 mod alias;
 mod benches;
-pub mod run;
\ No newline at end of file
+pub mod cli;
+pub mod run;
+pub mod session;
+
+pub use error::{EXIT_USAGE, ErrorFormat, ExecutionResult, LoxError, LoxErrorKind, ParserErrorKind, ResolverErrorKind, InterpreterErrorKind, InternalErrorKind, InputErrorKind, StackFrame};
+pub use interpreter::{interpreter::{Callable, Interpreter}, limits::Limits, native_functions::{Arity, NativeClosure, NativeContext, NativeFunction}, sandbox::{Capability, Sandbox}, types::Value};
+pub use parser::{cst::{SyntaxElement, SyntaxKind, SyntaxNode, SyntaxToken, SyntaxTree}, position::{Position, Span}, source_map::{SourceFile, SourceMap}, tokens::{Trivia, TriviaKind}};
+pub use session::Session;
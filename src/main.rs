@@ -1,35 +1,112 @@
-use std::{env, io};
+use std::{env, io, sync::{atomic::{AtomicBool, Ordering}, Arc}};
 
-use rlox::run;
+use rlox::{cli::{self, Command, Source}, run::{self, Options}};
 
 fn main()
 {
    const VERSION: &str = env!("CARGO_PKG_VERSION");
-   let args: Vec<String> = env::args().collect();
-   match args.len() {
-      2 => {
-         let arg = &args[1];
-         match arg.as_str() {
-            "--bench" => {
-               run::bench();
-            },
-            "--version" => {
-               println!("rlox {}", VERSION);
-            },
-            "--help" => {
-               let help =
+   let args: Vec<String> = env::args().skip(1).collect();
+   let arguments = match cli::parse_args(&args) {
+      Ok(arguments) => arguments,
+      Err(error) => {
+         std::process::exit(run::write_usage_error(&error, &mut io::stderr().lock()).exit_code());
+      }
+   };
+   let mut options = Options { error_format: arguments.error_format, limits: arguments.limits, sandbox: arguments.sandbox, ..Options::default() };
+   let result = match arguments.command {
+      Command::Repl => {
+         options.interrupt = Some(interrupt_on_ctrl_c());
+         run::repl_with_options(io::stdin().lock(), io::stdout(), io::stderr(), &options);
+         Ok(())
+      },
+      Command::Run(source, script_args) => {
+         options.script_args = script_args;
+         options.interrupt = Some(interrupt_on_ctrl_c());
+         match source {
+            Source::File(path) => run::run_file_with_options(&path, &options, &mut io::stdout().lock(), &mut io::stderr().lock()),
+            Source::Stdin      => run::run_reader(io::stdin().lock(), &options, &mut io::stdout().lock(), &mut io::stderr().lock()),
+            Source::Eval(code) => run::run_with_options(&code, &options, io::stdout().lock(), io::stderr().lock()),
+         }
+      },
+      Command::Dump(dump, source) => {
+         run::read_source(&source, options.error_format, &mut io::stderr().lock())
+            .and_then(|code| run::dump(&code, dump, &options, io::stdout().lock(), io::stderr().lock()))
+      },
+      Command::Bench(iterations) => {
+         run::bench(iterations);
+         Ok(())
+      },
+      Command::Version => {
+         println!("rlox {}", VERSION);
+         Ok(())
+      },
+      Command::Help => {
+         let help =
 "NAME
       rlox - Lox language tree-walk interpreter writtern in Rust.
 
 SYNOPSIS
-      rlox [OPTION] [FILE]
+      rlox [FILE | -e CODE | -] [ARGS...]
+      rlox --tokens | --cst | --ast | --resolve [FILE | -e CODE | -]
+      rlox --bench [--iterations N]
+
+      Run without arguments to start an interactive session. The value of expression statements is printed back and
+      multi-line input is accepted while braces, block comments or strings are still open. Press Ctrl-C to stop the
+      line being run and Ctrl-D to exit.
+
+      Otherwise runs the script in FILE, the CODE supplied with -e or the script read from the standard input with -.
+      Any argument following the script is passed to it: `args()` returns their number and `args(n)` the n-th one.
 
 OPTIONS
-      --bench     benchmark the interpreter on a standard set of tests written by the author of the Lox language and display the outcome.
+      -e, --eval CODE   run CODE instead of a file
+
+      -                 read the script from the standard input
+
+      --                stop parsing options, the next argument is the script file
+
+      --tokens          print the tokens produced by the lexer, with their positions, instead of running the script
+
+      --cst             print the lossless concrete syntax tree, with the whitespace and the comments attached to
+                        the tokens, instead of running the script
+
+      --ast             print the syntax tree produced by the parser as S-expressions instead of running the script
+
+      --resolve         print each variable expression with the scope distance found by the resolver (or `global`)
+                        instead of running the script
+
+      --error-format=FORMAT
+                        report errors as `human` readable text (default) or as `json`, one object per line with the
+                        stage, a stable error code, the message and the position
 
-      --version   output version information and exit
+      --sandbox PROFILE choose the native functions available to the script: `pure` (no clock and no IO),
+                        `standard` (clock but no IO, default), `read-only:DIR` (clock and `readFile` limited to the
+                        files inside DIR) or `full` (`readFile` can read any file)
 
-      --help      output help information and exit
+      --max-call-depth N
+                        raise a `Stack overflow.` runtime error when more than N calls are nested (default 1024)
+
+      --max-instructions N
+                        stop the script with a runtime error after N statements and expressions have been evaluated
+
+      --timeout MS      stop the script with a runtime error once it has been running for MS milliseconds
+
+      --max-memory BYTES
+                        stop the script with a runtime error once its strings and instances took more than BYTES bytes
+
+      --bench           benchmark the interpreter on a standard set of tests written by the author of the Lox language and display the outcome.
+
+      --iterations N    with --bench, run each benchmark N times and display the average elapsed time
+
+      -V, --version     output version information and exit
+
+      -h, --help        output help information and exit
+
+EXIT STATUS
+      0    success
+      64   wrong command line usage
+      65   compile error (syntax or resolution)
+      66   input file cannot be read
+      70   runtime error
 
 AUTHOR
       Written by miraakx (https://github.com/miraakx)
@@ -37,21 +114,23 @@ AUTHOR
 COPYRIGHT
       Copyrights 2024 miraakx (https://github.com/miraakx) - MIT Licence (https://github.com/miraakx/lox?tab=License-1-ov-file#readme)
 
-";             println!("{}", help);
-            }
-            _ => {
-               let result = run::run_file(&args[1], &mut io::stdout().lock());
-                  if result.is_err() {
-                     println!("\nProgram terminated with error(s). See above.");
-                     std::process::exit(64);
-                  }
-            }
-         }
-      }
-      _ => {
-         println!("\nUsage: `rlox [path/to/script]`. Type `rlox --help` for further info.");
-         std::process::exit(64);
+";
+         println!("{}", help);
+         Ok(())
       }
    };
-   println!("\nProgram terminated successfully.");
-}
\ No newline at end of file
+   if let Err(error) = result {
+      std::process::exit(error.exit_code());
+   }
+}
+
+/// Returns a flag set by Ctrl-C, which then stops the running script with an `Interrupted` error instead of killing the process.
+fn interrupt_on_ctrl_c() -> Arc<AtomicBool>
+{
+   let interrupt = Arc::new(AtomicBool::new(false));
+   let handler_interrupt = Arc::clone(&interrupt);
+   if let Err(error) = ctrlc::set_handler(move || handler_interrupt.store(true, Ordering::Relaxed)) {
+      eprintln!("Cannot handle Ctrl-C: {}", error);
+   }
+   interrupt
+}
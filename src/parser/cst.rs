@@ -0,0 +1,440 @@
+use std::{cmp::Reverse, collections::BTreeMap, iter::Peekable, vec::IntoIter};
+
+use string_interner::StringInterner;
+
+use crate::{alias::IdentifierSymbol, error::LoxError, utils::native_stack::ensure_sufficient_stack};
+
+use super::{lexer::Lexer, parser::Parser, position::Span, tokens::{Token, TokenKind, Trivia}, types::{Expr, ExprKind, FunctionDeclaration, Stmt, StmtKind}};
+
+/// Kind of a node of the concrete syntax tree.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyntaxKind
+{
+    /// The whole code, ending with the end of file token.
+    Program,
+    ExprStmt,
+    PrintStmt,
+    VarDecl,
+    FunDecl,
+    ClassDecl,
+    Block,
+    IfStmt,
+    WhileStmt,
+    ForStmt,
+    ReturnStmt,
+    BreakStmt,
+    ContinueStmt,
+    /// A function or a method: its name, parameters and body.
+    Function,
+    Binary,
+    Grouping,
+    Unary,
+    Literal,
+    Variable,
+    Assign,
+    Logical,
+    Call,
+    Get,
+    Set,
+    This,
+    Super,
+    Interpolation
+}
+
+/// A token along with the trivia (whitespace, line breaks, comments) preceding it.
+#[derive(Clone, Debug)]
+pub struct SyntaxToken
+{
+    pub token: Token,
+    pub leading_trivia: Vec<Trivia>
+}
+
+#[derive(Clone, Debug)]
+pub enum SyntaxElement
+{
+    Node(SyntaxNode),
+    Token(SyntaxToken)
+}
+
+/// A statement, an expression or a function, with its children in source order.
+#[derive(Clone, Debug)]
+pub struct SyntaxNode
+{
+    pub kind: SyntaxKind,
+    pub span: Span,
+    pub children: Vec<SyntaxElement>
+}
+
+impl Drop for SyntaxNode
+{
+    /// Drops the children on a grown stack if needed, so that a deeply nested tree cannot overflow the native stack.
+    fn drop(&mut self)
+    {
+        let children = std::mem::take(&mut self.children);
+        ensure_sufficient_stack(|| drop(children));
+    }
+}
+
+/// Lossless syntax tree of a chunk of code: every byte of the code belongs to a token or to the trivia preceding it, so the tree can be written back unchanged.
+///
+/// The nodes follow the syntax tree built by the parser. If the code has errors, the tokens are listed flat under the `Program` node.
+#[derive(Clone, Debug)]
+pub struct SyntaxTree
+{
+    pub root: SyntaxNode,
+    /// Lexical and syntactical errors found in the code.
+    pub errors: Vec<LoxError>,
+    code: String,
+    start_offset: u32
+}
+
+impl SyntaxTree
+{
+    /// Builds the tree of `code`, which starts at `start_offset` in the `SourceMap`.
+    pub fn parse(code: &str, start_offset: u32, init_symbol: IdentifierSymbol, interner: &mut StringInterner) -> Self
+    {
+        let mut lexer = Lexer::new_with_offset(code, start_offset, interner).with_trivia();
+        // lexical errors are reported by the parser, the code of malformed tokens is kept as trivia
+        let tokens: Vec<Token> = lexer.by_ref().filter(|token| !matches!(token.kind, TokenKind::Error(_))).collect();
+        let trivia = lexer.take_trivia();
+
+        let mut parser = Parser::new(init_symbol);
+        let (stmts, errors) = match parser.parse(code, start_offset, interner) {
+            Ok(stmts) => (stmts, Vec::new()),
+            Err(errors) => (Vec::new(), errors),
+        };
+
+        let mut collector = NodeCollector { tokens: &tokens, nodes: BTreeMap::new() };
+        for stmt in &stmts {
+            collector.stmt(stmt);
+        }
+        let nodes: Vec<(Span, SyntaxKind)> = collector.nodes.into_values().collect();
+
+        let mut trivia = trivia.into_iter().peekable();
+        let tokens: Vec<SyntaxToken> = tokens.into_iter()
+            .map(|token| {
+                let mut leading_trivia = Vec::new();
+                while let Some(trivia) = trivia.next_if(|trivia| trivia.span.start.offset < token.span.start.offset || token.kind == TokenKind::Eof) {
+                    leading_trivia.push(trivia);
+                }
+                SyntaxToken { token, leading_trivia }
+            })
+            .collect();
+
+        // the lexer always ends with the end of file token, which carries the trivia ending the code
+        let first = &tokens[0];
+        let span = Span::new(first.leading_trivia.first().map_or(first.token.span.start, |trivia| trivia.span.start), tokens[tokens.len() - 1].token.span.end);
+        let root = build_node(SyntaxKind::Program, span, &mut nodes.into_iter().peekable(), &mut tokens.into_iter().peekable(), true);
+        Self { root, errors, code: code.to_owned(), start_offset }
+    }
+
+    /// Returns the code covered by `span`.
+    pub fn text(&self, span: Span) -> &str
+    {
+        &self.code[(span.start.offset - self.start_offset) as usize..(span.end.offset - self.start_offset) as usize]
+    }
+
+    /// Writes back the code of the tree: the tokens and their trivia, in order.
+    pub fn to_source(&self) -> String
+    {
+        let mut source = String::with_capacity(self.code.len());
+        self.write_node(&self.root, &mut source);
+        source
+    }
+
+    fn write_node(&self, node: &SyntaxNode, source: &mut String)
+    {
+        for child in &node.children {
+            match child {
+                SyntaxElement::Node(node) => {
+                    ensure_sufficient_stack(|| self.write_node(node, source));
+                },
+                SyntaxElement::Token(token) => {
+                    for trivia in &token.leading_trivia {
+                        source.push_str(self.text(trivia.span));
+                    }
+                    source.push_str(self.text(token.token.span));
+                }
+            }
+        }
+    }
+}
+
+/// Collects the span and the kind of every node of the syntax tree, sorted by start and then from the outermost node.
+struct NodeCollector<'a>
+{
+    tokens: &'a [Token],
+    nodes: BTreeMap<(u32, Reverse<u32>), (Span, SyntaxKind)>
+}
+
+impl NodeCollector<'_>
+{
+    /// Adds a node. Of the nodes covering the same code the innermost one is kept, and the ones covering no code are generated by the parser and left out.
+    fn add(&mut self, span: Span, kind: SyntaxKind)
+    {
+        if span.start.offset < span.end.offset {
+            self.nodes.insert((span.start.offset, Reverse(span.end.offset)), (span, kind));
+        }
+    }
+
+    /// The statements desugaring a `for` loop share the span of the loop, so statements are classified by their first token.
+    fn stmt(&mut self, stmt: &Stmt)
+    {
+        ensure_sufficient_stack(|| self.stmt_kind(stmt))
+    }
+
+    fn stmt_kind(&mut self, stmt: &Stmt)
+    {
+        let first_token = self.tokens.binary_search_by_key(&stmt.span.start.offset, |token| token.span.start.offset).ok().map(|index| &self.tokens[index].kind);
+        let kind = match first_token {
+            Some(TokenKind::Var)       => SyntaxKind::VarDecl,
+            Some(TokenKind::Fun)       => SyntaxKind::FunDecl,
+            Some(TokenKind::Class)     => SyntaxKind::ClassDecl,
+            Some(TokenKind::LeftBrace) => SyntaxKind::Block,
+            Some(TokenKind::If)        => SyntaxKind::IfStmt,
+            Some(TokenKind::While)     => SyntaxKind::WhileStmt,
+            Some(TokenKind::For)       => SyntaxKind::ForStmt,
+            Some(TokenKind::Print)     => SyntaxKind::PrintStmt,
+            Some(TokenKind::Return)    => SyntaxKind::ReturnStmt,
+            Some(TokenKind::Break)     => SyntaxKind::BreakStmt,
+            Some(TokenKind::Continue)  => SyntaxKind::ContinueStmt,
+            _                          => SyntaxKind::ExprStmt,
+        };
+        self.add(stmt.span, kind);
+        match &stmt.kind {
+            StmtKind::Expr(expr) | StmtKind::Print(expr) => {
+                self.expr(expr);
+            },
+            StmtKind::Var(_, opt_expr) | StmtKind::Return(opt_expr, _) => {
+                if let Some(expr) = opt_expr {
+                    self.expr(expr);
+                }
+            },
+            StmtKind::Block(stmts) => {
+                stmts.iter().for_each(|stmt| self.stmt(stmt));
+            },
+            StmtKind::If(if_stmt) => {
+                self.expr(&if_stmt.condition);
+                self.stmt(&if_stmt.then_stmt);
+            },
+            StmtKind::IfElse(if_else_stmt) => {
+                self.expr(&if_else_stmt.condition);
+                self.stmt(&if_else_stmt.then_stmt);
+                self.stmt(&if_else_stmt.else_stmt);
+            },
+            StmtKind::While(while_stmt) => {
+                self.expr(&while_stmt.condition);
+                self.stmt(&while_stmt.body);
+            },
+            StmtKind::FunctionDeclaration(function_declaration) => {
+                self.function(function_declaration);
+            },
+            StmtKind::ClassDeclaration(class_declaration) => {
+                if let Some(superclass_expr) = &class_declaration.superclass_expr {
+                    self.expr(superclass_expr);
+                }
+                class_declaration.methods.values().for_each(|method| self.function(method));
+            },
+            StmtKind::Break | StmtKind::Continue => {},
+        }
+    }
+
+    fn function(&mut self, function_declaration: &FunctionDeclaration)
+    {
+        self.add(function_declaration.span, SyntaxKind::Function);
+        function_declaration.body.iter().for_each(|stmt| self.stmt(stmt));
+    }
+
+    fn expr(&mut self, expr: &Expr)
+    {
+        ensure_sufficient_stack(|| self.expr_kind(expr))
+    }
+
+    fn expr_kind(&mut self, expr: &Expr)
+    {
+        let kind = match &expr.kind {
+            ExprKind::Binary(binary_expr) => {
+                self.expr(&binary_expr.left);
+                self.expr(&binary_expr.right);
+                SyntaxKind::Binary
+            },
+            ExprKind::Grouping(expr) => {
+                self.expr(expr);
+                SyntaxKind::Grouping
+            },
+            ExprKind::Unary(unary_expr) => {
+                self.expr(&unary_expr.expr);
+                SyntaxKind::Unary
+            },
+            ExprKind::Literal(_) => SyntaxKind::Literal,
+            ExprKind::Variable(_) => SyntaxKind::Variable,
+            ExprKind::Assign(assign_expr) => {
+                self.expr(&assign_expr.expr);
+                SyntaxKind::Assign
+            },
+            ExprKind::Logical(logical_expr) => {
+                self.expr(&logical_expr.left);
+                self.expr(&logical_expr.right);
+                SyntaxKind::Logical
+            },
+            ExprKind::Call(call_expr) => {
+                self.expr(&call_expr.callee);
+                call_expr.arguments.iter().for_each(|argument| self.expr(argument));
+                SyntaxKind::Call
+            },
+            ExprKind::Get(get_expr) => {
+                self.expr(&get_expr.expr);
+                SyntaxKind::Get
+            },
+            ExprKind::Set(set_expr) => {
+                self.expr(&set_expr.target);
+                self.expr(&set_expr.value);
+                SyntaxKind::Set
+            },
+            ExprKind::This(_) => SyntaxKind::This,
+            ExprKind::Super(_) => SyntaxKind::Super,
+            ExprKind::Interpolation(parts) => {
+                parts.iter().for_each(|part| self.expr(part));
+                SyntaxKind::Interpolation
+            },
+        };
+        self.add(expr.span, kind);
+    }
+}
+
+/// Builds the node covering `span` out of the nodes and the tokens that follow, taking every one inside the span.
+///
+/// The root node also takes the end of file token, which covers no code.
+fn build_node(kind: SyntaxKind, span: Span, nodes: &mut Peekable<IntoIter<(Span, SyntaxKind)>>, tokens: &mut Peekable<IntoIter<SyntaxToken>>, is_root: bool) -> SyntaxNode
+{
+    let mut children = Vec::new();
+    loop {
+        let next_node = nodes.peek().filter(|(node_span, _)| node_span.end.offset <= span.end.offset).map(|(node_span, _)| node_span.start.offset);
+        let next_token = tokens.peek().filter(|token| is_root || token.token.span.start.offset < span.end.offset).map(|token| token.token.span.start.offset);
+        match (next_node, next_token) {
+            (Some(node_start), Some(token_start)) if node_start <= token_start => {},
+            (Some(_), None) => {},
+            (_, Some(_)) => {
+                children.push(SyntaxElement::Token(tokens.next().unwrap()));
+                continue;
+            },
+            (None, None) => {
+                return SyntaxNode { kind, span, children };
+            }
+        }
+        let (node_span, node_kind) = nodes.next().unwrap();
+        children.push(SyntaxElement::Node(ensure_sufficient_stack(|| build_node(node_kind, node_span, nodes, tokens, false))));
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use string_interner::StringInterner;
+
+    use crate::parser::tokens::{TokenKind, TriviaKind};
+
+    use super::{SyntaxElement, SyntaxKind, SyntaxNode, SyntaxTree};
+
+    fn syntax_tree(code: &str) -> SyntaxTree
+    {
+        let mut interner = StringInterner::default();
+        let init_symbol = interner.get_or_intern_static("init");
+        SyntaxTree::parse(code, 0, init_symbol, &mut interner)
+    }
+
+    fn child_kinds(node: &SyntaxNode) -> Vec<SyntaxKind>
+    {
+        node.children.iter()
+            .filter_map(|child| match child {
+                SyntaxElement::Node(node) => Some(node.kind),
+                SyntaxElement::Token(_) => None,
+            })
+            .collect()
+    }
+
+    fn child_node(node: &SyntaxNode, index: usize) -> &SyntaxNode
+    {
+        node.children.iter()
+            .filter_map(|child| match child {
+                SyntaxElement::Node(node) => Some(node),
+                SyntaxElement::Token(_) => None,
+            })
+            .nth(index)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let codes = [
+            "",
+            "  \n// only a comment",
+            "print 1 + 2 * (3 - -x); // trailing\r\n",
+            "var s = \"a ${ b + \"${c}\" } d\";\r\t/* block /* nested */ */ print s;\n\n",
+            "for (var i = 0; i < 10; i = i + 1) { if (i == 2) continue; else print i; }",
+            "for (;;) break;",
+            "fun f(a, b) {\n    return a or b and !a;\n}\nf(1, 2)(3);",
+            "class A < B {\n  init(x) { this.x = x; }\n  get() { return super.get(); }\n}\nA(1).x.y = 2;",
+            "var r = r\"raw ${x}\"; print \"\"\"\n    dedented\n    \"\"\";",
+            "print 1 $ 2; var €",
+            "{ while (true) { print 1 } ",
+        ];
+        for code in codes {
+            let tree = syntax_tree(code);
+            assert_eq!(tree.to_source(), code);
+        }
+    }
+
+    #[test]
+    fn test_nodes() {
+        let tree = syntax_tree("print a.b(1);\nvar c = 2;");
+        assert!(tree.errors.is_empty());
+        assert_eq!(tree.root.kind, SyntaxKind::Program);
+        assert_eq!(child_kinds(&tree.root), vec![SyntaxKind::PrintStmt, SyntaxKind::VarDecl]);
+        let print = child_node(&tree.root, 0);
+        assert_eq!(tree.text(print.span), "print a.b(1);");
+        let call = child_node(print, 0);
+        assert_eq!(call.kind, SyntaxKind::Call);
+        assert_eq!(child_kinds(call), vec![SyntaxKind::Get, SyntaxKind::Literal]);
+        assert_eq!(child_kinds(child_node(call, 0)), vec![SyntaxKind::Variable]);
+
+        // the line break is the leading trivia of `var`, the end of file token is the last child of the program
+        let var = child_node(&tree.root, 1);
+        match &var.children[0] {
+            SyntaxElement::Token(token) => {
+                assert_eq!(token.token.kind, TokenKind::Var);
+                assert_eq!(token.leading_trivia.iter().map(|trivia| trivia.kind).collect::<Vec<TriviaKind>>(), vec![TriviaKind::Newline]);
+            },
+            SyntaxElement::Node(_) => panic!("expected a token"),
+        }
+        assert!(matches!(tree.root.children.last(), Some(SyntaxElement::Token(token)) if token.token.kind == TokenKind::Eof));
+    }
+
+    #[test]
+    fn test_desugared_nodes() {
+        let tree = syntax_tree("for (var i = 0; i < 2;) print i;\nclass A { m() {} n() {} }");
+        assert_eq!(child_kinds(&tree.root), vec![SyntaxKind::ForStmt, SyntaxKind::ClassDecl]);
+        let for_stmt = child_node(&tree.root, 0);
+        assert_eq!(child_kinds(for_stmt), vec![SyntaxKind::VarDecl, SyntaxKind::Binary, SyntaxKind::PrintStmt]);
+        let class = child_node(&tree.root, 1);
+        assert_eq!(child_kinds(class), vec![SyntaxKind::Function, SyntaxKind::Function]);
+        assert_eq!(tree.text(child_node(class, 1).span), "n() {}");
+    }
+
+    #[test]
+    fn test_errors() {
+        let tree = syntax_tree("print 1 $ 2;\nprint (;");
+        assert_eq!(tree.errors.len(), 2);
+        // the tokens are kept flat, the malformed one as trivia
+        assert!(child_kinds(&tree.root).is_empty());
+        match &tree.root.children[2] {
+            SyntaxElement::Token(token) => {
+                assert_eq!(token.token.kind, TokenKind::Number(2.0));
+                let trivia: Vec<&str> = token.leading_trivia.iter().map(|trivia| tree.text(trivia.span)).collect();
+                assert_eq!(trivia, vec![" ", "$", " "]);
+            },
+            SyntaxElement::Node(_) => panic!("expected a token"),
+        }
+        assert_eq!(tree.to_source(), "print 1 $ 2;\nprint (;");
+    }
+}
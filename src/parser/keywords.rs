@@ -19,6 +19,8 @@ pub const GREATER:          char = '>';
 pub const SLASH:            char = '/';
 pub const BACK_SLASH:       char = '\\';
 pub const QUOTE:            char = '"';
+pub const DOLLAR:           char = '$';
+pub const HASH:             char = '#';
 
 pub const TRUE:             &str = "true";
 pub const FALSE:            &str = "false";
@@ -1,41 +1,80 @@
-use std::{cell::RefCell, io::Write, rc::Rc};
+use std::{collections::VecDeque, rc::Rc};
 
 use string_interner::StringInterner;
+use unicode_xid::UnicodeXID;
 
 use crate::{error::*, utils::rc_cache::RcStringCache};
 
-use super::{keywords::*, position::Position, scanner::Scanner, tokens::{Token, TokenKind}};
+use super::{keywords::*, position::{Position, Span}, scanner::Scanner, tokens::{LexicalError, Token, TokenKind, Trivia, TriviaKind}};
 
-pub struct Lexer<'a, T: Write>
+pub struct Lexer<'a>
 {
+    code           : &'a str,
+    /// Offset of the first byte of `code` in the `SourceMap`.
+    start_offset   : u32,
     scanner        : Scanner<'a>,
     string_interner: &'a mut StringInterner,
     string_rc_cache: RcStringCache,
-    error_logger   : Rc<RefCell<T>>,
+    /// Tokens waiting to be returned: the errors met while scanning the last token, followed by that token.
+    pending        : VecDeque<Token>,
     end_of_file    : bool,
+    /// Number of braces opened inside each interpolated expression (`${...}`) still open, the innermost last.
+    interpolations : Vec<usize>,
+    /// Set once the `}` ending an interpolated expression has been returned: the next token goes on with the string.
+    resume_string  : bool,
+    /// Whitespace, comments and malformed tokens met so far, `None` unless in lossless mode.
+    trivia         : Option<Vec<Trivia>>,
     line           : u32,
     column         : u32
 }
 
-impl<'a, T: Write> Lexer<'a, T>
+impl<'a> Lexer<'a>
 {
-    pub fn new(code: &'a str, string_interner: &'a mut StringInterner, error_logger: Rc<RefCell<T>>) -> Self
+    /// Creates a new lexer. Lexical errors are returned as `TokenKind::Error` tokens.
+    pub fn new(code: &'a str, string_interner: &'a mut StringInterner) -> Self
+    {
+        Self::new_with_offset(code, 0, string_interner)
+    }
+
+    /// Creates a new lexer for code starting at `start_offset` in the `SourceMap`, the offset of the first position.
+    pub fn new_with_offset(code: &'a str, start_offset: u32, string_interner: &'a mut StringInterner) -> Self
     {
         Lexer
         {
+           code,
+           start_offset,
            scanner:       Scanner::from_str(code, 2),
-           error_logger:  error_logger,
+           pending:       VecDeque::new(),
            end_of_file:   false,
+           interpolations: Vec::new(),
+           resume_string: false,
+           trivia:        None,
            string_interner,
            line: 1,
            column: 1,
            string_rc_cache: RcStringCache::default()
         }
     }
+
+    /// Turns on the lossless mode: the code between tokens (whitespace, line breaks, comments and malformed tokens) is kept as `Trivia`.
+    ///
+    /// Together, the tokens (except `Error` ones) and the trivia cover every byte of the code.
+    pub fn with_trivia(mut self) -> Self
+    {
+        self.trivia = Some(Vec::new());
+        self
+    }
+
+    /// Returns the trivia met so far, in source order, and forgets them.
+    pub fn take_trivia(&mut self) -> Vec<Trivia>
+    {
+        self.trivia.as_mut().map(std::mem::take).unwrap_or_default()
+    }
 }
 
-impl <'a, T: Write> Lexer<'a,T>
+impl <'a> Lexer<'a>
 {
+    /// Moves to the next line, the line break having been consumed.
     #[inline]
     fn new_line(&mut self)
     {
@@ -49,38 +88,471 @@ impl <'a, T: Write> Lexer<'a,T>
         self.column += 1;
     }
 
+    /// Returns the current position. The column must have been advanced past every consumed char.
     #[inline]
     fn get_position(&self) -> Position
     {
-        Position { line: self.line, column: self.column }
+        Position { line: self.line, column: self.column, offset: self.start_offset + self.scanner.offset() as u32 }
+    }
+
+    /// Returns the position right after the char at `position`, on the same line.
+    fn position_after(&self, position: Position) -> Position
+    {
+        let width = self.code[(position.offset - self.start_offset) as usize..].chars().next().map_or(0, char::len_utf8);
+        Position { line: position.line, column: position.column + 1, offset: position.offset + width as u32 }
+    }
+
+    /// In lossless mode, keeps the code going from `start` to the current position as trivia. Consecutive whitespace is merged.
+    fn trivia(&mut self, kind: TriviaKind, start: Position)
+    {
+        let end = self.get_position();
+        if let Some(trivia) = &mut self.trivia {
+            match trivia.last_mut() {
+                Some(last) if kind == TriviaKind::Whitespace && last.kind == kind && last.span.end == start => {
+                    last.span.end = end;
+                },
+                _ => {
+                    trivia.push(Trivia { kind, span: Span::new(start, end) });
+                }
+            }
+        }
+    }
+
+    /// Returns the errors queued while scanning `token` first, then `token`.
+    fn emit(&mut self, token: Token) -> Option<Token>
+    {
+        self.pending.push_back(token);
+        self.pending.pop_front()
+    }
+
+    /// Queues an error token, returned before the token being scanned.
+    fn error(&mut self, kind: ParserErrorKind, span: Span)
+    {
+        let text = self.source_text(span);
+        self.pending.push_back(Token { kind: TokenKind::Error(Rc::new(LexicalError { kind, text })), span });
+    }
+
+    /// Returns the code covered by `span`.
+    fn source_text(&self, span: Span) -> String
+    {
+        self.code[(span.start.offset - self.start_offset) as usize..(span.end.offset - self.start_offset) as usize].to_owned()
+    }
+
+    /// Reads the characters of a string literal following its opening quote (or the `}` ending an interpolated expression), handling escapes.
+    ///
+    /// Returns `TokenKind::String` at the closing quote, or `TokenKind::StringPart` when an interpolated expression starts (`${`).
+    /// An unterminated string is reported spanning from `token_start` to the end of the code, and gives `None`.
+    fn string(&mut self, token_start: Position) -> Option<TokenKind>
+    {
+        let mut string = String::new();
+        loop
+        {
+            let position = self.get_position();
+            match self.scanner.next()
+            {
+                Some(BACK_SLASH) =>
+                {
+                    //handle the escape character '\'
+                    self.advance_column();
+                    self.escape(position, &mut string);
+                },
+                Some(DOLLAR) if self.scanner.is_peek(LEFT_BRACE) =>
+                {
+                    //start of an interpolated expression, lexed as ordinary tokens up to the matching '}'
+                    self.scanner.next();
+                    self.advance_column();
+                    self.advance_column();
+                    self.interpolations.push(0);
+                    return Some(TokenKind::StringPart(self.string_rc_cache.get(string)));
+                },
+                Some(QUOTE) =>
+                {
+                    //string's end quote
+                    self.advance_column();
+                    return Some(TokenKind::String(self.string_rc_cache.get(string)));
+                },
+                Some(ch @ (LINE_FEED | CARRIAGE_RETURN)) =>
+                {
+                    string.push(ch);
+                    if ch == CARRIAGE_RETURN && self.scanner.is_peek(LINE_FEED) {
+                        string.push(self.scanner.unwrap_next());
+                    }
+                    self.new_line();
+                },
+                None =>
+                {
+                    //unterminated string
+                    self.error(ParserErrorKind::UnterminatedString, Span::new(token_start, self.get_position()));
+                    return None;
+                },
+                Some(ch) =>
+                {
+                    //string character. goes on looping.
+                    self.advance_column();
+                    string.push(ch);
+                }
+            }
+        }
+    }
+
+    /// Reads a raw string following its `r` prefix: `r"..."` or `r#"..."#`, using as many `#` as needed to write `"#` inside.
+    ///
+    /// Escapes are not handled and the string can span lines. An unterminated string is reported spanning from `token_start` to the end of the code, and gives `None`.
+    fn raw_string(&mut self, token_start: Position) -> Option<TokenKind>
+    {
+        let mut hashes: usize = 0;
+        while self.scanner.is_peek(HASH) {
+            self.scanner.next();
+            self.advance_column();
+            hashes += 1;
+        }
+        if !self.scanner.is_peek(QUOTE) {
+            self.error(ParserErrorKind::InvalidRawString, Span::new(token_start, self.get_position()));
+            return None;
+        }
+        self.scanner.next();
+        self.advance_column();
+
+        let mut string = String::new();
+        loop
+        {
+            match self.scanner.next()
+            {
+                Some(QUOTE) =>
+                {
+                    //end quote, if followed by the same number of '#'
+                    self.advance_column();
+                    let mut closing_hashes: usize = 0;
+                    while closing_hashes < hashes && self.scanner.is_peek(HASH) {
+                        self.scanner.next();
+                        self.advance_column();
+                        closing_hashes += 1;
+                    }
+                    if closing_hashes == hashes {
+                        return Some(TokenKind::String(self.string_rc_cache.get(string)));
+                    }
+                    string.push(QUOTE);
+                    string.extend(std::iter::repeat_n(HASH, closing_hashes));
+                },
+                Some(ch @ (LINE_FEED | CARRIAGE_RETURN)) =>
+                {
+                    string.push(ch);
+                    if ch == CARRIAGE_RETURN && self.scanner.is_peek(LINE_FEED) {
+                        string.push(self.scanner.unwrap_next());
+                    }
+                    self.new_line();
+                },
+                Some(ch) =>
+                {
+                    self.advance_column();
+                    string.push(ch);
+                },
+                None =>
+                {
+                    self.error(ParserErrorKind::UnterminatedString, Span::new(token_start, self.get_position()));
+                    return None;
+                }
+            }
+        }
+    }
+
+    /// Reads a multi-line string following its opening `"""`, up to the closing `"""`, handling escapes.
+    ///
+    /// The line break right after the opening quotes and the line holding only the closing quotes are dropped,
+    /// then the whitespace common to the beginning of the other lines is removed, the closing line included. Lines holding only whitespace become empty.
+    /// Line breaks are written as `\n`. An unterminated string is reported spanning from `token_start` to the end of the code, and gives `None`.
+    fn multiline_string(&mut self, token_start: Position) -> Option<TokenKind>
+    {
+        //each line, with the number of whitespace chars indenting it. `None` if the line holds only whitespace.
+        let mut lines: Vec<(String, Option<usize>)> = Vec::new();
+        let mut line = String::new();
+        let mut indentation: Option<usize> = Some(0);
+        let mut is_indenting = true;
+        loop
+        {
+            let position = self.get_position();
+            match self.scanner.next()
+            {
+                Some(QUOTE) if self.scanner.is_peek(QUOTE) && self.scanner.is_peek_next(QUOTE) =>
+                {
+                    self.scanner.next();
+                    self.scanner.next();
+                    self.advance_column();
+                    self.advance_column();
+                    self.advance_column();
+                    break;
+                },
+                Some(ch @ (LINE_FEED | CARRIAGE_RETURN)) =>
+                {
+                    if ch == CARRIAGE_RETURN {
+                        self.scanner.consume_if_peek_is(LINE_FEED);
+                    }
+                    self.new_line();
+                    lines.push((std::mem::take(&mut line), if is_indenting { None } else { indentation }));
+                    indentation = Some(0);
+                    is_indenting = true;
+                },
+                Some(ch @ (SPACE | TAB)) if is_indenting =>
+                {
+                    self.advance_column();
+                    indentation = indentation.map(|count| count + 1);
+                    line.push(ch);
+                },
+                Some(BACK_SLASH) =>
+                {
+                    is_indenting = false;
+                    self.advance_column();
+                    self.escape(position, &mut line);
+                },
+                Some(ch) =>
+                {
+                    is_indenting = false;
+                    self.advance_column();
+                    line.push(ch);
+                },
+                None =>
+                {
+                    self.error(ParserErrorKind::UnterminatedString, Span::new(token_start, self.get_position()));
+                    return None;
+                }
+            }
+        }
+        lines.push((line, if is_indenting { None } else { indentation }));
+        Some(TokenKind::String(self.string_rc_cache.get(dedent(lines))))
+    }
+
+    /// Handles the escape sequence following a `\` (starting at `escape_start`), pushing the escaped character into `string`.
+    ///
+    /// Invalid escapes are reported, and the backslash is kept in the string.
+    fn escape(&mut self, escape_start: Position, string: &mut String)
+    {
+        match self.scanner.peek()
+        {
+            Some('n') => {
+                self.scanner.next();
+                self.advance_column();
+                string.push('\n');
+            },
+            Some('r') => {
+                self.scanner.next();
+                self.advance_column();
+                string.push('\r');
+            },
+            Some('t') => {
+                self.scanner.next();
+                self.advance_column();
+                string.push('\t');
+            },
+            Some('\\') => {
+                self.scanner.next();
+                self.advance_column();
+                string.push('\\');
+            },
+            Some('0') => {
+                self.scanner.next();
+                self.advance_column();
+                string.push('\0');
+            },
+            Some('"') => {
+                self.scanner.next();
+                self.advance_column();
+                string.push('"');
+            },
+            Some('$') => {
+                self.scanner.next();
+                self.advance_column();
+                string.push('$');
+            },
+            Some('x') => {
+                self.scanner.next();
+                self.advance_column();
+                if let Some(ch) = self.hex_escape(escape_start) {
+                    string.push(ch);
+                }
+            },
+            Some('u') => {
+                self.scanner.next();
+                self.advance_column();
+                if let Some(ch) = self.unicode_escape() {
+                    string.push(ch);
+                }
+            },
+            opt_ch => {
+                string.push(BACK_SLASH);
+                let reason = match opt_ch {
+                    Some(ch) if !ch.is_control() => format!("unknown escape '\\{}'", ch),
+                    _ => "expected an escape character after '\\'".to_owned(),
+                };
+                let escape_end = self.position_after(self.get_position());
+                self.escape_error(&reason, escape_start, escape_end);
+            }
+        }
+    }
+
+    /// Reads the two hexadecimal digits of a `\xNN` escape, whose `\x` (starting at `escape_start`) has already been consumed.
+    ///
+    /// Only ASCII characters (up to `\x7F`) can be written this way. Errors are reported and give `None`.
+    fn hex_escape(&mut self, escape_start: Position) -> Option<char>
+    {
+        let mut value: u32 = 0;
+        for _ in 0..2 {
+            match self.scanner.peek().and_then(|ch| ch.to_digit(16)) {
+                Some(digit) => {
+                    self.scanner.next();
+                    self.advance_column();
+                    value = value * 16 + digit;
+                },
+                None => {
+                    self.escape_error("expected two hexadecimal digits after '\\x'", self.get_position(), self.get_position());
+                    return None;
+                }
+            }
+        }
+        if value > 0x7F {
+            self.escape_error("'\\x' can only write ASCII characters, use '\\u{...}' above '\\x7F'", escape_start, self.get_position());
+            return None;
+        }
+        char::from_u32(value)
+    }
+
+    /// Reads the `{XXXX}` part of a `\u{XXXX}` escape (1 to 6 hexadecimal digits), whose `\u` has already been consumed.
+    ///
+    /// Errors are reported at the offending character (or at the digits, for an invalid code point) and give `None`.
+    fn unicode_escape(&mut self) -> Option<char>
+    {
+        if !self.scanner.is_peek(LEFT_BRACE) {
+            self.escape_error("expected '{' after '\\u'", self.get_position(), self.get_position());
+            return None;
+        }
+        self.scanner.next();
+        self.advance_column();
+        let digits_start = self.get_position();
+        let mut value: u32 = 0;
+        let mut digits: usize = 0;
+        while let Some(digit) = self.scanner.peek().and_then(|ch| ch.to_digit(16)) {
+            if digits == 6 {
+                self.escape_error("a unicode escape has at most 6 hexadecimal digits", self.get_position(), self.get_position());
+                return None;
+            }
+            self.scanner.next();
+            self.advance_column();
+            value = value * 16 + digit;
+            digits += 1;
+        }
+        let digits_end = self.get_position();
+        if !self.scanner.is_peek(RIGHT_BRACE) {
+            self.escape_error("expected a hexadecimal digit or '}'", digits_end, digits_end);
+            return None;
+        }
+        self.scanner.next();
+        self.advance_column();
+        if digits == 0 {
+            self.escape_error("missing hexadecimal digits in '\\u{}'", digits_end, digits_end);
+            return None;
+        }
+        let opt_ch = char::from_u32(value);
+        if opt_ch.is_none() {
+            self.escape_error(&format!("'{:X}' is not a valid unicode code point", value), digits_start, digits_end);
+        }
+        opt_ch
+    }
+
+    /// Reports a malformed escape sequence spanning from `start` to `end`, at least one character wide.
+    fn escape_error(&mut self, reason: &str, start: Position, end: Position)
+    {
+        let end = if end == start { self.position_after(end) } else { end };
+        self.error(ParserErrorKind::InvalidEscapeSequence(reason.to_owned()), Span::new(start, end));
+    }
+
+    /// Skips a block comment whose opening `/*` has already been consumed, along with any block comment nested inside it.
+    ///
+    /// Reports an `UnterminatedComment` error spanning from `comment_start` if the code ends before the comment is closed.
+    fn block_comment(&mut self, comment_start: Position)
+    {
+        let mut depth: usize = 1;
+        while depth > 0
+        {
+            match self.scanner.next()
+            {
+                None => {
+                    self.error(ParserErrorKind::UnterminatedComment, Span::new(comment_start, self.get_position()));
+                    return;
+                },
+                Some(SLASH) if self.scanner.is_peek(STAR) => {
+                    self.scanner.next();
+                    self.advance_column();
+                    self.advance_column();
+                    depth += 1;
+                },
+                Some(STAR) if self.scanner.is_peek(SLASH) => {
+                    self.scanner.next();
+                    self.advance_column();
+                    self.advance_column();
+                    depth -= 1;
+                },
+                Some(CARRIAGE_RETURN) => {
+                    self.scanner.consume_if_peek_is(LINE_FEED);
+                    self.new_line();
+                },
+                Some(LINE_FEED) => {
+                    self.new_line();
+                },
+                Some(_) => {
+                    self.advance_column();
+                }
+            }
+        }
     }
 }
 
-impl<'a, T: Write> Iterator for Lexer<'a, T>
+impl<'a> Iterator for Lexer<'a>
 {
     type Item = Token;
 
     /// Advances through the code, consuming one character at a time, until it can return a new token.
     /// When the file ends, it returns `TokenKind::Eof`, subsequent calls will return `None`.
     ///
+    /// Line comments (`// ...`) and block comments (`/* ... */`, which can nest) are skipped.
+    ///
+    /// Each lexical error gives a `TokenKind::Error` token, returned before the token it was found in.
+    /// A malformed token (eg. an unexpected character or an invalid number) is skipped, leaving only its errors.
+    ///
     /// Grammar:
-    /// NUMBER      -> DIGIT+ ( "." DIGIT+ )? ;
-    /// STRING      -> "\"" <any char except "\"">* "\"" ;
-    /// IDENTIFIER  -> ALPHA ( ALPHA | DIGIT )* ;
-    /// ALPHA       -> "a" ... "z" | "A" ... "Z" | "_" ;
+    /// NUMBER      -> DECIMAL | "0" ( "x" | "X" ) HEX_DIGITS | "0" ( "b" | "B" ) BIN_DIGITS ;
+    /// DECIMAL     -> DIGITS ( "." DIGITS )? ( ( "e" | "E" ) ( "+" | "-" )? DIGITS )? ;
+    /// DIGITS      -> DIGIT ( "_"? DIGIT )* ;
+    /// STRING      -> "\"" ( <any char except "\""> | "${" <tokens of an expression> "}" )* "\""
+    ///              | "\"\"\"" <any char> * "\"\"\""
+    ///              | "r" "#"* "\"" <any char> * "\"" "#"* ;
+    /// IDENTIFIER  -> ( XID_START | "_" ) XID_CONTINUE* ;
     /// DIGIT       -> "0" ... "9" ;
     ///
     fn next(&mut self) -> Option<Token>
     {
+        if let Some(token) = self.pending.pop_front() {
+            return Some(token);
+        }
+
         let mut opt_token_kind     : Option<TokenKind>;
 
-        //store the starting point for the new token
-        let mut token_start_column : u32 = self.column;
-        let mut token_start_line   : u32 = self.line;
+        let mut token_start        : Position = self.get_position();
         let mut is_token_started   : bool = false;
 
+        if self.resume_string {
+            self.resume_string = false;
+            if let Some(token_kind) = self.string(token_start) {
+                return self.emit(Token{ kind: token_kind, span: Span::new(token_start, self.get_position()) });
+            }
+            //the rest of the string is unterminated and its error has been queued
+            self.trivia(TriviaKind::Skipped, token_start);
+        }
+
         loop
         {
+            //store the starting point for the new token, until its first character is met
+            if !is_token_started {
+                token_start = self.get_position();
+            }
             let opt_ch: Option<char> = self.scanner.next();
             if opt_ch.is_none()
             {
@@ -88,7 +560,7 @@ impl<'a, T: Write> Iterator for Lexer<'a, T>
                     return None;
                 } else {
                     self.end_of_file = true;
-                    return Some(Token{ kind: TokenKind::Eof, position: self.get_position() });
+                    return self.emit(Token{ kind: TokenKind::Eof, span: Span::new(self.get_position(), self.get_position()) });
                 }
             }
 
@@ -100,31 +572,22 @@ impl<'a, T: Write> Iterator for Lexer<'a, T>
             {
                 SPACE | TAB =>
                 {
-                    if !is_token_started {
-                        token_start_column += 1;
-                    }
                     self.advance_column();
+                    self.trivia(TriviaKind::Whitespace, token_start);
                 },
                 //handles '\r'
                 CARRIAGE_RETURN =>
                 {
-                    if !is_token_started {
-                        token_start_line   += 1;
-                        token_start_column = 1;
-                    }
-                    self.new_line();
-
                     //handle Windows new line '\r\n'
                     self.scanner.consume_if_peek_is(LINE_FEED);
+                    self.new_line();
+                    self.trivia(TriviaKind::Newline, token_start);
                 },
                 //handles '\n'
                 LINE_FEED =>
                 {
-                    if !is_token_started {
-                        token_start_line   += 1;
-                        token_start_column = 1;
-                    }
                     self.new_line();
+                    self.trivia(TriviaKind::Newline, token_start);
                 },
                 LEFT_PAREN =>
                 {
@@ -142,13 +605,30 @@ impl<'a, T: Write> Iterator for Lexer<'a, T>
                 {
                     is_token_started = true;
                     self.advance_column();
+                    if let Some(open_braces) = self.interpolations.last_mut() {
+                        *open_braces += 1;
+                    }
                     opt_token_kind = Some(TokenKind::LeftBrace);
                 },
                 RIGHT_BRACE =>
                 {
                     is_token_started = true;
                     self.advance_column();
-                    opt_token_kind = Some(TokenKind::RightBrace);
+                    match self.interpolations.last_mut() {
+                        Some(0) => {
+                            //end of an interpolated expression, the string goes on with the next token
+                            self.interpolations.pop();
+                            self.resume_string = true;
+                            opt_token_kind = Some(TokenKind::RightBrace);
+                        },
+                        Some(open_braces) => {
+                            *open_braces -= 1;
+                            opt_token_kind = Some(TokenKind::RightBrace);
+                        },
+                        None => {
+                            opt_token_kind = Some(TokenKind::RightBrace);
+                        }
+                    }
                 },
                 COMMA =>
                 {
@@ -237,7 +717,13 @@ impl<'a, T: Write> Iterator for Lexer<'a, T>
                 SLASH =>
                 {
                     self.advance_column();
-                    if !self.scanner.is_peek(SLASH) {
+                    if self.scanner.is_peek(STAR) {
+                        //found '/*'. Handle block comment, which can span lines and nest.
+                        self.scanner.next();
+                        self.advance_column();
+                        self.block_comment(token_start);
+                        self.trivia(TriviaKind::BlockComment, token_start);
+                    } else if !self.scanner.is_peek(SLASH) {
                         is_token_started = true;
                         opt_token_kind = Some(TokenKind::Slash);
                     } else {
@@ -259,98 +745,56 @@ impl<'a, T: Write> Iterator for Lexer<'a, T>
                                 },
                                 _ => {
                                     self.scanner.next();
+                                    self.advance_column();
                                 }
                             }
                         }
+                        self.trivia(TriviaKind::LineComment, token_start);
                     }
                 },
                 QUOTE =>
                 {
                     is_token_started = true;
                     self.advance_column();
-                    let mut string = String::new();
-                    loop
-                    {
-                        match self.scanner.next()
-                        {
-                            Some(BACK_SLASH) =>
-                            {
-                                //handle the escape character '\'
-                                self.advance_column();
-                                match self.scanner.peek()
-                                {
-                                    Some('n') => {
-                                        self.scanner.next();
-                                        self.advance_column();
-                                        string.push('\n');
-                                    },
-                                    Some('r') => {
-                                        self.scanner.next();
-                                        self.advance_column();
-                                        string.push('\r');
-                                    },
-                                    Some('t') => {
-                                        self.scanner.next();
-                                        self.advance_column();
-                                        string.push('\t');
-                                    },
-                                    Some('\\') => {
-                                        self.scanner.next();
-                                        self.advance_column();
-                                        string.push('\\');
-                                    },
-                                    Some('0') => {
-                                        self.scanner.next();
-                                        self.advance_column();
-                                        string.push('\0');
-                                    },
-                                    Some('"') => {
-                                        self.scanner.next();
-                                        self.advance_column();
-                                        string.push('"');
-                                    },
-                                    _=> {
-                                        string.push(ch);
-                                        let _ = writeln!(self.error_logger.borrow_mut(), "{}", LoxError::parser_error(ParserErrorKind::InvalidEscapeCharacter, self.get_position()));
-                                    }
-                                }
-                            },
-                            Some('"') =>
-                            {
-                                //string's end quote
-                                self.advance_column();
-                                let rc_string = self.string_rc_cache.get(string);
-                                opt_token_kind = Some(TokenKind::String(rc_string));
-                                break;
-                            },
-                            None =>
-                            {
-                                //unterminated string
-                                let _ = writeln!(self.error_logger.borrow_mut(), "{}", LoxError::parser_error(ParserErrorKind::UnterminatedString, self.get_position()));
-                                let rc_string = self.string_rc_cache.get(string);
-                                opt_token_kind = Some(TokenKind::String(rc_string));
-                                break;
-                            },
-                            Some(ch) =>
-                            {
-                                //string character. goes on looping.
-                                self.advance_column();
-                                string.push(ch);
-                            }
-                        }
+                    if self.scanner.is_peek(QUOTE) && self.scanner.is_peek_next(QUOTE) {
+                        //found '"""'. Handle multi-line string.
+                        self.scanner.next();
+                        self.scanner.next();
+                        self.advance_column();
+                        self.advance_column();
+                        opt_token_kind = self.multiline_string(token_start);
+                    } else {
+                        opt_token_kind = self.string(token_start);
                     }
                 },
+                'r' if matches!(self.scanner.peek(), Some(QUOTE | HASH)) =>
+                {
+                    is_token_started = true;
+                    self.advance_column();
+                    opt_token_kind = self.raw_string(token_start);
+                },
                 ch if ch.is_ascii_digit() =>
                 {
                     is_token_started = true;
                     self.advance_column();
                     let mut number_string = String::from(ch);
+                    let is_radix = ch == '0' && matches!(self.scanner.peek(), Some('x' | 'X' | 'b' | 'B'));
                     let mut flag_decimal_point = false;
 
-                    //parse the number and stores the chars into 'number_string'
+                    //stores the chars of the literal into 'number_string', letters and '_' included, so that malformed literals are reported as a whole.
                     //handle carefully the decimal separator. '.' must be followed by another digit to be part of the number, otherwise it's excluded.
-                    while self.scanner.is_peek_ascii_digit() || self.scanner.is_peek('.') && self.scanner.is_peek_next_ascii_digit() && !flag_decimal_point
+                    //the sign of the exponent must be followed by a digit too.
+                    loop
                     {
+                        let is_part_of_number = match self.scanner.peek() {
+                            Some(next) if UnicodeXID::is_xid_continue(next) => true,
+                            Some('.') => !flag_decimal_point && self.scanner.is_peek_next_ascii_digit(),
+                            Some('+' | '-') => !is_radix && number_string.ends_with(['e', 'E']) && self.scanner.is_peek_next_ascii_digit(),
+                            _ => false,
+                        };
+                        if !is_part_of_number {
+                            break;
+                        }
                         self.advance_column();
                         if self.scanner.is_peek('.') {
                             flag_decimal_point = true;
@@ -358,14 +802,19 @@ impl<'a, T: Write> Iterator for Lexer<'a, T>
                         number_string.push(self.scanner.unwrap_next());
                     }
                     //parse the number
-                    match number_string.parse::<f64>()
+                    match parse_number(&number_string)
                     {
                         Ok(number) => {
                             opt_token_kind = Some(TokenKind::Number(number));
                         }
-                        Err(_) => {
-                            let _ = writeln!(self.error_logger.borrow_mut(), "{}", LoxError::parser_error(ParserErrorKind::ParseFloatError(number_string), self.get_position()));
-                            opt_token_kind = Some(TokenKind::Number(f64::NAN));
+                        Err((reason, index)) => {
+                            let error_start = Position {
+                                line  : token_start.line,
+                                column: token_start.column + index as u32,
+                                offset: token_start.offset + number_string.char_indices().nth(index).map_or(number_string.len(), |(i, _)| i) as u32
+                            };
+                            let error_end = self.position_after(error_start);
+                            self.error(ParserErrorKind::InvalidNumber(reason), Span::new(error_start, error_end));
                         }
                     }
 
@@ -395,30 +844,128 @@ impl<'a, T: Write> Iterator for Lexer<'a, T>
                 },
                 _ =>
                 {
-                    let _ = writeln!(self.error_logger.borrow_mut(), "{}", LoxError::parser_error(ParserErrorKind::UnexpectedToken(ch), self.get_position()));
-                    opt_token_kind = Some(TokenKind::UnexpectedToken);
+                    is_token_started = true;
+                    self.advance_column();
+                    self.error(ParserErrorKind::UnexpectedToken(ch), Span::new(token_start, self.get_position()));
                 }
             }
             if let Some(token_kind) = opt_token_kind
             {
-                return Some(
+                return self.emit(
                     Token{
                         kind: token_kind,
-                        position: Position {
-                            line  : token_start_line,
-                            column: token_start_column
-                        }
+                        span: Span::new(token_start, self.get_position())
                     }
                 );
             }
+            else if is_token_started
+            {
+                //the token is malformed and its errors have been queued. skip it.
+                is_token_started = false;
+                self.trivia(TriviaKind::Skipped, token_start);
+            }
         }
     }
 }
 
+/// Computes the value of a number literal: decimal (`1_000.5e-3`), hexadecimal (`0x1F`) or binary (`0b1010`).
+///
+/// A malformed literal gives the reason and the index of the offending char (the length of the literal if something is missing at the end).
+fn parse_number(literal: &str) -> Result<f64, (String, usize)>
+{
+    let chars: Vec<char> = literal.chars().collect();
+    let (radix, base_name) = match chars.get(1) {
+        Some('x' | 'X') if chars[0] == '0' => (16, "hexadecimal"),
+        Some('b' | 'B') if chars[0] == '0' => (2, "binary"),
+        _ => (10, "decimal"),
+    };
+    let is_separator_valid = |index: usize| {
+        index > 0 && chars[index - 1].is_digit(radix) && matches!(chars.get(index + 1), Some(next) if next.is_digit(radix))
+    };
+
+    if radix != 10 {
+        if chars.len() == 2 {
+            return Err((format!("missing digits after '{}'", literal), 2));
+        }
+        let mut value: f64 = 0.0;
+        for (index, ch) in chars.iter().enumerate().skip(2) {
+            match ch.to_digit(radix) {
+                Some(digit) => { value = value * radix as f64 + digit as f64; },
+                None if *ch == '_' && is_separator_valid(index) => {},
+                None if *ch == '_' => { return Err(("'_' must be placed between two digits".to_owned(), index)); },
+                None if *ch == '.' => { return Err((format!("a {} number cannot have a fractional part", base_name), index)); },
+                None => { return Err((format!("'{}' is not a {} digit", ch, base_name), index)); },
+            }
+        }
+        return Ok(value);
+    }
+
+    let mut exponent_digits: Option<usize> = None;
+    for (index, ch) in chars.iter().enumerate() {
+        match ch {
+            '0'..='9' => {
+                exponent_digits = exponent_digits.map(|digits| digits + 1);
+            },
+            '_' if is_separator_valid(index) => {},
+            '_' => {
+                return Err(("'_' must be placed between two digits".to_owned(), index));
+            },
+            '.' if exponent_digits.is_some() => {
+                return Err(("the exponent cannot have a fractional part".to_owned(), index));
+            },
+            '.' => {},
+            'e' | 'E' if exponent_digits.is_none() => {
+                exponent_digits = Some(0);
+            },
+            '+' | '-' => {},
+            _ => {
+                return Err((format!("unexpected character '{}'", ch), index));
+            },
+        }
+    }
+    if exponent_digits == Some(0) {
+        return Err(("missing digits in the exponent".to_owned(), chars.len()));
+    }
+    let digits: String = chars.iter().filter(|ch| **ch != '_').collect();
+    digits.parse::<f64>().map_err(|_| (format!("cannot parse '{}'", literal), 0))
+}
+
+/// Joins the lines of a multi-line string, removing the whitespace common to their beginning (see `Lexer::multiline_string`).
+///
+/// Each line comes with the number of whitespace chars indenting it, `None` if it holds only whitespace.
+fn dedent(mut lines: Vec<(String, Option<usize>)>) -> String
+{
+    if lines.len() > 1 && lines[0].1.is_none() {
+        lines.remove(0);
+    }
+    //the indentation of the closing quotes counts, when they are on their own line
+    let closing_indentation = match lines.last() {
+        Some((closing_line, None)) if lines.len() > 1 => Some(closing_line.len()),
+        _ => None,
+    };
+    if closing_indentation.is_some() {
+        lines.pop();
+    }
+    let common_indentation = lines.iter()
+        .filter_map(|(_, indentation)| *indentation)
+        .chain(closing_indentation)
+        .min()
+        .unwrap_or(0);
+    let dedented: Vec<&str> = lines.iter()
+        .map(|(line, indentation)| match indentation {
+            //the indentation is made of ASCII whitespace, one byte per char
+            Some(_) => &line[common_indentation..],
+            None => "",
+        })
+        .collect();
+    dedented.join("\n")
+}
+
+/// Checks if `ch` can start an identifier: `_` or any character with the XID_Start property (UAX #31), eg. `a`, `à` or `変`.
 #[inline]
-const fn is_identifier(ch: char) -> bool
+fn is_identifier(ch: char) -> bool
 {
-    ch.is_ascii_alphabetic() || ch == '_'
+    ch == '_' || UnicodeXID::is_xid_start(ch)
 }
 
 /// Search the input string for a language keyword.
@@ -488,28 +1035,37 @@ fn compare(str: &str, keyword: &str, token_kind: TokenKind) -> Option<TokenKind>
 }
 
 #[cfg(test)]
+#[allow(clippy::get_first)]
 mod tests {
-    use std::{cell::RefCell, fs, io::stdout, rc::Rc};
+    use std::{fs, rc::Rc};
 
     use string_interner::StringInterner;
 
-    use crate::parser::tokens::{Token, TokenKind};
+    use crate::{error::ParserErrorKind, parser::tokens::{LexicalError, Token, TokenKind, TriviaKind}};
 
-    use super::{Lexer, Position};
+    use super::{Lexer, Position, Span};
 
     fn tokenize(code: &str) -> Vec<Token>
     {
         let mut interner = StringInterner::default();
-        let lexer = Lexer::new(code, &mut interner, Rc::new(RefCell::new(stdout())));
+        let lexer = Lexer::new(code, &mut interner);
         lexer.collect()
     }
 
     fn tokenize_with_interner(code: &str, string_interner: &mut StringInterner) -> Vec<Token>
     {
-        let lexer = Lexer::new(code, string_interner, Rc::new(RefCell::new(stdout())));
+        let lexer = Lexer::new(code, string_interner);
         lexer.collect()
     }
 
+    fn lexical_error(token: &Token) -> &LexicalError
+    {
+        match &token.kind {
+            TokenKind::Error(error) => error,
+            kind => panic!("expected an error token, got {:?}", kind),
+        }
+    }
+
     #[test]
     fn test_parens()
     {
@@ -589,6 +1145,38 @@ mod tests {
         let token = &vec.get(1).unwrap().kind;
         assert_eq!(token, &TokenKind::Number(1.0));
 
+        assert_eq!(tokenize("0x1F").get(0).unwrap().kind, TokenKind::Number(31.0));
+        assert_eq!(tokenize("0XfF_fF").get(0).unwrap().kind, TokenKind::Number(65535.0));
+        assert_eq!(tokenize("0b1010").get(0).unwrap().kind, TokenKind::Number(10.0));
+        assert_eq!(tokenize("0B1111_0000").get(0).unwrap().kind, TokenKind::Number(240.0));
+        assert_eq!(tokenize("1e-9").get(0).unwrap().kind, TokenKind::Number(1e-9));
+        assert_eq!(tokenize("2.5E3").get(0).unwrap().kind, TokenKind::Number(2500.0));
+        assert_eq!(tokenize("1e+2").get(0).unwrap().kind, TokenKind::Number(100.0));
+        assert_eq!(tokenize("1_000_000").get(0).unwrap().kind, TokenKind::Number(1_000_000.0));
+        assert_eq!(tokenize("1_0.0_1e1_0").get(0).unwrap().kind, TokenKind::Number(10.01e10));
+
+        let vec = tokenize("1e5-2");
+        assert_eq!(vec.iter().map(|token| token.kind.clone()).collect::<Vec<TokenKind>>(), vec![TokenKind::Number(1e5), TokenKind::Minus, TokenKind::Number(2.0), TokenKind::Eof]);
+    }
+
+    #[test]
+    fn test_invalid_numbers()
+    {
+        let number_error = |code: &str| {
+            let tokens = tokenize(code);
+            assert_eq!(tokens.len(), 2, "the literal '{}' must be replaced by a single error", code);
+            (lexical_error(&tokens[0]).kind.to_string(), tokens[0].span.start.column)
+        };
+        assert_eq!(number_error("0x"), ("Invalid number: missing digits after '0x'.".to_owned(), 3));
+        assert_eq!(number_error("0b102"), ("Invalid number: '2' is not a binary digit.".to_owned(), 5));
+        assert_eq!(number_error("0xFG"), ("Invalid number: 'G' is not a hexadecimal digit.".to_owned(), 4));
+        assert_eq!(number_error("0x1.5"), ("Invalid number: a hexadecimal number cannot have a fractional part.".to_owned(), 4));
+        assert_eq!(number_error("1__000"), ("Invalid number: '_' must be placed between two digits.".to_owned(), 2));
+        assert_eq!(number_error("1_"), ("Invalid number: '_' must be placed between two digits.".to_owned(), 2));
+        assert_eq!(number_error("0x_1"), ("Invalid number: '_' must be placed between two digits.".to_owned(), 3));
+        assert_eq!(number_error("1e"), ("Invalid number: missing digits in the exponent.".to_owned(), 3));
+        assert_eq!(number_error("1e5e3"), ("Invalid number: unexpected character 'e'.".to_owned(), 4));
+        assert_eq!(number_error("12abc"), ("Invalid number: unexpected character 'a'.".to_owned(), 3));
     }
 
     #[test]
@@ -601,6 +1189,72 @@ mod tests {
         let vec = tokenize("\"\\n \\0 \\r \\t \\\\ \\\"\"");
         let token = &vec.get(0).unwrap().kind;
         assert_eq!(token, &TokenKind::String(Rc::new("\n \0 \r \t \\ \"".to_owned())));
+
+        let vec = tokenize("\"\\x41\\x7e\\x00 \\u{48}\\u{e9}\\u{1F600}\\u{10FFFF}\"");
+        let token = &vec.get(0).unwrap().kind;
+        assert_eq!(token, &TokenKind::String(Rc::new("A~\0 H\u{e9}\u{1F600}\u{10FFFF}".to_owned())));
+    }
+
+    #[test]
+    fn test_invalid_escapes()
+    {
+        let escape_error = |code: &str| {
+            let tokens = tokenize(code);
+            assert_eq!(tokens.len(), 3, "the string {} must give a single error followed by the string", code);
+            assert!(matches!(tokens[1].kind, TokenKind::String(_)));
+            (lexical_error(&tokens[0]).kind.to_string(), tokens[0].span.start.column, tokens[0].span.end.column)
+        };
+        assert_eq!(escape_error("\"\\x80\""), ("Invalid escape sequence: '\\x' can only write ASCII characters, use '\\u{...}' above '\\x7F'.".to_owned(), 2, 6));
+        assert_eq!(escape_error("\"ab\\x4\""), ("Invalid escape sequence: expected two hexadecimal digits after '\\x'.".to_owned(), 7, 8));
+        assert_eq!(escape_error("\"\\u41\""), ("Invalid escape sequence: expected '{' after '\\u'.".to_owned(), 4, 5));
+        assert_eq!(escape_error("\"\\u{}\""), ("Invalid escape sequence: missing hexadecimal digits in '\\u{}'.".to_owned(), 5, 6));
+        assert_eq!(escape_error("\"\\u{D800}\""), ("Invalid escape sequence: 'D800' is not a valid unicode code point.".to_owned(), 5, 9));
+        assert_eq!(escape_error("\"\\u{110000}\""), ("Invalid escape sequence: '110000' is not a valid unicode code point.".to_owned(), 5, 11));
+        assert_eq!(escape_error("\"\\u{1234567}\""), ("Invalid escape sequence: a unicode escape has at most 6 hexadecimal digits.".to_owned(), 11, 12));
+        assert_eq!(escape_error("\"\\u{12G}\""), ("Invalid escape sequence: expected a hexadecimal digit or '}'.".to_owned(), 7, 8));
+        assert_eq!(escape_error("\"\\q\""), ("Invalid escape sequence: unknown escape '\\q'.".to_owned(), 2, 4));
+        assert_eq!(escape_error("\"a\\\n\""), ("Invalid escape sequence: expected an escape character after '\\'.".to_owned(), 3, 5));
+    }
+
+    #[test]
+    fn test_raw_strings()
+    {
+        let string = |code: &str| match tokenize(code).remove(0).kind {
+            TokenKind::String(string) => string.to_string(),
+            kind => panic!("expected a string, got {:?}", kind),
+        };
+        assert_eq!(string(r#"r"a\d+\n${x}""#), r"a\d+\n${x}");
+        assert_eq!(string(r###"r#"say "hi""#"###), r#"say "hi""#);
+        assert_eq!(string(r###"r##"a "# b"##"###), r##"a "# b"##);
+        assert_eq!(string("r\"one\ntwo\""), "one\ntwo");
+
+        let tokens = tokenize("r\"a\nb\" x");
+        assert_eq!(tokens[1].span.start, Position { line: 2, column: 4, offset: 7 });
+
+        assert_eq!(lexical_error(&tokenize("r#x")[0]).kind, ParserErrorKind::InvalidRawString);
+        assert_eq!(lexical_error(&tokenize("r#\"a\"")[0]).kind, ParserErrorKind::UnterminatedString);
+    }
+
+    #[test]
+    fn test_multiline_strings()
+    {
+        let string = |code: &str| match tokenize(code).remove(0).kind {
+            TokenKind::String(string) => string.to_string(),
+            kind => panic!("expected a string, got {:?}", kind),
+        };
+        assert_eq!(string("\"\"\"one line\"\"\""), "one line");
+        assert_eq!(string("\"\"\"\n    a\n      b\n\n    c\n    \"\"\""), "a\n  b\n\nc");
+        // the closing quotes set the indentation
+        assert_eq!(string("\"\"\"\n    a\n  \"\"\""), "  a");
+        // the last line is kept when it holds text
+        assert_eq!(string("\"\"\"\n  a\n  b\"\"\""), "a\nb");
+        // escapes are not indentation
+        assert_eq!(string("\"\"\"\n  \\ta \"quoted\"\n  b\r\n  \"\"\""), "\ta \"quoted\"\nb");
+        assert_eq!(string("\"\"\"${x}\"\"\""), "${x}");
+
+        let tokens = tokenize("\"\"\"\na\n\"\"\" x");
+        assert_eq!(tokens[0].span, Span::new(Position { line: 1, column: 1, offset: 0 }, Position { line: 3, column: 4, offset: 9 }));
+        assert_eq!(tokens[1].span.start, Position { line: 3, column: 5, offset: 10 });
     }
 
     #[test]
@@ -643,11 +1297,11 @@ mod tests {
     #[test]
     fn test_unexpected_tokens()
     {
-        assert_eq!(tokenize(":").get(0).unwrap().kind, TokenKind::UnexpectedToken);
-        assert_eq!(tokenize("&").get(0).unwrap().kind, TokenKind::UnexpectedToken);
-        assert_eq!(tokenize("&&").get(0).unwrap().kind, TokenKind::UnexpectedToken);
-        assert_eq!(tokenize("|").get(0).unwrap().kind, TokenKind::UnexpectedToken);
-        assert_eq!(tokenize("||").get(0).unwrap().kind, TokenKind::UnexpectedToken);
+        assert_eq!(lexical_error(&tokenize(":")[0]).kind, ParserErrorKind::UnexpectedToken(':'));
+        assert_eq!(lexical_error(&tokenize("&")[0]).kind, ParserErrorKind::UnexpectedToken('&'));
+        assert_eq!(lexical_error(&tokenize("&&")[1]).kind, ParserErrorKind::UnexpectedToken('&'));
+        assert_eq!(lexical_error(&tokenize("|")[0]).kind, ParserErrorKind::UnexpectedToken('|'));
+        assert_eq!(lexical_error(&tokenize("||")[1]).kind, ParserErrorKind::UnexpectedToken('|'));
     }
 
     #[test]
@@ -671,147 +1325,264 @@ mod tests {
         assert_eq!(tokenize("\r\n\n\r\n\n").get(0).unwrap().kind, TokenKind::Eof);
         assert_eq!(tokenize("\r\n\r\r\n\r").get(0).unwrap().kind, TokenKind::Eof);
         assert_eq!(tokenize("//Hello World!\n//Hello World!\n//Hello World!").get(0).unwrap().kind, TokenKind::Eof);
+        assert_eq!(tokenize("/**/").get(0).unwrap().kind, TokenKind::Eof);
+        assert_eq!(tokenize("/* Hello\r\nWorld! */").get(0).unwrap().kind, TokenKind::Eof);
+        assert_eq!(tokenize("/* /* nested */ // still a comment \n */").get(0).unwrap().kind, TokenKind::Eof);
+    }
+
+    #[test]
+    fn test_error_tokens()
+    {
+        let tokens = tokenize("print 1 € \"a\\q\\x80\" 0b12 ;");
+        let kinds: Vec<String> = tokens.iter().map(|token| token.kind.to_string()).collect();
+        assert_eq!(kinds, vec!["Print", "Number", "Error", "Error", "Error", "String", "Error", "Semicolon", "EndOfFile"]);
+        // errors come before the token they were found in, each with the offending code
+        let errors: Vec<(&str, u32, u32)> = tokens.iter()
+            .filter(|token| matches!(token.kind, TokenKind::Error(_)))
+            .map(|token| (lexical_error(token).text.as_str(), token.span.start.column, token.span.end.column))
+            .collect();
+        assert_eq!(errors, vec![("€", 9, 10), ("\\q", 13, 15), ("\\x80", 15, 19), ("2", 24, 25)]);
+        assert_eq!(tokens[7].span.start, Position { line: 1, column: 26, offset: 27 });
+
+        // positions and offending code are tracked across multi-line strings
+        let tokens = tokenize("\"é\r\n\" 1 #\n\"open");
+        assert_eq!(lexical_error(&tokens[2]).text, "#");
+        assert_eq!(tokens[2].span.start, Position { line: 2, column: 5, offset: 9 });
+        assert_eq!(lexical_error(&tokens[3]).text, "\"open");
+        assert_eq!(tokens[3].span, Span::new(Position { line: 3, column: 1, offset: 11 }, Position { line: 3, column: 6, offset: 16 }));
+    }
+
+    #[test]
+    fn test_unicode_identifiers() {
+        let mut interner = StringInterner::default();
+        let tokens = tokenize_with_interner("città 変数 _ñ1 x\u{301}y var", &mut interner);
+        assert_eq!(tokens[0].kind, TokenKind::Identifier(interner.get("città").unwrap()));
+        assert_eq!(tokens[1].kind, TokenKind::Identifier(interner.get("変数").unwrap()));
+        assert_eq!(tokens[2].kind, TokenKind::Identifier(interner.get("_ñ1").unwrap()));
+        // combining marks can continue an identifier
+        assert_eq!(tokens[3].kind, TokenKind::Identifier(interner.get("x\u{301}y").unwrap()));
+        assert_eq!(tokens[4].kind, TokenKind::Var);
+        // columns count characters, not bytes
+        assert_eq!(tokens[0].span, Span::new(Position { line: 1, column: 1, offset: 0 }, Position { line: 1, column: 6, offset: 6 }));
+        assert_eq!(tokens[1].span, Span::new(Position { line: 1, column: 7, offset: 7 }, Position { line: 1, column: 9, offset: 13 }));
+        assert_eq!(tokens[4].span.start, Position { line: 1, column: 18, offset: 24 });
+
+        assert_eq!(lexical_error(&tokenize("€")[0]).kind, ParserErrorKind::UnexpectedToken('€'));
+        assert_eq!(lexical_error(&tokenize("\u{301}")[0]).kind, ParserErrorKind::UnexpectedToken('\u{301}'));
+        assert_eq!(lexical_error(&tokenize("٣")[0]).kind, ParserErrorKind::UnexpectedToken('٣'));
+    }
+
+    #[test]
+    fn test_interpolation() {
+        let mut interner = StringInterner::default();
+        let tokens = tokenize_with_interner("\"a ${b} c ${ {d} } e\" }", &mut interner);
+        let kinds: Vec<TokenKind> = tokens.iter().map(|token| token.kind.clone()).collect();
+        assert_eq!(kinds, vec![
+            TokenKind::StringPart(Rc::new("a ".to_owned())),
+            TokenKind::Identifier(interner.get("b").unwrap()),
+            TokenKind::RightBrace,
+            TokenKind::StringPart(Rc::new(" c ".to_owned())),
+            TokenKind::LeftBrace,
+            TokenKind::Identifier(interner.get("d").unwrap()),
+            TokenKind::RightBrace,
+            TokenKind::RightBrace,
+            TokenKind::String(Rc::new(" e".to_owned())),
+            TokenKind::RightBrace,
+            TokenKind::Eof
+        ]);
+        assert_eq!(tokens[2].span, Span::new(Position { line: 1, column: 7, offset: 6 }, Position { line: 1, column: 8, offset: 7 }));
+        assert_eq!(tokens[3].span, Span::new(Position { line: 1, column: 8, offset: 7 }, Position { line: 1, column: 13, offset: 12 }));
+        assert_eq!(tokenize("\"$ \\${}\"").get(0).unwrap().kind, TokenKind::String(Rc::new("$ ${}".to_owned())));
+    }
+
+    #[test]
+    fn test_offsets() {
+        let code = "a\r\n€ \"é\" b // c\nd";
+        let mut interner = StringInterner::default();
+        let tokens: Vec<Token> = Lexer::new_with_offset(code, 10, &mut interner).collect();
+        let offsets: Vec<(u32, u32)> = tokens.iter().map(|token| (token.span.start.offset, token.span.end.offset)).collect();
+        assert_eq!(offsets, vec![(10, 11), (13, 16), (17, 21), (22, 23), (29, 30), (30, 30)]);
+        // the offsets slice the code of each token
+        let texts: Vec<&str> = tokens.iter().map(|token| &code[token.span.offsets().start - 10..token.span.offsets().end - 10]).collect();
+        assert_eq!(texts, vec!["a", "€", "\"é\"", "b", "d", ""]);
+        assert_eq!(tokens[1].span, Span::new(Position { line: 2, column: 1, offset: 13 }, Position { line: 2, column: 2, offset: 16 }));
+    }
+
+    #[test]
+    fn test_trivia() {
+        let code = "a \t b//c\r\n/* d */$ \n";
+        let mut interner = StringInterner::default();
+        let mut lexer = Lexer::new(code, &mut interner).with_trivia();
+        let tokens: Vec<Token> = lexer.by_ref().collect();
+        let trivia = lexer.take_trivia();
+        let kinds: Vec<(TriviaKind, &str)> = trivia.iter().map(|trivia| (trivia.kind, &code[trivia.span.offsets()])).collect();
+        assert_eq!(kinds, vec![
+            (TriviaKind::Whitespace, " \t "), (TriviaKind::LineComment, "//c"), (TriviaKind::Newline, "\r\n"), (TriviaKind::BlockComment, "/* d */"),
+            (TriviaKind::Skipped, "$"), (TriviaKind::Whitespace, " "), (TriviaKind::Newline, "\n")
+        ]);
+        assert!(matches!(tokens[2].kind, TokenKind::Error(_)));
+        assert_eq!(trivia[2].span.end, Position { line: 2, column: 1, offset: 10 });
+        assert!(lexer.take_trivia().is_empty());
+        // without the lossless mode nothing is kept
+        let mut lexer = Lexer::new(code, &mut interner);
+        lexer.by_ref().for_each(drop);
+        assert!(lexer.take_trivia().is_empty());
+    }
+
+    #[test]
+    fn test_block_comments() {
+        let tokens = tokenize("a /* one\n/* two */ **/ b/**/c");
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(tokens[1].span, Span::new(Position { line: 2, column: 15, offset: 23 }, Position { line: 2, column: 16, offset: 24 }));
+        assert_eq!(tokens[2].span.start, Position { line: 2, column: 20, offset: 28 });
+        assert_eq!(tokenize("a /*/ b */ / c").iter().map(|token| token.kind.clone()).collect::<Vec<TokenKind>>()[1], TokenKind::Slash);
+
+        let tokens = tokenize("print 1;\n/* open /* nested */\n");
+        assert_eq!(tokens.len(), 5);
+        assert_eq!(tokens[4].kind, TokenKind::Eof);
+        assert_eq!(lexical_error(&tokens[3]).kind, ParserErrorKind::UnterminatedComment);
+        assert_eq!(tokens[3].span, Span::new(Position { line: 2, column: 1, offset: 9 }, Position { line: 3, column: 1, offset: 30 }));
     }
 
     #[test]
     fn test_position_1() {
-        assert_eq!(tokenize("").get(0).unwrap().position, Position { line: 1, column: 1});
+        assert_eq!(tokenize("").get(0).unwrap().span.start, Position { line: 1, column: 1, offset: 0 });
     }
 
     #[test]
     fn test_position_2() {
-        assert_eq!(tokenize(" ").get(0).unwrap().position, Position { line: 1, column: 2});
+        assert_eq!(tokenize(" ").get(0).unwrap().span.start, Position { line: 1, column: 2, offset: 1 });
     }
 
     #[test]
     fn test_position_3() {
-        assert_eq!(tokenize("   ").get(0).unwrap().position, Position { line: 1, column: 4});
+        assert_eq!(tokenize("   ").get(0).unwrap().span.start, Position { line: 1, column: 4, offset: 3 });
     }
 
     #[test]
     fn test_position_4() {
-        assert_eq!(tokenize("\r\n").get(0).unwrap().position, Position { line: 2, column: 1});
+        assert_eq!(tokenize("\r\n").get(0).unwrap().span.start, Position { line: 2, column: 1, offset: 2 });
     }
 
     #[test]
     fn test_position_5() {
-        assert_eq!(tokenize("\r").get(0).unwrap().position, Position { line: 2, column: 1});
+        assert_eq!(tokenize("\r").get(0).unwrap().span.start, Position { line: 2, column: 1, offset: 1 });
     }
 
     #[test]
     fn test_position_6() {
-        assert_eq!(tokenize("\n").get(0).unwrap().position, Position { line: 2, column: 1});
+        assert_eq!(tokenize("\n").get(0).unwrap().span.start, Position { line: 2, column: 1, offset: 1 });
     }
 
     #[test]
     fn test_position_7() {
-        assert_eq!(tokenize("\n\r").get(0).unwrap().position, Position { line: 3, column: 1});
+        assert_eq!(tokenize("\n\r").get(0).unwrap().span.start, Position { line: 3, column: 1, offset: 2 });
     }
 
     #[test]
     fn test_position_8() {
-        assert_eq!(tokenize("\t").get(0).unwrap().position, Position { line: 1, column: 2});
+        assert_eq!(tokenize("\t").get(0).unwrap().span.start, Position { line: 1, column: 2, offset: 1 });
     }
 
     #[test]
     fn test_position_9() {
-        assert_eq!(tokenize("//Hello World!").get(0).unwrap().position, Position { line: 1, column: 3});
+        assert_eq!(tokenize("//Hello World!").get(0).unwrap().span.start, Position { line: 1, column: 15, offset: 14 });
     }
 
     #[test]
     fn test_position_10() {
-        assert_eq!(tokenize("//Hello World!\n").get(0).unwrap().position, Position { line: 2, column: 1});
+        assert_eq!(tokenize("//Hello World!\n").get(0).unwrap().span.start, Position { line: 2, column: 1, offset: 15 });
     }
 
     #[test]
     fn test_position_11() {
-        assert_eq!(tokenize("\r\n\n\r").get(0).unwrap().position, Position { line: 4, column: 1});
+        assert_eq!(tokenize("\r\n\n\r").get(0).unwrap().span.start, Position { line: 4, column: 1, offset: 4 });
     }
 
     #[test]
     fn test_position_13() {
         let tokens = tokenize("var foo;");
 
-        assert_eq!(tokens.get(0).unwrap().position, Position { line: 1, column: 1});
+        assert_eq!(tokens.get(0).unwrap().span.start, Position { line: 1, column: 1, offset: 0 });
 
-        assert_eq!(tokens.get(1).unwrap().position, Position { line: 1, column: 5});
+        assert_eq!(tokens.get(1).unwrap().span.start, Position { line: 1, column: 5, offset: 4 });
 
         assert_eq!(tokens.get(2).unwrap().kind, TokenKind::Semicolon);
-        assert_eq!(tokens.get(2).unwrap().position, Position { line: 1, column: 8});
+        assert_eq!(tokens.get(2).unwrap().span.start, Position { line: 1, column: 8, offset: 7 });
     }
 
     #[test]
     fn test_position_14() {
         let tokens = tokenize("  var foo = \"  \"  ;");
 
-        assert_eq!(tokens.get(0).unwrap().position, Position { line: 1, column: 3});
+        assert_eq!(tokens.get(0).unwrap().span.start, Position { line: 1, column: 3, offset: 2 });
 
-        assert_eq!(tokens.get(1).unwrap().position, Position { line: 1, column: 7});
+        assert_eq!(tokens.get(1).unwrap().span.start, Position { line: 1, column: 7, offset: 6 });
 
         assert_eq!(tokens.get(2).unwrap().kind, TokenKind::Equal);
-        assert_eq!(tokens.get(2).unwrap().position, Position { line: 1, column: 11});
+        assert_eq!(tokens.get(2).unwrap().span.start, Position { line: 1, column: 11, offset: 10 });
 
-        assert_eq!(tokens.get(3).unwrap().position, Position { line: 1, column: 13});
+        assert_eq!(tokens.get(3).unwrap().span.start, Position { line: 1, column: 13, offset: 12 });
 
         assert_eq!(tokens.get(4).unwrap().kind, TokenKind::Semicolon);
-        assert_eq!(tokens.get(4).unwrap().position, Position { line: 1, column: 19});
+        assert_eq!(tokens.get(4).unwrap().span.start, Position { line: 1, column: 19, offset: 18 });
     }
 
     #[test]
     fn test_position_15() {
         let tokens = tokenize("\r\n\n\r  var foo = \"  \"  \t;");
-        assert_eq!(tokens.get(0).unwrap().position, Position { line: 4, column: 3});
+        assert_eq!(tokens.get(0).unwrap().span.start, Position { line: 4, column: 3, offset: 6 });
 
-        assert_eq!(tokens.get(1).unwrap().position, Position { line: 4, column: 7});
+        assert_eq!(tokens.get(1).unwrap().span.start, Position { line: 4, column: 7, offset: 10 });
 
         assert_eq!(tokens.get(2).unwrap().kind, TokenKind::Equal);
-        assert_eq!(tokens.get(2).unwrap().position, Position { line: 4, column: 11});
+        assert_eq!(tokens.get(2).unwrap().span.start, Position { line: 4, column: 11, offset: 14 });
 
-        assert_eq!(tokens.get(3).unwrap().position, Position { line: 4, column: 13});
+        assert_eq!(tokens.get(3).unwrap().span.start, Position { line: 4, column: 13, offset: 16 });
 
         assert_eq!(tokens.get(4).unwrap().kind, TokenKind::Semicolon);
-        assert_eq!(tokens.get(4).unwrap().position, Position { line: 4, column: 20});
+        assert_eq!(tokens.get(4).unwrap().span.start, Position { line: 4, column: 20, offset: 23 });
     }
 
     #[test]
     fn test_position_16() {
         let tokens = tokenize("class Bar < Foo {\n   do_stuff() {\n\n   print \"Hello!\";\r\n   }\r}");
         assert_eq!(tokens.get(0).unwrap().kind, TokenKind::Class);
-        assert_eq!(tokens.get(0).unwrap().position, Position { line: 1, column: 1});
+        assert_eq!(tokens.get(0).unwrap().span.start, Position { line: 1, column: 1, offset: 0 });
 
-        assert_eq!(tokens.get(1).unwrap().position, Position { line: 1, column: 7});
+        assert_eq!(tokens.get(1).unwrap().span.start, Position { line: 1, column: 7, offset: 6 });
 
         assert_eq!(tokens.get(2).unwrap().kind, TokenKind::Less);
-        assert_eq!(tokens.get(2).unwrap().position, Position { line: 1, column: 11});
+        assert_eq!(tokens.get(2).unwrap().span.start, Position { line: 1, column: 11, offset: 10 });
 
-        assert_eq!(tokens.get(3).unwrap().position, Position { line: 1, column: 13});
+        assert_eq!(tokens.get(3).unwrap().span.start, Position { line: 1, column: 13, offset: 12 });
 
         assert_eq!(tokens.get(4).unwrap().kind, TokenKind::LeftBrace);
-        assert_eq!(tokens.get(4).unwrap().position, Position { line: 1, column: 17});
+        assert_eq!(tokens.get(4).unwrap().span.start, Position { line: 1, column: 17, offset: 16 });
 
-        assert_eq!(tokens.get(5).unwrap().position, Position { line: 2, column: 4});
+        assert_eq!(tokens.get(5).unwrap().span.start, Position { line: 2, column: 4, offset: 21 });
 
         assert_eq!(tokens.get(6).unwrap().kind, TokenKind::LeftParen);
-        assert_eq!(tokens.get(6).unwrap().position, Position { line: 2, column: 12});
+        assert_eq!(tokens.get(6).unwrap().span.start, Position { line: 2, column: 12, offset: 29 });
 
         assert_eq!(tokens.get(7).unwrap().kind, TokenKind::RightParen);
-        assert_eq!(tokens.get(7).unwrap().position, Position { line: 2, column: 13});
+        assert_eq!(tokens.get(7).unwrap().span.start, Position { line: 2, column: 13, offset: 30 });
 
         assert_eq!(tokens.get(8).unwrap().kind, TokenKind::LeftBrace);
-        assert_eq!(tokens.get(8).unwrap().position, Position { line: 2, column: 15});
+        assert_eq!(tokens.get(8).unwrap().span.start, Position { line: 2, column: 15, offset: 32 });
 
-        assert_eq!(tokens.get(9).unwrap().position, Position { line: 4, column: 4});
+        assert_eq!(tokens.get(9).unwrap().span.start, Position { line: 4, column: 4, offset: 38 });
 
-        assert_eq!(tokens.get(10).unwrap().position, Position { line: 4, column: 10});
+        assert_eq!(tokens.get(10).unwrap().span.start, Position { line: 4, column: 10, offset: 44 });
 
         assert_eq!(tokens.get(11).unwrap().kind, TokenKind::Semicolon);
-        assert_eq!(tokens.get(11).unwrap().position, Position { line: 4, column: 18});
+        assert_eq!(tokens.get(11).unwrap().span.start, Position { line: 4, column: 18, offset: 52 });
 
         assert_eq!(tokens.get(12).unwrap().kind, TokenKind::RightBrace);
-        assert_eq!(tokens.get(12).unwrap().position, Position { line: 5, column: 4});
+        assert_eq!(tokens.get(12).unwrap().span.start, Position { line: 5, column: 4, offset: 58 });
 
         assert_eq!(tokens.get(13).unwrap().kind, TokenKind::RightBrace);
-        assert_eq!(tokens.get(13).unwrap().position, Position { line: 6, column: 1});
+        assert_eq!(tokens.get(13).unwrap().span.start, Position { line: 6, column: 1, offset: 60 });
     }
 
     #[test]
@@ -862,14 +1633,14 @@ mod tests {
                 let filter_expect: Vec<&str> = line.split("// expect: ").collect();
                 if let Some(result) = filter_expect.last()
                 {
-                    let expected_result: Vec<&str> = result.split(" ").into_iter().collect();
+                    let expected_result: Vec<&str> = result.split(" ").collect();
                     expected_results.push(expected_result);
                 }
             }
         }
 
         let it = expected_results.iter().zip(result.iter());
-        for (_, (expected, token)) in it.enumerate() {
+        for (expected, token) in it {
             match expected[0] {
                 "NUMBER" => {
                     let expected_tk_kind = TokenKind::Number(expected[2].parse::<f64>().unwrap());
@@ -1,8 +1,12 @@
 mod keywords;
 mod scanner;
-mod lexer;
+pub mod lexer;
+#[allow(clippy::module_inception)]
 pub mod parser;
 pub mod resolver;
 pub mod tokens;
 pub mod position;
-pub mod types;
\ No newline at end of file
+pub mod source_map;
+pub mod cst;
+pub mod types;
+pub mod printer;
\ No newline at end of file
@@ -1,29 +1,70 @@
 use std::cell::RefCell;
-use std::io::Write;
 use std::rc::Rc;
 
 use rustc_hash::FxHashMap;
 use string_interner::StringInterner;
 
 use crate::alias::IdentifierSymbol;
-use crate::error::{ExecutionResult, InternalErrorKind, LoxError, ParserErrorKind};
+use crate::error::{InternalErrorKind, LoxError, ParserErrorKind};
+use crate::utils::native_stack::ensure_sufficient_stack;
 use crate::utils::peekable_iterator::Peekable;
 
 use super::lexer::Lexer;
+use super::position::{Position, Span};
 use super::tokens::{Token, TokenKind, TokenSource};
-use super::types::{AssignExpr, BinaryExpr, BinaryOperatorKind, CallExpr, ClassDeclaration, Expr, ExprKind, FunctionDeclaration, GetExpr, Identifier, IfElseStmt, IfStmt, Literal, LogicalExpr, LogicalOperatorKind, Operator, SetExpr, Stmt, UnaryExpr, UnaryOperatorKind, WhileStmt};
+use super::types::{AssignExpr, BinaryExpr, BinaryOperatorKind, CallExpr, ClassDeclaration, Expr, ExprKind, FunctionDeclaration, GetExpr, Identifier, IfElseStmt, IfStmt, Literal, LogicalExpr, LogicalOperatorKind, Operator, SetExpr, Stmt, StmtKind, UnaryExpr, UnaryOperatorKind, WhileStmt};
 
-pub struct Parser<T: Write>
+/// Passes on the tokens of the lexer to the parser, reporting the lexical errors instead.
+struct LexicalErrorReporter<'a, 'b>
+{
+    lexer: &'a mut Lexer<'b>,
+    errors: Rc<RefCell<Vec<LoxError>>>,
+    /// Start of each token following a lexical error. A syntax error found there is a consequence of the lexical one.
+    after_errors: Rc<RefCell<Vec<Position>>>,
+    follows_error: bool
+}
+
+impl Iterator for LexicalErrorReporter<'_, '_>
+{
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token>
+    {
+        loop {
+            let token = self.lexer.next()?;
+            match &token.kind {
+                TokenKind::Error(error) => {
+                    self.errors.borrow_mut().push(LoxError::parser_error(error.kind.clone(), token.span));
+                    self.follows_error = true;
+                },
+                _ => {
+                    if self.follows_error {
+                        self.follows_error = false;
+                        self.after_errors.borrow_mut().push(token.span.start);
+                    }
+                    return Some(token);
+                }
+            }
+        }
+    }
+}
+
+pub struct Parser
 {
     in_loop: u32,
-    error_logger: Rc<RefCell<T>>,
     init_symbol: IdentifierSymbol
 }
 
-impl <T: Write> Parser<T>
+impl Parser
 {
-    pub fn new(error_logger: Rc<RefCell<T>>, init_symbol: IdentifierSymbol) -> Self {
-        Self { in_loop: 0, error_logger, init_symbol }
+    pub fn new(init_symbol: IdentifierSymbol) -> Self {
+        Self { in_loop: 0, init_symbol }
+    }
+
+    /// Runs `parse`, first growing the native stack if it is about to run out.
+    fn nested<R>(&mut self, token_source: &mut TokenSource, parse: impl FnOnce(&mut Self, &mut TokenSource) -> Result<R, LoxError>) -> Result<R, LoxError>
+    {
+        ensure_sufficient_stack(|| parse(self, token_source))
     }
 
     /// Handles syntactical errors when the parser meets one.
@@ -59,25 +100,30 @@ impl <T: Write> Parser<T>
     /// Parses the source code using a "recursive descent" parsing alghoritm.
     ///
     /// Returns a `Vec` of `Stmt`. There are various kind of `Stmt` variants (eg. `Var`, `Block`, `If`, `Expr` etc.).
+    /// If any lexical or syntactical error is found, returns all of them in the order they were met.
+    /// Each lexical error is reported once, the syntactical errors it causes in the following token are not.
+    /// The offsets of the spans start from `start_offset`, the start of the code in the `SourceMap`.
     ///
     /// Grammar:
     /// program     -> declaration* EOF ;
     ///
-    pub fn parse(&mut self, code: &str, interner: &mut StringInterner) -> Result<Vec<Stmt>, ExecutionResult>
+    pub fn parse(&mut self, code: &str, start_offset: u32, interner: &mut StringInterner) -> Result<Vec<Stmt>, Vec<LoxError>>
     {
         let mut statements: Vec<Stmt> = vec![];
 
-        let mut is_error  : bool      = false;
+        let errors: Rc<RefCell<Vec<LoxError>>> = Rc::new(RefCell::new(Vec::new()));
+        let after_errors: Rc<RefCell<Vec<Position>>> = Rc::new(RefCell::new(Vec::new()));
 
-        let mut lexer       : Lexer<'_, T>      = Lexer::new(code, interner, self.error_logger.clone());
-        let mut token_source: TokenSource    = Peekable::new(&mut lexer);
+        let mut lexer       : Lexer<'_>      = Lexer::new_with_offset(code, start_offset, interner);
+        let mut reporter    : LexicalErrorReporter = LexicalErrorReporter { lexer: &mut lexer, errors: Rc::clone(&errors), after_errors: Rc::clone(&after_errors), follows_error: false };
+        let mut token_source: TokenSource    = Peekable::new(&mut reporter);
 
         loop {
             if token_source.is_at_end() {
-                if is_error {
-                    return Err(ExecutionResult::ParserError);
-                } else {
+                if errors.borrow().is_empty() {
                     return Ok(statements);
+                } else {
+                    return Err(errors.take());
                 }
             }
             let result = self.declaration(&mut token_source);
@@ -86,8 +132,10 @@ impl <T: Write> Parser<T>
                     statements.push(stmt);
                 }
                 Err(err) => {
-                    is_error = true;
-                    let _ = writeln!(self.error_logger.borrow_mut(), "{}", err);
+                    let is_caused_by_lexical_error = matches!(err.span, Some(span) if after_errors.borrow().contains(&span.start));
+                    if !is_caused_by_lexical_error {
+                        errors.borrow_mut().push(err);
+                    }
 
                     // In case of a syntactical error call `synchronize` to skip to the next statement to avoids spitting out gibberish error messages.
                     self.synchronize(&mut token_source);
@@ -109,17 +157,18 @@ impl <T: Write> Parser<T>
     ///
     fn declaration(&mut self, token_source: &mut TokenSource)  -> Result<Stmt, LoxError>
     {
+        let start = token_source.peek().unwrap().span;
         if token_source.consume_if(TokenKind::Var)
         {
-            self.var_declaration(token_source)
+            self.var_declaration(token_source, start)
         }
         else if token_source.consume_if(TokenKind::Fun)
         {
-            self.fun_declaration(token_source)
+            self.fun_declaration(token_source, start)
         }
         else if token_source.consume_if(TokenKind::Class)
         {
-            self.class_declaration(token_source)
+            self.class_declaration(token_source, start)
         }
         else
         {
@@ -135,7 +184,7 @@ impl <T: Write> Parser<T>
     /// function    -> IDENTIFIER "(" parameters? ")" block ;
     /// parameters  -> IDENTIFIER ( "," IDENTIFIER )* ;
     ///
-    fn class_declaration(&mut self, token_source: &mut TokenSource) -> Result<Stmt, LoxError>
+    fn class_declaration(&mut self, token_source: &mut TokenSource, start: Span) -> Result<Stmt, LoxError>
     {
         let class_name = token_source.consume_identifier("Expect class name.")?;
         let mut class_stmt;
@@ -145,7 +194,7 @@ impl <T: Write> Parser<T>
         {
             token_source.consume();
             let superclass_name = token_source.consume_identifier("Expect superclass name")?;
-            let superclass_expr = Expr::new(ExprKind::Variable(superclass_name));
+            let superclass_expr = Expr::new(ExprKind::Variable(superclass_name.clone()), superclass_name.span);
             class_stmt = ClassDeclaration::new(class_name, Some(superclass_expr));
         }
         else
@@ -161,9 +210,9 @@ impl <T: Write> Parser<T>
             methods.insert(method_declaration.identifier.name, Rc::new(method_declaration));
         }
         class_stmt.methods = methods;
-        token_source.consume_or_error(TokenKind::RightBrace, "Expect '}' after class body.")?;
+        let right_brace = token_source.consume_or_error(TokenKind::RightBrace, "Expect '}' after class body.")?;
 
-        Ok(Stmt::ClassDeclaration(Rc::new(class_stmt)))
+        Ok(Stmt::new(StmtKind::ClassDeclaration(Rc::new(class_stmt)), start.to(right_brace.span)))
     }
 
     /// Parses a function declaration (eg.fun foo(param1, param2, ...) { ... }).
@@ -175,9 +224,11 @@ impl <T: Write> Parser<T>
     /// function    -> IDENTIFIER "(" parameters? ")" block ;
     /// parameters  -> IDENTIFIER ( "," IDENTIFIER )* ;
     ///
-    fn fun_declaration(&mut self, token_source: &mut TokenSource)  -> Result<Stmt, LoxError>
+    fn fun_declaration(&mut self, token_source: &mut TokenSource, start: Span)  -> Result<Stmt, LoxError>
     {
-        Ok(Stmt::FunctionDeclaration(Rc::new(self.create_fun_declaration(token_source, false)?)))
+        let declaration = self.create_fun_declaration(token_source, false)?;
+        let span = start.to(declaration.span);
+        Ok(Stmt::new(StmtKind::FunctionDeclaration(Rc::new(declaration)), span))
     }
 
     /// Common method used to parse both a function declaration or a class method. Used in `fun_declaration` and in `class_declaration`.
@@ -200,14 +251,15 @@ impl <T: Write> Parser<T>
                 }
             }
         }
-        let right_paren_position = token_source.consume_or_error(TokenKind::RightParen, "Expect ')' after parameters.")?.position;
-        token_source.consume_or_error(TokenKind::LeftBrace, format!("Expect '{{' before {} body.", kind).as_str())?;
-        let body: Stmt = self.block_statement(token_source)?;
+        let right_paren_span = token_source.consume_or_error(TokenKind::RightParen, "Expect ')' after parameters.")?.span;
+        let left_brace = token_source.consume_or_error(TokenKind::LeftBrace, format!("Expect '{{' before {} body.", kind).as_str())?;
+        let body: Stmt = self.block_statement(token_source, left_brace.span)?;
         if args.len() > 255 {
-            return Err(LoxError::parser_error(ParserErrorKind::TooManyParameters, right_paren_position));
+            return Err(LoxError::parser_error(ParserErrorKind::TooManyParameters, right_paren_span));
         }
-        let stmts = match body {
-            Stmt::Block(stmts) => {
+        let span = identifier.span.to(body.span);
+        let stmts = match body.into_kind() {
+            StmtKind::Block(stmts) => {
                 stmts
             },
             _ => {
@@ -218,8 +270,7 @@ impl <T: Write> Parser<T>
         if is_method && identifier.name == self.init_symbol {
             is_initializer = true;
         }
-        let declaration = FunctionDeclaration::new(identifier, args, stmts, is_initializer);
-        Ok(declaration)
+        Ok(FunctionDeclaration::new(identifier, args, stmts, is_initializer, span))
     }
 
     /// Parses a variable declaration (eg.var foo = something;).
@@ -229,16 +280,16 @@ impl <T: Write> Parser<T>
     /// Grammar:
     /// varDecl     -> "var" IDENTIFIER ( "=" expression )? ";" ;
     ///
-    fn var_declaration(&mut self, token_source: &mut TokenSource)  -> Result<Stmt, LoxError>
+    fn var_declaration(&mut self, token_source: &mut TokenSource, start: Span)  -> Result<Stmt, LoxError>
     {
         let identifier = token_source.consume_identifier("Expect variable name.")?;
         if token_source.consume_if(TokenKind::Equal) {
             let expr: Expr = self.expression(token_source)?;
-            token_source.consume_or_error(TokenKind::Semicolon, "Expect ';' after variable declaration.")?;
-            Ok(Stmt::Var(identifier, Some(expr)))
+            let semicolon = token_source.consume_or_error(TokenKind::Semicolon, "Expect ';' after variable declaration.")?;
+            Ok(Stmt::new(StmtKind::Var(identifier, Some(expr)), start.to(semicolon.span)))
         } else {
-            token_source.consume_or_error(TokenKind::Semicolon, "Expect ';' after variable declaration.")?;
-            Ok(Stmt::Var(identifier, None))
+            let semicolon = token_source.consume_or_error(TokenKind::Semicolon, "Expect ';' after variable declaration.")?;
+            Ok(Stmt::new(StmtKind::Var(identifier, None), start.to(semicolon.span)))
         }
     }
 
@@ -248,48 +299,54 @@ impl <T: Write> Parser<T>
     /// statement   -> exprStmt | forStmt | ifStmt | printStmt | returnStmt | whileStmt | block ;
     ///
     fn statement(&mut self, token_source: &mut TokenSource) -> Result<Stmt, LoxError>
+    {
+        self.nested(token_source, Self::statement_kind)
+    }
+
+    fn statement_kind(&mut self, token_source: &mut TokenSource) -> Result<Stmt, LoxError>
     {
         let token = token_source.peek().unwrap();
+        let start = token.span;
         match token.kind {
             TokenKind::Print => {
                 token_source.consume();
-                self.print_statement(token_source)
+                self.print_statement(token_source, start)
             },
             TokenKind::LeftBrace => {
                 token_source.consume();
-                self.block_statement(token_source)
+                self.block_statement(token_source, start)
             },
             TokenKind::If => {
                 token_source.consume();
-                self.if_statement(token_source)
+                self.if_statement(token_source, start)
             },
             TokenKind::While => {
                 self.in_loop += 1;
                 token_source.consume();
-                let while_stmt = self.while_statement(token_source);
+                let while_stmt = self.while_statement(token_source, start);
                 self.in_loop -= 1;
                 while_stmt
             },
             TokenKind::For => {
                 self.in_loop += 1;
                 token_source.consume();
-                let for_stmt = self.for_statement(token_source);
+                let for_stmt = self.for_statement(token_source, start);
                 self.in_loop -= 1;
                 for_stmt
             },
             TokenKind::Break => {
                 if self.in_loop < 1 {
-                    return Err(LoxError::parser_error(ParserErrorKind::BreakOutsideLoop, token.position));
+                    return Err(LoxError::parser_error(ParserErrorKind::BreakOutsideLoop, token.span).with_help("`break` can only be used inside a `while` or `for` loop"));
                 }
                 token_source.consume();
-                self.break_statement(token_source)
+                self.break_statement(token_source, start)
             },
             TokenKind::Continue => {
                 if self.in_loop < 1 {
-                    return Err(LoxError::parser_error(ParserErrorKind::ContinueOutsideLoop, token.position))
+                    return Err(LoxError::parser_error(ParserErrorKind::ContinueOutsideLoop, token.span).with_help("`continue` can only be used inside a `while` or `for` loop"));
                 }
                 token_source.consume();
-                self.continue_statement(token_source)
+                self.continue_statement(token_source, start)
             },
             TokenKind::Return => {
                 self.return_statement(token_source)
@@ -313,8 +370,8 @@ impl <T: Write> Parser<T>
         } else {
             None
         };
-        token_source.consume_or_error(TokenKind::Semicolon, "Expect ';' after return value.")?;
-        Ok(Stmt::Return(expr, return_token.position))
+        let semicolon = token_source.consume_or_error(TokenKind::Semicolon, "Expect ';' after return value.")?;
+        Ok(Stmt::new(StmtKind::Return(expr, return_token.span), return_token.span.to(semicolon.span)))
     }
 
     /// Parses a continue statement.
@@ -324,10 +381,10 @@ impl <T: Write> Parser<T>
     /// Grammar:
     /// continueStmt  -> "continue" ";" ;
     ///
-    fn continue_statement(&mut self, token_source: &mut TokenSource) -> Result<Stmt, LoxError>
+    fn continue_statement(&mut self, token_source: &mut TokenSource, start: Span) -> Result<Stmt, LoxError>
     {
-        token_source.consume_or_error(TokenKind::Semicolon, "Expect ';' after 'continue'.")?;
-        Ok(Stmt::Continue)
+        let semicolon = token_source.consume_or_error(TokenKind::Semicolon, "Expect ';' after 'continue'.")?;
+        Ok(Stmt::new(StmtKind::Continue, start.to(semicolon.span)))
     }
 
     /// Parses a break statement.
@@ -337,10 +394,10 @@ impl <T: Write> Parser<T>
     /// Grammar:
     /// continueStmt  -> "break" ";" ;
     ///
-    fn break_statement(&mut self, token_source: &mut TokenSource) -> Result<Stmt, LoxError>
+    fn break_statement(&mut self, token_source: &mut TokenSource, start: Span) -> Result<Stmt, LoxError>
     {
-        token_source.consume_or_error(TokenKind::Semicolon, "Expect ';' after 'break'.")?;
-        Ok(Stmt::Break)
+        let semicolon = token_source.consume_or_error(TokenKind::Semicolon, "Expect ';' after 'break'.")?;
+        Ok(Stmt::new(StmtKind::Break, start.to(semicolon.span)))
     }
 
     /// Parses a `for` statement.
@@ -349,7 +406,7 @@ impl <T: Write> Parser<T>
     /// Grammar:
     /// forStmt     -> "for" "(" ( varDecl | exprStmt | ";" ) expression? ";" expression? ")" statement ;
     ///
-    fn for_statement(&mut self, token_source: &mut TokenSource) -> Result<Stmt, LoxError>
+    fn for_statement(&mut self, token_source: &mut TokenSource, start: Span) -> Result<Stmt, LoxError>
     {
         //consume left paren first
         token_source.consume_or_error(TokenKind::LeftParen, "Expect '(' after 'for'.")?;
@@ -357,8 +414,9 @@ impl <T: Write> Parser<T>
         //parse initializer
         let opt_initializer =
             if !token_source.check(TokenKind::Semicolon) {
+                let initializer_start = token_source.peek().unwrap().span;
                 if token_source.consume_if(TokenKind::Var) {
-                    Some(self.var_declaration(token_source)?)
+                    Some(self.var_declaration(token_source, initializer_start)?)
                 } else {
                     Some(self.expression_statement(token_source)?)
                 }
@@ -372,7 +430,9 @@ impl <T: Write> Parser<T>
             if !token_source.check(TokenKind::Semicolon) {
                 self.expression(token_source)?
             } else {
-                Expr::new(ExprKind::Literal(Literal::True(token_source.peek().unwrap().position)))
+                //the condition is missing: the generated `true` covers no code
+                let position = token_source.peek().unwrap().span.start;
+                Expr::new(ExprKind::Literal(Literal::True(Span::new(position, position))), Span::new(position, position))
             };
         token_source.consume_or_error(TokenKind::Semicolon, "Expect ';' after loop condition.")?;
 
@@ -388,21 +448,24 @@ impl <T: Write> Parser<T>
         //parse body
         let body = self.statement(token_source)?;
 
-        //desugaring phase
+        //desugaring phase: the generated statements span the whole loop, except the block running the body and the increment
+        let span = start.to(body.span);
         let body_plus_increment =
-            if opt_increment.is_some() {
-                Stmt::Block(vec![body, Stmt::Expr(opt_increment.unwrap())])
-            } else {
-                body
+            match opt_increment {
+                Some(increment) => {
+                    let body_span = body.span;
+                    let increment_span = increment.span;
+                    Stmt::new(StmtKind::Block(vec![body, Stmt::new(StmtKind::Expr(increment), increment_span)]), body_span)
+                },
+                None => body
             };
 
-        let while_stmt = Stmt::While(Box::new(WhileStmt {condition: condition_expr, body: body_plus_increment })) ;
+        let while_stmt = Stmt::new(StmtKind::While(Box::new(WhileStmt {condition: condition_expr, body: body_plus_increment })), span);
 
         let  initializer_plus_while =
-            if opt_initializer.is_some() {
-                Stmt::Block(vec![opt_initializer.unwrap(), while_stmt])
-            } else {
-                while_stmt
+            match opt_initializer {
+                Some(initializer) => Stmt::new(StmtKind::Block(vec![initializer, while_stmt]), span),
+                None => while_stmt
             };
 
         Ok(initializer_plus_while)
@@ -414,13 +477,14 @@ impl <T: Write> Parser<T>
     /// Grammar:
     /// whileStmt   -> "while" "(" expression ")" statement ;
     ///
-    fn while_statement(&mut self, token_source: &mut TokenSource) -> Result<Stmt, LoxError>
+    fn while_statement(&mut self, token_source: &mut TokenSource, start: Span) -> Result<Stmt, LoxError>
     {
         token_source.consume_or_error(TokenKind::LeftParen, "Expect '(' after 'while'.")?;
         let expr = self.expression(token_source)?;
         token_source.consume_or_error(TokenKind::RightParen, "Expect ')' after while condition.")?;
         let stmt = self.statement(token_source)?;
-        Ok(Stmt::While(Box::new(WhileStmt { condition: expr, body: stmt })))
+        let span = start.to(stmt.span);
+        Ok(Stmt::new(StmtKind::While(Box::new(WhileStmt { condition: expr, body: stmt })), span))
     }
 
     /// Parses an `if` statement.
@@ -428,7 +492,7 @@ impl <T: Write> Parser<T>
     /// Grammar:
     /// ifStmt  -> "if" "(" expression ")" statement ( "else" statement )? ;
     ///
-    fn if_statement(&mut self, token_source: &mut TokenSource) -> Result<Stmt, LoxError>
+    fn if_statement(&mut self, token_source: &mut TokenSource, start: Span) -> Result<Stmt, LoxError>
     {
         token_source.consume_or_error(TokenKind::LeftParen, "Expect '(' after 'if'.")?;
         let condition = self.expression(token_source)?;
@@ -436,9 +500,12 @@ impl <T: Write> Parser<T>
         let then_stmt = self.statement(token_source)?;
 
         if token_source.consume_if(TokenKind::Else) {
-            Ok(Stmt::IfElse(Box::new(IfElseStmt { condition, then_stmt, else_stmt: self.statement(token_source)? })))
+            let else_stmt = self.statement(token_source)?;
+            let span = start.to(else_stmt.span);
+            Ok(Stmt::new(StmtKind::IfElse(Box::new(IfElseStmt { condition, then_stmt, else_stmt })), span))
         } else {
-            Ok(Stmt::If(Box::new(IfStmt { condition, then_stmt })))
+            let span = start.to(then_stmt.span);
+            Ok(Stmt::new(StmtKind::If(Box::new(IfStmt { condition, then_stmt })), span))
         }
     }
 
@@ -447,14 +514,14 @@ impl <T: Write> Parser<T>
     /// Grammar:
     /// block   -> "{" declaration* "}" ;
     ///
-    fn block_statement(&mut self, token_source: &mut TokenSource) -> Result<Stmt, LoxError>
+    fn block_statement(&mut self, token_source: &mut TokenSource, start: Span) -> Result<Stmt, LoxError>
     {
         let mut statements: Vec<Stmt> = vec![];
         while !token_source.check(TokenKind::RightBrace) && !token_source.is_at_end() {
             statements.push(self.declaration(token_source)?);
         }
-        token_source.consume_or_error(TokenKind::RightBrace, "Expect '}' after block.")?;
-        Ok(Stmt::Block(statements))
+        let right_brace = token_source.consume_or_error(TokenKind::RightBrace, "Expect '}' after block.")?;
+        Ok(Stmt::new(StmtKind::Block(statements), start.to(right_brace.span)))
     }
 
     /// Parses a `print` statement.
@@ -462,11 +529,11 @@ impl <T: Write> Parser<T>
     /// Grammar:
     /// printStmt   -> "print" expression ";" ;
     ///
-    fn print_statement(&mut self, token_source: &mut TokenSource) -> Result<Stmt, LoxError>
+    fn print_statement(&mut self, token_source: &mut TokenSource, start: Span) -> Result<Stmt, LoxError>
     {
         let expr = self.expression(token_source)?;
-        token_source.consume_or_error(TokenKind::Semicolon, "Expect ';' after value.")?;
-        Ok(Stmt::Print(expr))
+        let semicolon = token_source.consume_or_error(TokenKind::Semicolon, "Expect ';' after value.")?;
+        Ok(Stmt::new(StmtKind::Print(expr), start.to(semicolon.span)))
     }
 
     /// Parses an expression statement statement (an expression used in a place where a statement is expected).
@@ -477,8 +544,9 @@ impl <T: Write> Parser<T>
     fn expression_statement(&mut self, token_source: &mut TokenSource) -> Result<Stmt, LoxError>
     {
         let expr = self.expression(token_source)?;
-        token_source.consume_or_error(TokenKind::Semicolon, "Expect ';' after expression.")?;
-        Ok(Stmt::Expr(expr))
+        let semicolon = token_source.consume_or_error(TokenKind::Semicolon, "Expect ';' after expression.")?;
+        let span = expr.span.to(semicolon.span);
+        Ok(Stmt::new(StmtKind::Expr(expr), span))
     }
 
     // ----------------------------------------------------
@@ -492,7 +560,7 @@ impl <T: Write> Parser<T>
     ///
     fn expression(&mut self, token_source: &mut TokenSource) -> Result<Expr,LoxError>
     {
-        self.assignment(token_source)
+        self.nested(token_source, Self::assignment)
     }
 
     /// Parses an assignment expression. Either an assignment to a variable or an attribute.
@@ -505,24 +573,22 @@ impl <T: Write> Parser<T>
         let expr = self.or(token_source)?;
 
         let peek_token = token_source.peek().unwrap();
-        //Copy position to evade borrow checker
-        let position = peek_token.position;
-
         match peek_token.kind {
             TokenKind::Equal => {
                 token_source.consume();
-                let value: Expr = self.assignment(token_source)?;
+                let value: Expr = self.nested(token_source, Self::assignment)?;
+                let span = expr.span.to(value.span);
 
-                match expr.kind {
+                match expr.into_kind() {
                     ExprKind::Variable(identifier) => {
-                        Ok(Expr::new(ExprKind::Assign(Box::new(AssignExpr { identifier, expr: value }))))
+                        Ok(Expr::new(ExprKind::Assign(Box::new(AssignExpr { identifier, expr: value })), span))
                     },
                     //Assign a value expression to an instance property
                     ExprKind::Get(get_expr) => {
-                        Ok(Expr::new(ExprKind::Set(Box::new(SetExpr { target: get_expr.expr, identifier: get_expr.identifier, value }))))
+                        Ok(Expr::new(ExprKind::Set(Box::new(SetExpr { target: get_expr.expr, identifier: get_expr.identifier, value })), span))
                     }
                     _ => {
-                        Err(LoxError::parser_error(ParserErrorKind::InvalidAssignmentTarget, position))
+                        Err(LoxError::parser_error(ParserErrorKind::InvalidAssignmentTarget, span))
                     }
                 }
             },
@@ -546,7 +612,8 @@ impl <T: Write> Parser<T>
                 TokenKind::Or => {
                     let operator = token_source.next().unwrap();
                     let right: Expr = self.and(token_source)?;
-                    expr =  Expr::new(ExprKind::Logical(Box::new(LogicalExpr { left: expr, operator: Operator::<LogicalOperatorKind>::from_token(&operator), right })));
+                    let span = expr.span.to(right.span);
+                    expr =  Expr::new(ExprKind::Logical(Box::new(LogicalExpr { left: expr, operator: Operator::<LogicalOperatorKind>::from_token(&operator), right })), span);
                 },
                 _ => {
                     return Ok(expr);
@@ -569,7 +636,8 @@ impl <T: Write> Parser<T>
                 TokenKind::And => {
                     let operator = token_source.next().unwrap();
                     let right: Expr = self.equality(token_source)?;
-                    expr = Expr::new(ExprKind::Logical(Box::new(LogicalExpr { left: expr, operator: Operator::<LogicalOperatorKind>::from_token(&operator), right })));
+                    let span = expr.span.to(right.span);
+                    expr = Expr::new(ExprKind::Logical(Box::new(LogicalExpr { left: expr, operator: Operator::<LogicalOperatorKind>::from_token(&operator), right })), span);
                 },
                 _ => {
                     return Ok(expr);
@@ -592,7 +660,8 @@ impl <T: Write> Parser<T>
                 TokenKind::BangEqual|TokenKind::EqualEqual => {
                     let operator: Token = token_source.next().unwrap();
                     let right: Expr = self.comparison(token_source)?;
-                    expr = Expr::new(ExprKind::Binary(Box::new(BinaryExpr { left: expr, operator: Operator::<BinaryOperatorKind>::from_token(&operator), right })));
+                    let span = expr.span.to(right.span);
+                    expr = Expr::new(ExprKind::Binary(Box::new(BinaryExpr { left: expr, operator: Operator::<BinaryOperatorKind>::from_token(&operator), right })), span);
                 },
                 _ => {
                     return Ok(expr);
@@ -615,7 +684,8 @@ impl <T: Write> Parser<T>
                 TokenKind::Greater | TokenKind::GreaterEqual | TokenKind::Less | TokenKind::LessEqual => {
                     let operator = token_source.next().unwrap();
                     let right: Expr = self.term(token_source)?;
-                    expr = Expr::new(ExprKind::Binary(Box::new(BinaryExpr { left: expr, operator: Operator::<BinaryOperatorKind>::from_token(&operator), right })));
+                    let span = expr.span.to(right.span);
+                    expr = Expr::new(ExprKind::Binary(Box::new(BinaryExpr { left: expr, operator: Operator::<BinaryOperatorKind>::from_token(&operator), right })), span);
                 },
                 _ => {
                     return Ok(expr);
@@ -638,7 +708,8 @@ impl <T: Write> Parser<T>
                 TokenKind::Minus | TokenKind::Plus => {
                     let operator = token_source.next().unwrap();
                     let right: Expr = self.factor(token_source)?;
-                    expr = Expr::new(ExprKind::Binary(Box::new(BinaryExpr { left: expr, operator: Operator::<BinaryOperatorKind>::from_token(&operator), right })));
+                    let span = expr.span.to(right.span);
+                    expr = Expr::new(ExprKind::Binary(Box::new(BinaryExpr { left: expr, operator: Operator::<BinaryOperatorKind>::from_token(&operator), right })), span);
                 },
                 _ => {
                     return Ok(expr);
@@ -661,7 +732,8 @@ impl <T: Write> Parser<T>
                 TokenKind::Slash | TokenKind::Star => {
                     let operator: Token = token_source.next().unwrap();
                     let right = self.unary(token_source)?;
-                    expr = Expr::new(ExprKind::Binary(Box::new(BinaryExpr { left: expr, operator: Operator::<BinaryOperatorKind>::from_token(&operator), right })));
+                    let span = expr.span.to(right.span);
+                    expr = Expr::new(ExprKind::Binary(Box::new(BinaryExpr { left: expr, operator: Operator::<BinaryOperatorKind>::from_token(&operator), right })), span);
                 },
                 _ => {
                     return Ok(expr);
@@ -681,8 +753,9 @@ impl <T: Write> Parser<T>
         match &peek_token.kind {
             TokenKind::Bang | TokenKind::Minus => {
                 let operator: Token = token_source.next().unwrap();
-                let right:    Expr = self.unary(token_source)?;
-                Ok(Expr::new(ExprKind::Unary(Box::new(UnaryExpr { operator: Operator::<UnaryOperatorKind>::from_token(&operator), expr: right }))))
+                let right:    Expr = self.nested(token_source, Self::unary)?;
+                let span = operator.span.to(right.span);
+                Ok(Expr::new(ExprKind::Unary(Box::new(UnaryExpr { operator: Operator::<UnaryOperatorKind>::from_token(&operator), expr: right })), span))
             },
             _ => {
                 self.call(token_source)
@@ -705,8 +778,9 @@ impl <T: Write> Parser<T>
             match token.kind {
                 TokenKind::LeftParen => {
                     let left_paren = token_source.next().unwrap();
-                    if token_source.consume_if(TokenKind::RightParen) {
-                        expr = Expr::new(ExprKind::Call(Box::new(CallExpr { callee: expr, arguments: Vec::new(), position: left_paren.position })));
+                    if token_source.check(TokenKind::RightParen) {
+                        let span = expr.span.to(token_source.next().unwrap().span);
+                        expr = Expr::new(ExprKind::Call(Box::new(CallExpr { callee: expr, arguments: Vec::new() })), span);
                         continue;
                     }
                     let mut args: Vec<Expr> = Vec::new();
@@ -716,17 +790,19 @@ impl <T: Write> Parser<T>
                             break;
                         }
                     }
-                    token_source.consume_or_error(TokenKind::RightParen, "Expect ')' after arguments.")?;
+                    let right_paren = token_source.consume_or_error(TokenKind::RightParen, "Expect ')' after arguments.")?;
                     if args.len() < 255 {
-                        expr = Expr::new(ExprKind::Call(Box::new(CallExpr { callee: expr, arguments: args, position: left_paren.position })));
+                        let span = expr.span.to(right_paren.span);
+                        expr = Expr::new(ExprKind::Call(Box::new(CallExpr { callee: expr, arguments: args })), span);
                     } else {
-                        return Err(LoxError::parser_error(ParserErrorKind::TooManyArguments, left_paren.position));
+                        return Err(LoxError::parser_error(ParserErrorKind::TooManyArguments, left_paren.span));
                     }
                 },
                 TokenKind::Dot => {
                     token_source.consume();
                     let identifier: Identifier = token_source.consume_identifier("Expect property name after '.'.")?;
-                    expr = Expr::new(ExprKind::Get(Box::new(GetExpr { expr, identifier })));
+                    let span = expr.span.to(identifier.span);
+                    expr = Expr::new(ExprKind::Get(Box::new(GetExpr { expr, identifier })), span);
                 },
                 _ => {
                     return Ok(expr);
@@ -735,55 +811,166 @@ impl <T: Write> Parser<T>
         }
     }
 
+    /// Parses an interpolated string, whose first piece has already been consumed: the lexer splits it at each `${` and resumes it after the matching `}`.
+    ///
+    /// Grammar:
+    /// interpolation -> ( STRING_PART expression "}" )+ STRING ;
+    ///
+    fn interpolation(&mut self, token_source: &mut TokenSource, first_part: Rc<String>, first_span: Span) -> Result<Expr, LoxError>
+    {
+        let mut parts: Vec<Expr> = Vec::new();
+        let mut part = first_part;
+        let mut part_span = first_span;
+        loop {
+            if !part.is_empty() {
+                parts.push(Expr::new(ExprKind::Literal(Literal::String(part, part_span)), part_span));
+            }
+            parts.push(self.expression(token_source)?);
+            token_source.consume_or_error(TokenKind::RightBrace, "Expect '}' after interpolated expression.")?;
+            let token = token_source.peek().unwrap().clone();
+            match token.kind {
+                TokenKind::StringPart(string) => {
+                    token_source.consume();
+                    part = string;
+                    part_span = token.span;
+                },
+                TokenKind::String(string) => {
+                    token_source.consume();
+                    if !string.is_empty() {
+                        parts.push(Expr::new(ExprKind::Literal(Literal::String(string, token.span)), token.span));
+                    }
+                    return Ok(Expr::new(ExprKind::Interpolation(parts), first_span.to(token.span)));
+                },
+                _ => {
+                    //the rest of the string is unterminated, an error already reported by the lexer
+                    return Err(LoxError::parser_error(ParserErrorKind::UnterminatedString, token.span));
+                }
+            }
+        }
+    }
+
     /// Parses a primary expression `(`.
     ///
     /// Grammar:
-    /// primary -> "true" | "false" | "nil" | "this" | NUMBER | STRING | IDENTIFIER | "(" expression ")" | "super" "." IDENTIFIER ;
+    /// primary -> "true" | "false" | "nil" | "this" | NUMBER | STRING | IDENTIFIER | "(" expression ")" | "super" "." IDENTIFIER | interpolation ;
     ///
     fn primary(&mut self, token_source: &mut TokenSource) -> Result<Expr, LoxError>
     {
         if token_source.is_at_end() {
-            return Err(LoxError::parser_error(ParserErrorKind::ExpectedExpression, token_source.peek().unwrap().position));
+            return Err(LoxError::parser_error(ParserErrorKind::ExpectedExpression, token_source.peek().unwrap().span));
         }
 
         let token = token_source.next().unwrap();
-        let position = token.position;
 
         match &token.kind {
             TokenKind::Nil => {
-                Ok(Expr::new(ExprKind::Literal(Literal::Nil(token.position))))
+                Ok(Expr::new(ExprKind::Literal(Literal::Nil(token.span)), token.span))
             }
             TokenKind::False  => {
-                Ok(Expr::new(ExprKind::Literal(Literal::False(token.position))))
+                Ok(Expr::new(ExprKind::Literal(Literal::False(token.span)), token.span))
             },
             TokenKind::True  => {
-                Ok(Expr::new(ExprKind::Literal(Literal::True(token.position))))
+                Ok(Expr::new(ExprKind::Literal(Literal::True(token.span)), token.span))
             },
             TokenKind::Number(number)  => {
-                Ok(Expr::new(ExprKind::Literal(Literal::Number(*number, token.position))))
+                Ok(Expr::new(ExprKind::Literal(Literal::Number(*number, token.span)), token.span))
             }
             TokenKind::String(string) => {
-                Ok(Expr::new(ExprKind::Literal(Literal::String(Rc::clone(string), token.position))))
+                Ok(Expr::new(ExprKind::Literal(Literal::String(Rc::clone(string), token.span)), token.span))
+            }
+            TokenKind::StringPart(string) => {
+                self.interpolation(token_source, Rc::clone(string), token.span)
             }
             TokenKind::Identifier(identifier) => {
-                Ok(Expr::new(ExprKind::Variable(Identifier {name: *identifier, position: token.position})))
+                Ok(Expr::new(ExprKind::Variable(Identifier {name: *identifier, span: token.span}), token.span))
             },
             TokenKind::LeftParen => {
                 let expr: Expr = self.expression(token_source)?;
-                token_source.consume_or_error(TokenKind::RightParen, "Expect ')' after expression.")?;
-                Ok(Expr::new(ExprKind::Grouping(Box::new(expr))))
+                let right_paren = token_source.consume_or_error(TokenKind::RightParen, "Expect ')' after expression.")?;
+                Ok(Expr::new(ExprKind::Grouping(Box::new(expr)), token.span.to(right_paren.span)))
             },
             TokenKind::This => {
-                Ok(Expr::new(ExprKind::This(token.position)))
+                Ok(Expr::new(ExprKind::This(token.span), token.span))
             },
             TokenKind::Super => {
                 token_source.consume_or_error(TokenKind::Dot, "Expect '.' after 'super'.")?;
                 let identifier: Identifier = token_source.consume_identifier("Expect superclass method name.")?;
-                Ok(Expr::new(ExprKind::Super(identifier)))
+                let span = token.span.to(identifier.span);
+                Ok(Expr::new(ExprKind::Super(identifier), span))
             },
             _ => {
-                Err(LoxError::parser_error(ParserErrorKind::ExpectedExpression, position))
+                Err(LoxError::parser_error(ParserErrorKind::ExpectedExpression, token.span))
             }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests
+{
+    use string_interner::StringInterner;
+
+    use crate::{error::{LoxError, LoxErrorKind, ParserErrorKind}, parser::{source_map::SourceMap, types::{Stmt, StmtKind}}};
+
+    use super::Parser;
+
+    fn parse_errors(code: &str) -> Vec<LoxError>
+    {
+        let mut interner = StringInterner::default();
+        let mut parser = Parser::new(interner.get_or_intern_static("init"));
+        parser.parse(code, 0, &mut interner).unwrap_err()
+    }
+
+    #[test]
+    fn test_stmt_spans() {
+        let mut interner = StringInterner::default();
+        let mut parser = Parser::new(interner.get_or_intern_static("init"));
+        let mut source_map = SourceMap::default();
+        source_map.add_file("first", "print 0;").unwrap();
+        let code = "var a = 1;\nwhile (a) { a = a - 1; break; }\nfor (var i = 0; i < 2; i = i + 1) print i;\nfun f() { return; }\nclass A {}\nif (a) a; else {}";
+        let start = source_map.add_file("second", code).unwrap().start();
+        let stmts = parser.parse(code, start, &mut interner).unwrap();
+        let text = |stmt: &Stmt| source_map.text(stmt.span).unwrap().to_owned();
+        let texts: Vec<String> = stmts.iter().map(text).collect();
+        assert_eq!(texts, vec![
+            "var a = 1;",
+            "while (a) { a = a - 1; break; }",
+            "for (var i = 0; i < 2; i = i + 1) print i;",
+            "fun f() { return; }",
+            "class A {}",
+            "if (a) a; else {}"
+        ]);
+        // nested statements have their own span, the ones desugaring a `for` loop span the whole loop
+        match &stmts[1].kind {
+            StmtKind::While(while_stmt) => match &while_stmt.body.kind {
+                StmtKind::Block(stmts) => assert_eq!(stmts.iter().map(text).collect::<Vec<String>>(), vec!["a = a - 1;", "break;"]),
+                kind => panic!("expected a block, got {:?}", kind),
+            },
+            kind => panic!("expected a while statement, got {:?}", kind),
+        }
+        match &stmts[2].kind {
+            StmtKind::Block(stmts) => assert_eq!(stmts.iter().map(text).collect::<Vec<String>>(), vec!["var i = 0;", "for (var i = 0; i < 2; i = i + 1) print i;"]),
+            kind => panic!("expected a block, got {:?}", kind),
+        }
+        assert_eq!(source_map.position(stmts[3].span.start.offset), Some(stmts[3].span.start));
+        assert_eq!(stmts[3].span.start.line, 4);
+    }
+
+    #[test]
+    fn test_interpolation_errors() {
+        // an empty interpolated expression is reported at its closing brace
+        let errors = parse_errors("print \"a ${} b\";");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, LoxErrorKind::Parser(ParserErrorKind::ExpectedExpression));
+        assert_eq!((errors[0].span.unwrap().start.column, errors[0].span.unwrap().end.column), (12, 13));
+
+        let errors = parse_errors("print \"a ${x b\";");
+        assert_eq!(errors[0].kind, LoxErrorKind::Parser(ParserErrorKind::ExpectedToken("Expect '}' after interpolated expression.".to_owned())));
+        assert_eq!(errors[0].span.unwrap().start.column, 14);
+
+        // the string is unterminated after the interpolated expression: reported once
+        let errors = parse_errors("print \"a ${x} b");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, LoxErrorKind::Parser(ParserErrorKind::UnterminatedString));
+    }
+}
@@ -1,10 +1,13 @@
 use core::fmt;
+use std::ops::Range;
 
+/// A point of the source code: its line and column (counted in chars, from 1) and its byte offset in the `SourceMap`.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Position
 {
     pub line: u32,
-    pub column: u32
+    pub column: u32,
+    pub offset: u32
 }
 
 impl fmt::Display for Position
@@ -13,4 +16,32 @@ impl fmt::Display for Position
     {
         write!(f, "line: {}, column: {}.", self.line, self.column)
     }
-}
\ No newline at end of file
+}
+
+/// A range of source code: `start` is the position of its first character, `end` the position right after its last one.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Span
+{
+    pub start: Position,
+    pub end: Position
+}
+
+impl Span
+{
+    pub const fn new(start: Position, end: Position) -> Self
+    {
+        Self { start, end }
+    }
+
+    /// Returns the span going from the start of `self` to the end of `other`.
+    pub const fn to(self, other: Span) -> Self
+    {
+        Self { start: self.start, end: other.end }
+    }
+
+    /// Returns the byte offsets covered by the span.
+    pub const fn offsets(&self) -> Range<usize>
+    {
+        self.start.offset as usize..self.end.offset as usize
+    }
+}
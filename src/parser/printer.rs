@@ -0,0 +1,381 @@
+use string_interner::StringInterner;
+
+use crate::{alias::{IdentifierSymbol, SideTable}, utils::native_stack::ensure_sufficient_stack};
+
+use super::{cst::{SyntaxElement, SyntaxNode, SyntaxTree}, position::Span, tokens::{Token, TokenKind}, types::{BinaryOperatorKind, Expr, ExprKind, Literal, LogicalOperatorKind, Stmt, StmtKind, UnaryOperatorKind}};
+
+/// Converts tokens, syntax trees and resolver results to a human readable text, to help debugging the front end of the interpreter.
+pub struct Printer<'a>
+{
+    string_interner: &'a StringInterner
+}
+
+impl <'a> Printer<'a>
+{
+    pub fn new(string_interner: &'a StringInterner) -> Self
+    {
+        Self { string_interner }
+    }
+
+    fn name(&self, symbol: IdentifierSymbol) -> &str
+    {
+        self.string_interner.resolve(symbol).unwrap()
+    }
+
+    /// Prints a token with its position, eg. `[line 1:5] Identifier(foo)`.
+    pub fn print_token(&self, token: &Token) -> String
+    {
+        format!("[line {}:{}] {}", token.span.start.line, token.span.start.column, self.token_kind(token))
+    }
+
+    fn token_kind(&self, token: &Token) -> String
+    {
+        match &token.kind {
+            TokenKind::String(value)     => format!("String({:?})", value),
+            TokenKind::StringPart(value) => format!("StringPart({:?})", value),
+            TokenKind::Number(value)     => format!("Number({})", value),
+            TokenKind::Identifier(name)  => format!("Identifier({})", self.name(*name)),
+            TokenKind::Error(error)      => format!("Error({:?})", error.text),
+            kind                         => kind.to_string(),
+        }
+    }
+
+    /// Prints a concrete syntax tree, one line per node, token and trivia, eg. `Literal@6..7` followed by `  Number(1) "1"`.
+    ///
+    /// Nodes show the byte offsets of their code, tokens and trivia show their code. Children are indented by two spaces.
+    pub fn print_syntax_tree(&self, tree: &SyntaxTree) -> Vec<String>
+    {
+        let mut lines = Vec::new();
+        self.syntax_node(tree, &tree.root, 0, &mut lines);
+        lines
+    }
+
+    fn syntax_node(&self, tree: &SyntaxTree, node: &SyntaxNode, depth: usize, lines: &mut Vec<String>)
+    {
+        ensure_sufficient_stack(|| self.syntax_node_lines(tree, node, depth, lines))
+    }
+
+    fn syntax_node_lines(&self, tree: &SyntaxTree, node: &SyntaxNode, depth: usize, lines: &mut Vec<String>)
+    {
+        lines.push(format!("{}{:?}@{}..{}", indent(depth), node.kind, node.span.start.offset, node.span.end.offset));
+        for child in &node.children {
+            match child {
+                SyntaxElement::Node(child) => {
+                    self.syntax_node(tree, child, depth + 1, lines);
+                },
+                SyntaxElement::Token(token) => {
+                    for trivia in &token.leading_trivia {
+                        lines.push(format!("{}{:?} {:?}", indent(depth + 1), trivia.kind, tree.text(trivia.span)));
+                    }
+                    lines.push(format!("{}{} {:?}", indent(depth + 1), self.token_kind(&token.token), tree.text(token.token.span)));
+                }
+            }
+        }
+    }
+
+    /// Prints a statement as an S-expression, in the same format of the `AstPrinter` described in the book.
+    ///
+    /// The statements nested in blocks, functions, classes and loops are printed on their own lines, indented by two spaces for each level.
+    pub fn print_stmt(&self, stmt: &Stmt) -> String
+    {
+        self.stmt(stmt, 0)
+    }
+
+    fn stmt(&self, stmt: &Stmt, depth: usize) -> String
+    {
+        ensure_sufficient_stack(|| self.stmt_kind(stmt, depth))
+    }
+
+    fn stmt_kind(&self, stmt: &Stmt, depth: usize) -> String
+    {
+        match &stmt.kind
+        {
+            StmtKind::Print(expr) => {
+                format!("(print {})", self.print_expr(expr))
+            },
+            StmtKind::Expr(expr) => {
+                format!("(; {})", self.print_expr(expr))
+            },
+            StmtKind::Var(identifier, opt_expr) => {
+                match opt_expr {
+                    Some(expr) => format!("(var {} = {})", self.name(identifier.name), self.print_expr(expr)),
+                    None       => format!("(var {})", self.name(identifier.name)),
+                }
+            },
+            StmtKind::Block(stmts) => {
+                format!("(block{})", self.nested(stmts, depth))
+            },
+            StmtKind::If(if_stmt) => {
+                format!("(if {}{})", self.print_expr(&if_stmt.condition), self.nested([&if_stmt.then_stmt], depth))
+            },
+            StmtKind::IfElse(if_else_stmt) => {
+                format!("(if-else {}{})", self.print_expr(&if_else_stmt.condition), self.nested([&if_else_stmt.then_stmt, &if_else_stmt.else_stmt], depth))
+            },
+            StmtKind::While(while_stmt) => {
+                format!("(while {}{})", self.print_expr(&while_stmt.condition), self.nested([&while_stmt.body], depth))
+            },
+            StmtKind::Break => {
+                "(break)".to_string()
+            },
+            StmtKind::Continue => {
+                "(continue)".to_string()
+            },
+            StmtKind::Return(opt_expr, _) => {
+                match opt_expr {
+                    Some(expr) => format!("(return {})", self.print_expr(expr)),
+                    None       => "(return)".to_string(),
+                }
+            },
+            StmtKind::FunctionDeclaration(func_decl) => {
+                let parameters: Vec<&str> = func_decl.parameters.iter().map(|param| self.name(*param)).collect();
+                format!("(fun {}({}){})", self.name(func_decl.identifier.name), parameters.join(" "), self.nested(&func_decl.body, depth))
+            },
+            StmtKind::ClassDeclaration(class_decl) => {
+                let mut text = format!("(class {}", self.name(class_decl.identifier.name));
+                if let Some(superclass) = &class_decl.superclass_expr {
+                    text.push_str(&format!(" < {}", self.print_expr(superclass)));
+                }
+                // methods are stored in a hash map, sort them by position to keep the source order
+                let mut methods: Vec<_> = class_decl.methods.values().collect();
+                methods.sort_by_key(|method| (method.identifier.span.start.line, method.identifier.span.start.column));
+                for method in methods {
+                    let stmt = Stmt::new(StmtKind::FunctionDeclaration(std::rc::Rc::clone(method)), method.identifier.span);
+                    text.push_str(&format!("\n{}{}", indent(depth + 1), self.stmt(&stmt, depth + 1)));
+                }
+                text.push(')');
+                text
+            },
+        }
+    }
+
+    fn nested<'s>(&self, stmts: impl IntoIterator<Item = &'s Stmt>, depth: usize) -> String
+    {
+        stmts.into_iter()
+            .map(|stmt| format!("\n{}{}", indent(depth + 1), self.stmt(stmt, depth + 1)))
+            .collect()
+    }
+
+    /// Prints an expression as an S-expression, eg. `(* (- 123) (group 45.67))`.
+    pub fn print_expr(&self, expr: &Expr) -> String
+    {
+        ensure_sufficient_stack(|| self.expr_kind(expr))
+    }
+
+    fn expr_kind(&self, expr: &Expr) -> String
+    {
+        match &expr.kind
+        {
+            ExprKind::Binary(binary_expr) => {
+                let operator = match binary_expr.operator.kind {
+                    BinaryOperatorKind::Minus        => "-",
+                    BinaryOperatorKind::Plus         => "+",
+                    BinaryOperatorKind::Slash        => "/",
+                    BinaryOperatorKind::Star         => "*",
+                    BinaryOperatorKind::BangEqual    => "!=",
+                    BinaryOperatorKind::EqualEqual   => "==",
+                    BinaryOperatorKind::Greater      => ">",
+                    BinaryOperatorKind::GreaterEqual => ">=",
+                    BinaryOperatorKind::Less         => "<",
+                    BinaryOperatorKind::LessEqual    => "<=",
+                };
+                format!("({} {} {})", operator, self.print_expr(&binary_expr.left), self.print_expr(&binary_expr.right))
+            },
+            ExprKind::Logical(logical_expr) => {
+                let operator = match logical_expr.operator.kind {
+                    LogicalOperatorKind::And => "and",
+                    LogicalOperatorKind::Or  => "or",
+                };
+                format!("({} {} {})", operator, self.print_expr(&logical_expr.left), self.print_expr(&logical_expr.right))
+            },
+            ExprKind::Unary(unary_expr) => {
+                let operator = match unary_expr.operator.kind {
+                    UnaryOperatorKind::Bang  => "!",
+                    UnaryOperatorKind::Minus => "-",
+                };
+                format!("({} {})", operator, self.print_expr(&unary_expr.expr))
+            },
+            ExprKind::Grouping(expr) => {
+                format!("(group {})", self.print_expr(expr))
+            },
+            ExprKind::Literal(literal) => {
+                match literal {
+                    Literal::String(value, _) => format!("{:?}", value),
+                    Literal::Number(value, _) => format!("{}", value),
+                    Literal::True(_)          => "true".to_string(),
+                    Literal::False(_)         => "false".to_string(),
+                    Literal::Nil(_)           => "nil".to_string(),
+                }
+            },
+            ExprKind::Variable(identifier) => {
+                self.name(identifier.name).to_string()
+            },
+            ExprKind::Assign(assign_expr) => {
+                format!("(= {} {})", self.name(assign_expr.identifier.name), self.print_expr(&assign_expr.expr))
+            },
+            ExprKind::Call(call_expr) => {
+                let mut text = format!("(call {}", self.print_expr(&call_expr.callee));
+                for argument in &call_expr.arguments {
+                    text.push(' ');
+                    text.push_str(&self.print_expr(argument));
+                }
+                text.push(')');
+                text
+            },
+            ExprKind::Get(get_expr) => {
+                format!("(. {} {})", self.print_expr(&get_expr.expr), self.name(get_expr.identifier.name))
+            },
+            ExprKind::Set(set_expr) => {
+                format!("(= {} {} {})", self.print_expr(&set_expr.target), self.name(set_expr.identifier.name), self.print_expr(&set_expr.value))
+            },
+            ExprKind::This(_) => {
+                "this".to_string()
+            },
+            ExprKind::Super(identifier) => {
+                format!("(super {})", self.name(identifier.name))
+            },
+            ExprKind::Interpolation(parts) => {
+                let parts: Vec<String> = parts.iter().map(|part| self.print_expr(part)).collect();
+                format!("(interpolate {})", parts.join(" "))
+            },
+        }
+    }
+
+    /// Lists every variable expression (variables, assignments, `this` and `super`) in source order,
+    /// along with the scope distance recorded by the resolver or `global` if it was not resolved locally.
+    pub fn print_resolved(&self, stmts: &[Stmt], side_table: &SideTable) -> Vec<String>
+    {
+        let mut lines = Vec::new();
+        for stmt in stmts {
+            self.resolved_stmt(stmt, side_table, &mut lines);
+        }
+        lines
+    }
+
+    fn resolved_stmt(&self, stmt: &Stmt, side_table: &SideTable, lines: &mut Vec<String>)
+    {
+        ensure_sufficient_stack(|| self.resolved_stmt_kind(stmt, side_table, lines))
+    }
+
+    fn resolved_stmt_kind(&self, stmt: &Stmt, side_table: &SideTable, lines: &mut Vec<String>)
+    {
+        match &stmt.kind
+        {
+            StmtKind::Print(expr) | StmtKind::Expr(expr) => {
+                self.resolved_expr(expr, side_table, lines);
+            },
+            StmtKind::Var(_, opt_expr) | StmtKind::Return(opt_expr, _) => {
+                if let Some(expr) = opt_expr {
+                    self.resolved_expr(expr, side_table, lines);
+                }
+            },
+            StmtKind::Block(stmts) => {
+                for stmt in stmts {
+                    self.resolved_stmt(stmt, side_table, lines);
+                }
+            },
+            StmtKind::If(if_stmt) => {
+                self.resolved_expr(&if_stmt.condition, side_table, lines);
+                self.resolved_stmt(&if_stmt.then_stmt, side_table, lines);
+            },
+            StmtKind::IfElse(if_else_stmt) => {
+                self.resolved_expr(&if_else_stmt.condition, side_table, lines);
+                self.resolved_stmt(&if_else_stmt.then_stmt, side_table, lines);
+                self.resolved_stmt(&if_else_stmt.else_stmt, side_table, lines);
+            },
+            StmtKind::While(while_stmt) => {
+                self.resolved_expr(&while_stmt.condition, side_table, lines);
+                self.resolved_stmt(&while_stmt.body, side_table, lines);
+            },
+            StmtKind::Break | StmtKind::Continue => {},
+            StmtKind::FunctionDeclaration(func_decl) => {
+                for stmt in &func_decl.body {
+                    self.resolved_stmt(stmt, side_table, lines);
+                }
+            },
+            StmtKind::ClassDeclaration(class_decl) => {
+                if let Some(superclass) = &class_decl.superclass_expr {
+                    self.resolved_expr(superclass, side_table, lines);
+                }
+                let mut methods: Vec<_> = class_decl.methods.values().collect();
+                methods.sort_by_key(|method| (method.identifier.span.start.line, method.identifier.span.start.column));
+                for method in methods {
+                    for stmt in &method.body {
+                        self.resolved_stmt(stmt, side_table, lines);
+                    }
+                }
+            },
+        }
+    }
+
+    fn resolved_expr(&self, expr: &Expr, side_table: &SideTable, lines: &mut Vec<String>)
+    {
+        ensure_sufficient_stack(|| self.resolved_expr_kind(expr, side_table, lines))
+    }
+
+    fn resolved_expr_kind(&self, expr: &Expr, side_table: &SideTable, lines: &mut Vec<String>)
+    {
+        match &expr.kind
+        {
+            ExprKind::Variable(identifier) => {
+                lines.push(self.resolution(expr, self.name(identifier.name), &identifier.span, side_table));
+            },
+            ExprKind::This(span) => {
+                lines.push(self.resolution(expr, "this", span, side_table));
+            },
+            ExprKind::Super(identifier) => {
+                lines.push(self.resolution(expr, "super", &identifier.span, side_table));
+            },
+            ExprKind::Assign(assign_expr) => {
+                let name = format!("{} (assignment)", self.name(assign_expr.identifier.name));
+                lines.push(self.resolution(expr, &name, &assign_expr.identifier.span, side_table));
+                self.resolved_expr(&assign_expr.expr, side_table, lines);
+            },
+            ExprKind::Binary(binary_expr) => {
+                self.resolved_expr(&binary_expr.left, side_table, lines);
+                self.resolved_expr(&binary_expr.right, side_table, lines);
+            },
+            ExprKind::Logical(logical_expr) => {
+                self.resolved_expr(&logical_expr.left, side_table, lines);
+                self.resolved_expr(&logical_expr.right, side_table, lines);
+            },
+            ExprKind::Unary(unary_expr) => {
+                self.resolved_expr(&unary_expr.expr, side_table, lines);
+            },
+            ExprKind::Grouping(expr) => {
+                self.resolved_expr(expr, side_table, lines);
+            },
+            ExprKind::Literal(_) => {},
+            ExprKind::Interpolation(parts) => {
+                for part in parts {
+                    self.resolved_expr(part, side_table, lines);
+                }
+            },
+            ExprKind::Call(call_expr) => {
+                self.resolved_expr(&call_expr.callee, side_table, lines);
+                for argument in &call_expr.arguments {
+                    self.resolved_expr(argument, side_table, lines);
+                }
+            },
+            ExprKind::Get(get_expr) => {
+                self.resolved_expr(&get_expr.expr, side_table, lines);
+            },
+            ExprKind::Set(set_expr) => {
+                self.resolved_expr(&set_expr.target, side_table, lines);
+                self.resolved_expr(&set_expr.value, side_table, lines);
+            },
+        }
+    }
+
+    fn resolution(&self, expr: &Expr, name: &str, span: &Span, side_table: &SideTable) -> String
+    {
+        match side_table.get(&expr.id) {
+            Some(distance) => format!("[line {}:{}] {} -> distance {}", span.start.line, span.start.column, name, distance),
+            None           => format!("[line {}:{}] {} -> global", span.start.line, span.start.column, name),
+        }
+    }
+}
+
+fn indent(depth: usize) -> String
+{
+    "  ".repeat(depth)
+}
@@ -1,28 +1,27 @@
-use std::{cell::RefCell, io::Write, rc::Rc};
+use std::rc::Rc;
 
 use rustc_hash::FxHashMap;
 use string_interner::StringInterner;
 
-use crate::{alias::{IdentifierSymbol, SideTable}, error::{ExecutionResult, LoxError, ResolverErrorKind}, utils::stack::Stack};
+use crate::{alias::{IdentifierSymbol, SideTable}, error::{LoxError, ResolverErrorKind}, utils::{native_stack::ensure_sufficient_stack, stack::Stack}};
 
-use super::{types::{Expr, ExprKind, FunctionDeclaration, Stmt}, position::Position};
+use super::{types::{Expr, ExprKind, FunctionDeclaration, Stmt, StmtKind}, position::Span};
 
-pub struct Resolver<'a, T:Write>
+pub struct Resolver<'a>
 {
     stack:            Stack<FxHashMap<IdentifierSymbol, bool>>,
     string_interner:  &'a StringInterner,
-    has_error:        bool,
+    errors:           Vec<LoxError>,
     current_function: FunctionType,
     current_class:    ClassType,
     this_symbol:      IdentifierSymbol,
     init_symbol:      IdentifierSymbol,
     super_symbol:     IdentifierSymbol,
-    error_logger:     Rc<RefCell<T>>,
 }
 
-impl <'a, T:Write> Resolver<'a,T>
+impl <'a> Resolver<'a>
 {
-    pub fn new(error_logger: Rc<RefCell<T>>, string_interner: &'a mut StringInterner) -> Self
+    pub fn new(string_interner: &'a mut StringInterner) -> Self
     {
         let this_symbol = string_interner.get("this").unwrap();
         let init_symbol = string_interner.get("init").unwrap();
@@ -31,33 +30,34 @@ impl <'a, T:Write> Resolver<'a,T>
         {
             stack: Stack::new(),
             string_interner,
-            has_error: false,
+            errors: Vec::new(),
             current_function: FunctionType::None,
             current_class: ClassType::None,
             this_symbol,
             init_symbol,
             super_symbol,
-            error_logger,
         }
     }
 
-    fn error(&mut self, err_kind: ResolverErrorKind, position: &Position)
+    fn error(&mut self, err_kind: ResolverErrorKind, span: &Span)
     {
-        let _ = writeln!(self.error_logger.borrow_mut(), "{}", LoxError::resolver_error(err_kind, *position));
-        self.has_error = true;
+        self.errors.push(LoxError::resolver_error(err_kind, *span));
     }
 
-    pub fn resolve(&mut self, stmts: &[Stmt]) -> Result<SideTable, ExecutionResult>
+    /// Resolves all the variables of the supplied statements.
+    ///
+    /// Returns the side table or all the errors found.
+    pub fn resolve(&mut self, stmts: &[Stmt]) -> Result<SideTable, Vec<LoxError>>
     {
         let mut side_table: SideTable = FxHashMap::default();
         for stmt in stmts
         {
             self.resolve_stmt(stmt, self.current_function, self.current_class, &mut side_table);
         }
-        if self.has_error {
-            Err(ExecutionResult::ResolverError)
-        } else {
+        if self.errors.is_empty() {
             Ok(side_table)
+        } else {
+            Err(std::mem::take(&mut self.errors))
         }
     }
 
@@ -69,6 +69,11 @@ impl <'a, T:Write> Resolver<'a,T>
     }
 
     fn resolve_stmt(&mut self, stmt: &Stmt, function_type: FunctionType, class_type: ClassType, side_table: &mut SideTable)
+    {
+        ensure_sufficient_stack(|| self.resolve_stmt_kind(stmt, function_type, class_type, side_table))
+    }
+
+    fn resolve_stmt_kind(&mut self, stmt: &Stmt, function_type: FunctionType, class_type: ClassType, side_table: &mut SideTable)
     {
         let enclosing_function = self.current_function;
         let enclosing_class = self.current_class;
@@ -76,27 +81,27 @@ impl <'a, T:Write> Resolver<'a,T>
         self.current_function = function_type;
         self.current_class = class_type;
         // set current function
-        match stmt
+        match &stmt.kind
         {
-            Stmt::Print(print_expr) =>
+            StmtKind::Print(print_expr) =>
             {
                 self.resolve_expr(print_expr, side_table);
             },
-            Stmt::Expr(expr) =>
+            StmtKind::Expr(expr) =>
             {
                 self.resolve_expr(expr, side_table);
             },
-            Stmt::Var(identifier, opt_expr) =>
+            StmtKind::Var(identifier, opt_expr) =>
             {
                 if let Err(err_kind) = self.declare(identifier.name) {
-                    self.error(err_kind, &identifier.position);
+                    self.error(err_kind, &identifier.span);
                 }
                 if let Some(expr) = opt_expr {
                     self.resolve_expr(expr, side_table);
                 }
                 self.define(identifier.name)
             },
-            Stmt::Block(stmt_list) =>
+            StmtKind::Block(stmt_list) =>
             {
                 self.begin_scope();
                 for stmt in stmt_list {
@@ -104,41 +109,41 @@ impl <'a, T:Write> Resolver<'a,T>
                 }
                 self.end_scope();
             },
-            Stmt::If(if_stmt) =>
+            StmtKind::If(if_stmt) =>
             {
                 self.resolve_expr(&if_stmt.condition, side_table);
                 self.resolve_stmt(&if_stmt.then_stmt, self.current_function, self.current_class, side_table);
             },
-            Stmt::IfElse(if_else_stmt) =>
+            StmtKind::IfElse(if_else_stmt) =>
             {
                 self.resolve_expr(&if_else_stmt.condition, side_table);
                 self.resolve_stmt(&if_else_stmt.then_stmt, self.current_function, self.current_class, side_table);
                 self.resolve_stmt(&if_else_stmt.else_stmt, self.current_function, self.current_class, side_table);
             },
-            Stmt::While(while_stmt) =>
+            StmtKind::While(while_stmt) =>
             {
                 self.resolve_expr(&while_stmt.condition, side_table);
                 self.resolve_stmt(&while_stmt.body, self.current_function, self.current_class, side_table);
             },
-            Stmt::Break     => { /*do nothing*/ },
-            Stmt::Continue  => { /*do nothing*/ },
-            Stmt::FunctionDeclaration(func_decl) =>
+            StmtKind::Break     => { /*do nothing*/ },
+            StmtKind::Continue  => { /*do nothing*/ },
+            StmtKind::FunctionDeclaration(func_decl) =>
             {
                 if let Err(err_kind) = self.declare(func_decl.identifier.name) {
-                    self.error(err_kind, &func_decl.identifier.position);
+                    self.error(err_kind, &func_decl.identifier.span);
                 }
                 self.define(func_decl.identifier.name);
                 self.resolve_function(func_decl, FunctionType::Function, self.current_class, side_table);
             },
-            Stmt::Return(opt_expr, position) =>
+            StmtKind::Return(opt_expr, span) =>
             {
                 match self.current_function {
                     FunctionType::None => {
-                        self.error(ResolverErrorKind::ReturnFromTopLevelCode, position)
+                        self.error(ResolverErrorKind::ReturnFromTopLevelCode, span)
                     },
                     FunctionType::Initializer => {
                         if opt_expr.is_some() {
-                            self.error(ResolverErrorKind::ReturnFromInitializer, position)
+                            self.errors.push(LoxError::resolver_error(ResolverErrorKind::ReturnFromInitializer, *span).with_help("an initializer always returns `this`, use `return;` to leave it early"));
                         }
                     },
                     _ => {
@@ -148,12 +153,12 @@ impl <'a, T:Write> Resolver<'a,T>
                     }
                 }
             },
-            Stmt::ClassDeclaration(class_declaration) =>
+            StmtKind::ClassDeclaration(class_declaration) =>
             {
                 self.current_class = ClassType::Class;
                 //resolve class name
                 if let Err(err_kind) = self.declare(class_declaration.identifier.name) {
-                    self.error(err_kind, &class_declaration.identifier.position);
+                    self.error(err_kind, &class_declaration.identifier.span);
                 }
                 self.define(class_declaration.identifier.name);
 
@@ -163,21 +168,18 @@ impl <'a, T:Write> Resolver<'a,T>
                     //A class cannot inherit from itself!
                     if let ExprKind::Variable(superclass_identifier) = &superclass_expr.kind {
                         if superclass_identifier.name == class_declaration.identifier.name {
-                            self.error(ResolverErrorKind::ClassCantInheritFromItslef, &superclass_identifier.position);
+                            self.error(ResolverErrorKind::ClassCantInheritFromItslef, &superclass_identifier.span);
                         }
                     }
 
                     self.resolve_expr(superclass_expr, side_table);
                 }
 
-                match &class_declaration.superclass_expr {
-                    Some(superclass) => {
-                        self.current_class = ClassType::SubClass;
-                        self.resolve_expr(superclass, side_table);
-                        self.begin_scope();
-                        self.define(self.super_symbol);
-                    },
-                    None => {},
+                if let Some(superclass) = &class_declaration.superclass_expr {
+                    self.current_class = ClassType::SubClass;
+                    self.resolve_expr(superclass, side_table);
+                    self.begin_scope();
+                    self.define(self.super_symbol);
                 }
 
                 //Start THIS scope wrapping around methods declarations
@@ -215,7 +217,7 @@ impl <'a, T:Write> Resolver<'a,T>
         for (index, param) in func_decl.parameters.iter().enumerate()
         {
             if let Err(err_kind) = self.declare(*param) {
-                self.error(err_kind, &func_decl.positions[index]);
+                self.error(err_kind, &func_decl.spans[index]);
             }
             self.define(*param);
         }
@@ -226,6 +228,11 @@ impl <'a, T:Write> Resolver<'a,T>
     }
 
     fn resolve_expr(&mut self, expr: &Expr, side_table: &mut SideTable)
+    {
+        ensure_sufficient_stack(|| self.resolve_expr_kind(expr, side_table))
+    }
+
+    fn resolve_expr_kind(&mut self, expr: &Expr, side_table: &mut SideTable)
     {
         match &expr.kind
         {
@@ -246,6 +253,13 @@ impl <'a, T:Write> Resolver<'a,T>
             {
                 /*do nothing*/
             },
+            ExprKind::Interpolation(parts) =>
+            {
+                for part in parts
+                {
+                    self.resolve_expr(part, side_table);
+                }
+            },
             ExprKind::Variable(identifier) =>
             {
                 if !self.stack.is_empty()
@@ -253,7 +267,7 @@ impl <'a, T:Write> Resolver<'a,T>
                     let opt_bool =self.stack.peek().unwrap().get(&identifier.name);
                     if opt_bool.is_none() || !(*opt_bool.unwrap())
                     {
-                        LoxError::resolver_error(crate::error::ResolverErrorKind::LocalVariableNotFound(self.string_interner.resolve(identifier.name).unwrap().to_owned()), identifier.position);
+                        LoxError::resolver_error(crate::error::ResolverErrorKind::LocalVariableNotFound(self.string_interner.resolve(identifier.name).unwrap().to_owned()), identifier.span);
                     }
                 }
                 self.resolve_local(expr, identifier.name, side_table);
@@ -285,11 +299,11 @@ impl <'a, T:Write> Resolver<'a,T>
                 self.resolve_expr(&set_expr.target, side_table);
                 self.resolve_expr(&set_expr.value,  side_table);
             },
-            ExprKind::This(position) => {
+            ExprKind::This(span) => {
                 match self.current_class
                 {
                     ClassType::None => {
-                        self.error(ResolverErrorKind::InvalidThisUsage, position)
+                        self.error(ResolverErrorKind::InvalidThisUsage, span)
                     },
                     _ => {
                         self.resolve_local(expr, self.this_symbol, side_table);
@@ -300,13 +314,13 @@ impl <'a, T:Write> Resolver<'a,T>
                 match self.current_class
                 {
                     ClassType::None => {
-                        self.error(crate::error::ResolverErrorKind::CantUseSuperOutsideClass, &identifier.position);
+                        self.error(crate::error::ResolverErrorKind::CantUseSuperOutsideClass, &identifier.span);
                     },
                     ClassType::SubClass => {
 
                     },
                     ClassType::Class => {
-                        self.error(crate::error::ResolverErrorKind::CantUseSuperWithoutSuperClass, &identifier.position);
+                        self.error(crate::error::ResolverErrorKind::CantUseSuperWithoutSuperClass, &identifier.span);
                     }
                 }
                 self.resolve_local(expr, self.super_symbol, side_table);
@@ -1,9 +1,13 @@
 use std::str::Chars;
 
+use unicode_xid::UnicodeXID;
+
 use crate::utils::peekable_iterator::NthPeekable;
 pub struct Scanner<'a>
 {
     iter:   NthPeekable<Chars<'a>, char>,
+    /// Number of bytes consumed so far.
+    offset: usize,
 }
 
 impl <'a> Scanner<'a>
@@ -13,12 +17,23 @@ impl <'a> Scanner<'a>
         Scanner
         {
             iter: NthPeekable::new(str.chars(), peek_dept),
+            offset: 0,
         }
     }
 
     pub fn next(&mut self) -> Option<char>
     {
-        self.iter.next()
+        let next = self.iter.next();
+        if let Some(ch) = next {
+            self.offset += ch.len_utf8();
+        }
+        next
+    }
+
+    /// Returns the byte offset of the next char.
+    pub fn offset(&self) -> usize
+    {
+        self.offset
     }
 
     pub fn peek(&mut self) -> Option<char>
@@ -28,7 +43,7 @@ impl <'a> Scanner<'a>
 
     pub fn peek_next(&mut self) -> Option<char>
     {
-        self.iter.peek_nth(1).cloned()
+        self.peek_nth(1)
     }
 
     pub fn peek_nth(&mut self, index: usize) -> Option<char>
@@ -38,17 +53,12 @@ impl <'a> Scanner<'a>
 
     pub fn is_peek(&mut self, ch: char) -> bool
     {
-        self.peek().map_or(false, |next_ch| next_ch==ch)
+        self.peek() == Some(ch)
     }
 
     pub fn is_peek_next(&mut self, ch: char) -> bool
     {
-        self.peek_next().map_or(false, |next_ch| next_ch==ch)
-    }
-
-    pub fn is_peek_ascii_digit(&mut self) -> bool
-    {
-        matches!(self.peek(), Some(chr) if chr.is_ascii_digit())
+        self.peek_next() == Some(ch)
     }
 
     pub fn is_peek_next_ascii_digit(&mut self) -> bool
@@ -58,13 +68,13 @@ impl <'a> Scanner<'a>
 
     pub fn is_peek_identifier_char(&mut self) -> bool
     {
-        matches!(self.peek(), Some(ch) if ch.is_ascii_alphabetic() || ch == '_' || ch.is_ascii_digit())
+        matches!(self.peek(), Some(ch) if UnicodeXID::is_xid_continue(ch))
     }
 
     pub fn consume_if_peek_is(&mut self, ch: char)
     {
-        if self.peek().map_or(false, |next_ch| next_ch==ch) {
-            self.next();
+        if self.peek() == Some(ch) {
+            self.consume();
         }
     }
 
@@ -80,6 +90,7 @@ impl <'a> Scanner<'a>
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests
 {
     use super::Scanner;
@@ -98,7 +109,6 @@ mod tests
         assert_eq!(scanner.next(), Some('t'));
         assert_eq!(scanner.is_peek('e'), true);
         assert_eq!(scanner.is_peek_next('s'), true);
-        assert_eq!(scanner.is_peek_ascii_digit(), false);
         assert_eq!(scanner.is_peek_next_ascii_digit(), false);
         assert_eq!(scanner.is_peek_identifier_char(), true);
 
@@ -106,9 +116,14 @@ mod tests
         scanner.consume();
         scanner.consume();
         assert_eq!(scanner.peek(), Some('1'));
-        assert_eq!(scanner.is_peek_ascii_digit(), true);
         assert_eq!(scanner.is_peek_next_ascii_digit(), true);
         assert_eq!(scanner.is_peek_identifier_char(), true);
+
+        let mut scanner = Scanner::from_str("à€", 2);
+        assert!(scanner.is_peek_identifier_char());
+        scanner.consume();
+        assert!(!scanner.is_peek_identifier_char());
+        assert_eq!(scanner.offset(), 2);
     }
 
     #[test]
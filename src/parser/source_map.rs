@@ -0,0 +1,217 @@
+use super::position::{Position, Span};
+
+/// A file, or a chunk of code, registered in a `SourceMap`.
+#[derive(Clone, Debug)]
+pub struct SourceFile
+{
+    name: String,
+    source: String,
+    /// Offset of the first byte of the file in the source map.
+    start: u32,
+    /// Offset of the first byte of each line, relative to the start of the file.
+    line_starts: Vec<usize>
+}
+
+impl SourceFile
+{
+    fn new(name: String, source: String, start: u32) -> Self
+    {
+        let line_starts = line_starts(&source);
+        Self { name, source, start, line_starts }
+    }
+
+    pub fn name(&self) -> &str
+    {
+        &self.name
+    }
+
+    pub fn source(&self) -> &str
+    {
+        &self.source
+    }
+
+    /// Returns the offset of the first byte of the file.
+    pub fn start(&self) -> u32
+    {
+        self.start
+    }
+
+    /// Returns the offset right after the last byte of the file, where its end of file token is.
+    pub fn end(&self) -> u32
+    {
+        self.start + self.source.len() as u32
+    }
+
+    /// Returns the line and column of `offset`, which must be between the start and the end of the file.
+    pub fn position(&self, offset: u32) -> Position
+    {
+        let relative = (offset - self.start) as usize;
+        let line_index = self.line_starts.partition_point(|line_start| *line_start <= relative) - 1;
+        let line_start = self.line_starts[line_index];
+        let column = self.source[line_start..relative].chars().count() + 1;
+        Position { line: line_index as u32 + 1, column: column as u32, offset }
+    }
+
+    /// Returns the offset of the char at the given line and column, `None` if the file has no such char.
+    ///
+    /// The column right after the last char of a line is accepted, giving the offset of the line break.
+    pub fn offset(&self, line: u32, column: u32) -> Option<u32>
+    {
+        let line_start = *self.line_starts.get((line as usize).checked_sub(1)?)?;
+        let line_end = self.line_starts.get(line as usize).copied().unwrap_or(self.source.len());
+        let text = self.source[line_start..line_end].trim_end_matches(['\r', '\n']);
+        let index = match text.char_indices().nth((column as usize).checked_sub(1)?) {
+            Some((index, _)) => index,
+            None if column as usize == text.chars().count() + 1 => text.len(),
+            None => return None,
+        };
+        Some(self.start + (line_start + index) as u32)
+    }
+}
+
+/// Returns the offset of the first byte of each line of `source`.
+pub(crate) fn line_starts(source: &str) -> Vec<usize>
+{
+    let bytes = source.as_bytes();
+    let mut line_starts = vec![0];
+    for (index, byte) in bytes.iter().enumerate() {
+        //'\r\n', '\n' and '\r' end a line, like in the lexer
+        let is_line_end = match byte {
+            b'\n' => true,
+            b'\r' => bytes.get(index + 1) != Some(&b'\n'),
+            _ => false,
+        };
+        if is_line_end {
+            line_starts.push(index + 1);
+        }
+    }
+    line_starts
+}
+
+/// Returns the text of the given line of `source` without its line break, `None` if there is no such line.
+pub(crate) fn line_text(source: &str, line: u32) -> Option<&str>
+{
+    let line_starts = line_starts(source);
+    let line_start = *line_starts.get((line as usize).checked_sub(1)?)?;
+    let line_end = line_starts.get(line as usize).copied().unwrap_or(source.len());
+    Some(source[line_start..line_end].trim_end_matches(['\r', '\n']))
+}
+
+/// The source code of all the files parsed by a session, resolving byte offsets to files, lines and columns.
+///
+/// Files are laid out one after the other, each one starting a byte after the end of the previous one,
+/// so an offset identifies both a file and a position inside it. Like lines and columns, offsets are `u32`.
+#[derive(Clone, Debug, Default)]
+pub struct SourceMap
+{
+    files: Vec<SourceFile>,
+    /// Offset of the next file. Offsets are never reused, not even after removing old files.
+    next_start: u32
+}
+
+impl SourceMap
+{
+    /// Registers a new file and returns it, `None` if its offsets would not fit in a `u32` (about 4 GiB of code in the whole map).
+    pub fn add_file(&mut self, name: impl Into<String>, source: impl Into<String>) -> Option<&SourceFile>
+    {
+        let source = source.into();
+        let end = u32::try_from(source.len()).ok().and_then(|len| self.next_start.checked_add(len))?;
+        let next_start = end.checked_add(1)?;
+        self.files.push(SourceFile::new(name.into(), source, self.next_start));
+        self.next_start = next_start;
+        self.files.last()
+    }
+
+    /// Removes the oldest files, keeping only the latest `count`. Spans pointing into the removed files no longer resolve.
+    pub fn retain_latest(&mut self, count: usize)
+    {
+        let removed = self.files.len().saturating_sub(count);
+        self.files.drain(..removed);
+    }
+
+    pub fn files(&self) -> &[SourceFile]
+    {
+        &self.files
+    }
+
+    /// Returns the file containing `offset`.
+    pub fn file(&self, offset: u32) -> Option<&SourceFile>
+    {
+        let index = self.files.partition_point(|file| file.end() < offset);
+        self.files.get(index).filter(|file| file.start() <= offset)
+    }
+
+    /// Returns the position of `offset`, with its line and column in its file.
+    pub fn position(&self, offset: u32) -> Option<Position>
+    {
+        self.file(offset).map(|file| file.position(offset))
+    }
+
+    /// Returns the code covered by `span`.
+    pub fn text(&self, span: Span) -> Option<&str>
+    {
+        let file = self.file(span.start.offset)?;
+        let start = span.start.offset - file.start();
+        let end = span.end.offset.checked_sub(file.start())?;
+        file.source().get(start as usize..end as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use crate::parser::position::{Position, Span};
+
+    use super::SourceMap;
+
+    #[test]
+    fn test_source_map() {
+        let mut source_map = SourceMap::default();
+        assert_eq!(source_map.add_file("first", "var à = 1;\r\nprint à;\rà;\n").unwrap().start(), 0);
+        assert_eq!(source_map.add_file("second", "print 2;").unwrap().start(), 28);
+        assert_eq!(source_map.add_file("empty", "").unwrap().start(), 37);
+
+        assert_eq!(source_map.file(0).unwrap().name(), "first");
+        assert_eq!(source_map.file(27).unwrap().name(), "first");
+        assert_eq!(source_map.file(28).unwrap().name(), "second");
+        assert_eq!(source_map.file(36).unwrap().name(), "second");
+        assert_eq!(source_map.file(37).unwrap().name(), "empty");
+        assert!(source_map.file(38).is_none());
+
+        let first = &source_map.files()[0];
+        assert_eq!(first.position(9), Position { line: 1, column: 9, offset: 9 });
+        assert_eq!(first.position(13), Position { line: 2, column: 1, offset: 13 });
+        assert_eq!(first.position(22), Position { line: 2, column: 9, offset: 22 });
+        assert_eq!(first.position(23), Position { line: 3, column: 1, offset: 23 });
+        assert_eq!(first.position(27), Position { line: 4, column: 1, offset: 27 });
+        assert_eq!(source_map.position(34), Some(Position { line: 1, column: 7, offset: 34 }));
+        assert_eq!(first.offset(1, 9), Some(9));
+        assert_eq!(first.offset(2, 7), Some(19));
+        assert_eq!(first.offset(1, 11), Some(11));
+        assert_eq!(first.offset(1, 12), None);
+        assert_eq!(first.offset(4, 1), Some(27));
+        assert_eq!(first.offset(5, 1), None);
+
+        let span = Span::new(first.position(19), first.position(21));
+        assert_eq!(source_map.text(span), Some("à"));
+        let span = Span::new(source_map.position(34).unwrap(), source_map.position(35).unwrap());
+        assert_eq!(source_map.text(span), Some("2"));
+
+        // the offsets of the removed files are not reused
+        source_map.retain_latest(1);
+        assert_eq!(source_map.files().len(), 1);
+        assert!(source_map.file(0).is_none());
+        assert_eq!(source_map.file(37).unwrap().name(), "empty");
+        assert_eq!(source_map.add_file("fourth", "a;").unwrap().start(), 38);
+    }
+
+    #[test]
+    fn test_offset_overflow() {
+        let mut source_map = SourceMap { files: Vec::new(), next_start: u32::MAX - 3 };
+        assert!(source_map.add_file("too long", "print 1;").is_none());
+        assert!(source_map.files().is_empty());
+        assert_eq!(source_map.add_file("fits", "a;").unwrap().end(), u32::MAX - 1);
+        // the next file would start after the largest offset
+        assert!(source_map.add_file("empty", "").is_none());
+    }
+}
@@ -5,13 +5,13 @@ use string_interner::Symbol;
 
 use crate::{alias::IdentifierSymbol, error::{InternalErrorKind, LoxError, ParserErrorKind}, utils::peekable_iterator::Peekable};
 
-use super::{position::Position, types::Identifier};
+use super::{position::Span, types::Identifier};
 
 #[derive(Clone, Debug)]
 pub struct Token
 {
     pub kind:     TokenKind,
-    pub position: Position
+    pub span: Span
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -36,11 +36,44 @@ pub enum TokenKind
     Print,              Return,
     True,               False,
     String(Rc<String>), Number(f64),  Identifier(IdentifierSymbol),
+    /// Piece of a string literal preceding an interpolated expression (`"Hello ${`), the string goes on after the expression.
+    StringPart(Rc<String>),
     Break,              Continue,
-    UnexpectedToken,
+    /// Lexical error, spanning the offending code.
+    Error(Rc<LexicalError>),
     Eof
 }
 
+/// Lexical error carried by a `TokenKind::Error` token.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LexicalError
+{
+    pub kind: ParserErrorKind,
+    /// The offending code, eg. the unexpected character or the malformed number.
+    pub text: String
+}
+
+/// Code skipped by the lexer, kept only in lossless mode (see `Lexer::with_trivia`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trivia
+{
+    pub kind: TriviaKind,
+    pub span: Span
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TriviaKind
+{
+    /// Spaces and tabs.
+    Whitespace,
+    /// A single line break: `\n`, `\r\n` or `\r`.
+    Newline,
+    LineComment,
+    BlockComment,
+    /// A malformed token, reported by an `Error` token.
+    Skipped
+}
+
 impl fmt::Display for TokenKind
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
@@ -83,11 +116,12 @@ impl fmt::Display for TokenKind
             TokenKind::True             => { write!(f, "True") },
             TokenKind::False            => { write!(f, "False") },
             TokenKind::String(_)        => { write!(f, "String") },
+            TokenKind::StringPart(_)    => { write!(f, "StringPart") },
             TokenKind::Number(_)        => { write!(f, "Number") },
             TokenKind::Identifier(id)   => { write!(f, "{}", id.to_usize()) },
             TokenKind::Break            => { write!(f, "Break") },
             TokenKind::Continue         => { write!(f, "Continue") },
-            TokenKind::UnexpectedToken  => { write!(f, "UnexpectedToken") },
+            TokenKind::Error(_)         => { write!(f, "Error") },
             TokenKind::Eof              => { write!(f, "EndOfFile") },
         }
     }
@@ -112,14 +146,14 @@ impl<'a> TokenSource<'a>
         if std::mem::discriminant(&token.kind) == std::mem::discriminant(&token_kind) {
             true
         } else {
-            return Err(LoxError::parser_error(ParserErrorKind::ExpectedToken(message.to_string()), token.position));
+            return Err(LoxError::parser_error(ParserErrorKind::ExpectedToken(message.to_string()), token.span));
         };
         if is_token_kind {
             let token = self.next().unwrap();
             return Ok(token);
         }
 
-        Err(LoxError::parser_error(ParserErrorKind::ExpectedToken(message.to_string()), token.position))
+        Err(LoxError::parser_error(ParserErrorKind::ExpectedToken(message.to_string()), token.span))
 
     }
 
@@ -129,12 +163,12 @@ impl<'a> TokenSource<'a>
     pub fn consume_identifier(&mut self, message: &str) -> Result<Identifier, LoxError>
     {
         let mut is_identifier = false;
-        let position;
+        let span;
 
         match self.peek()
         {
             Some(token) => {
-                position = token.position;
+                span = token.span;
                 if let TokenKind::Identifier(_) = &token.kind {
                     is_identifier = true
                 }
@@ -151,7 +185,7 @@ impl<'a> TokenSource<'a>
             {
                 TokenKind::Identifier(identifier) =>
                 {
-                    return Ok(Identifier {name: *identifier, position: token.position});
+                    return Ok(Identifier {name: *identifier, span: token.span});
                 },
                 _ => {
                     return Err(LoxError::internal_error(InternalErrorKind::ExpectToken));
@@ -159,7 +193,7 @@ impl<'a> TokenSource<'a>
             }
         }
 
-        Err(LoxError::parser_error(ParserErrorKind::ExpectedIdentifier(message.to_string()), position))
+        Err(LoxError::parser_error(ParserErrorKind::ExpectedIdentifier(message.to_string()), span))
 
     }
 
@@ -8,14 +8,15 @@ use unique_id::sequence::SequenceGenerator;
 use unique_id::Generator;
 
 use crate::alias::IdentifierSymbol;
+use crate::utils::native_stack::ensure_sufficient_stack;
 
-use super::{position::Position, tokens::{Token, TokenKind}};
+use super::{position::Span, tokens::{Token, TokenKind}};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Identifier
 {
     pub name: IdentifierSymbol,
-    pub position: Position
+    pub span: Span
 }
 
 impl fmt::Display for Identifier
@@ -30,7 +31,7 @@ impl fmt::Display for Identifier
 pub struct Operator<Kind>
 {
     pub kind: Kind,
-    pub position: Position
+    pub span: Span
 }
 
 #[derive(Clone, Debug)]
@@ -64,7 +65,7 @@ impl Operator<BinaryOperatorKind>
                 panic!("Internal error, unexpecter operator type");
             }
         };
-        Self { kind: bonary_op_kind, position: token.position }
+        Self { kind: bonary_op_kind, span: token.span }
     }
 }
 
@@ -85,7 +86,7 @@ impl Operator<UnaryOperatorKind>
                 panic!("Internal error, unexpecter operator type");
             }
         };
-        Self { kind: bonary_op_kind, position: token.position }
+        Self { kind: bonary_op_kind, span: token.span }
     }
 }
 
@@ -107,17 +108,17 @@ impl Operator<LogicalOperatorKind>
                 panic!("Internal error, unexpecter operator type");
             }
         };
-        Self { kind: bonary_op_kind, position: token.position }
+        Self { kind: bonary_op_kind, span: token.span }
     }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Literal {
-    String(Rc<String>, Position),
-    Number(f64, Position),
-    True(Position),
-    False(Position),
-    Nil(Position),
+    String(Rc<String>, Span),
+    Number(f64, Span),
+    True(Span),
+    False(Span),
+    Nil(Span),
 }
 
 static ID_GENERATOR: Lazy<SequenceGenerator> = Lazy::new(SequenceGenerator::default);
@@ -126,13 +127,31 @@ static ID_GENERATOR: Lazy<SequenceGenerator> = Lazy::new(SequenceGenerator::defa
 pub struct Expr
 {
     pub id: i64,
-    pub kind: ExprKind
+    pub kind: ExprKind,
+    pub span: Span
 }
 
 impl Expr
 {
-    pub fn new(kind: ExprKind) -> Self {
-        Self { id: ID_GENERATOR.next_id(), kind }
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Self { id: ID_GENERATOR.next_id(), kind, span }
+    }
+
+    /// Takes the kind of the expression, dropping the rest.
+    pub fn into_kind(mut self) -> ExprKind {
+        std::mem::replace(&mut self.kind, ExprKind::This(self.span))
+    }
+}
+
+impl Drop for Expr
+{
+    /// Drops the sub-expressions on a grown stack if needed, so that a deeply nested tree cannot overflow the native stack.
+    fn drop(&mut self)
+    {
+        if !matches!(self.kind, ExprKind::Literal(_) | ExprKind::Variable(_) | ExprKind::This(_) | ExprKind::Super(_)) {
+            let kind = std::mem::replace(&mut self.kind, ExprKind::This(self.span));
+            ensure_sufficient_stack(|| drop(kind));
+        }
     }
 }
 
@@ -149,8 +168,10 @@ pub enum ExprKind
     Call    (Box<CallExpr>),
     Get     (Box<GetExpr>),
     Set     (Box<SetExpr>),
-    This    (Position),
-    Super   (Identifier)
+    This    (Span),
+    Super   (Identifier),
+    /// Interpolated string (`"a ${b} c"`): the string pieces and the embedded expressions, in source order.
+    Interpolation(Vec<Expr>)
 }
 
 #[derive(Clone, Debug)]
@@ -195,12 +216,42 @@ pub struct SetExpr {
 #[derive(Clone, Debug)]
 pub struct CallExpr {
     pub callee: Expr,
-    pub arguments: Vec<Expr>,
-    pub position: Position
+    pub arguments: Vec<Expr>
+}
+
+#[derive(Clone, Debug)]
+pub struct Stmt
+{
+    pub kind: StmtKind,
+    pub span: Span
+}
+
+impl Stmt
+{
+    pub const fn new(kind: StmtKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+
+    /// Takes the kind of the statement, dropping the rest.
+    pub fn into_kind(mut self) -> StmtKind {
+        std::mem::replace(&mut self.kind, StmtKind::Break)
+    }
+}
+
+impl Drop for Stmt
+{
+    /// Drops the nested statements and expressions on a grown stack if needed, like `Expr`.
+    fn drop(&mut self)
+    {
+        if !matches!(self.kind, StmtKind::Break | StmtKind::Continue) {
+            let kind = std::mem::replace(&mut self.kind, StmtKind::Break);
+            ensure_sufficient_stack(|| drop(kind));
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
-pub enum Stmt
+pub enum StmtKind
 {
     Expr    (Expr),
     Var     (Identifier, Option<Expr>),
@@ -208,7 +259,7 @@ pub enum Stmt
     If      (Box<IfStmt>),
     IfElse  (Box<IfElseStmt>),
     While   (Box<WhileStmt>),
-    Return  (Option<Expr>, Position),
+    Return  (Option<Expr>, Span),
     Break,
     Continue,
     FunctionDeclaration (Rc<FunctionDeclaration>),
@@ -240,23 +291,26 @@ pub struct FunctionDeclaration
 {
     pub identifier: Identifier,
     pub parameters: Vec<IdentifierSymbol>,
-    pub positions: Vec<Position>,
+    pub spans: Vec<Span>,
     //Attenzione! non puo' essere uno Stmt altrimenti i parametri della funzione verrebbero definiti in uno scope esterno rispetto al body e l'utente potrebbe ridefinirli nel body!
     pub body: Vec<Stmt>,
-    pub is_initializer: bool
+    pub is_initializer: bool,
+    /// The code of the function, from its name to the end of its body.
+    pub span: Span
 }
 
 impl FunctionDeclaration
 {
-    pub fn new(identifier: Identifier, parameters: Vec<Identifier>, body: Vec<Stmt>, is_initializer: bool) -> Self
+    pub fn new(identifier: Identifier, parameters: Vec<Identifier>, body: Vec<Stmt>, is_initializer: bool, span: Span) -> Self
     {
         Self
         {
             identifier,
             parameters: parameters.iter().map(|p| p.name).collect(),
-            positions: parameters.iter().map(|p| p.position).collect(),
+            spans: parameters.iter().map(|p| p.span).collect(),
             body,
-            is_initializer
+            is_initializer,
+            span
         }
     }
 }
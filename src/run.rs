@@ -1,85 +1,530 @@
-use std::{cell::RefCell, fs, io::Write, rc::Rc};
+use std::{cell::RefCell, fs, io::{self, BufRead, Read, Write}, rc::Rc, sync::{atomic::{AtomicBool, Ordering}, Arc}};
 
 use string_interner::StringInterner;
 
-use crate::{alias::IdentifierSymbol, benches::{BINARY_TREES_LOX, EQUALITY_LOX, FIB_LOX, INSTANTIATION_LOX, INVOCATION_LOX, METHOD_CALL_LOX, PROPERTIES_LOX, STRING_EQUALITY_LOX, TREES_LOX, ZOO_BATCH_LOX, ZOO_LOX}, error::ExecutionResult, interpreter::interpreter::Interpreter, parser::{parser::Parser, resolver::Resolver, types::Stmt}};
+use crate::{cli::{Dump, Source, UsageError}, benches::{BINARY_TREES_LOX, EQUALITY_LOX, FIB_LOX, INSTANTIATION_LOX, INVOCATION_LOX, METHOD_CALL_LOX, PROPERTIES_LOX, STRING_EQUALITY_LOX, TREES_LOX, ZOO_BATCH_LOX, ZOO_LOX}, error::{ErrorFormat, ExecutionResult, InputErrorKind, LoxError, ParserErrorKind}, interpreter::{limits::Limits, sandbox::Sandbox}, parser::{lexer::Lexer, source_map::SourceMap, tokens::TokenKind}, session::Session};
+
+/// Number of inputs whose code the REPL keeps to show the source of errors.
+const REPL_MAX_CHUNKS: usize = 1000;
+
+/// Settings for running a script.
+#[derive(Clone, Debug, Default)]
+pub struct Options
+{
+   /// Arguments made available to the script through the `args` native function.
+   pub script_args: Vec<String>,
+   /// Format of the errors written to the error writer.
+   pub error_format: ErrorFormat,
+   /// Resource limits enforced while running the script.
+   pub limits: Limits,
+   /// Flag that interrupts the script when set (eg. by a Ctrl-C handler).
+   pub interrupt: Option<Arc<AtomicBool>>,
+   /// Native functions available to the script.
+   pub sandbox: Sandbox
+}
 
 /// Executes a file.
-pub fn run_file(filepath: &str, writer: &mut dyn Write) -> Result<(), ExecutionResult>
+///
+/// Program output is written to `writer`, diagnostics to `error_writer`.
+pub fn run_file(filepath: &str, writer: &mut dyn Write, error_writer: &mut dyn Write) -> Result<(), ExecutionResult>
+{
+   run_file_with_options(filepath, &Options::default(), writer, error_writer)
+}
+
+/// Executes a file with the supplied options.
+pub fn run_file_with_options(filepath: &str, options: &Options, writer: &mut dyn Write, error_writer: &mut dyn Write) -> Result<(), ExecutionResult>
 {
    let r_code = fs::read_to_string(filepath);
    match r_code {
       Ok(code) => {
-         run(&code, writer)
+         run_with_options(&code, options, writer, error_writer)
+      },
+      Err(error) => {
+         Err(write_input_error(error_writer, InputErrorKind::CannotReadFile(error.to_string()), options.error_format))
+      },
+   }
+}
+
+/// Executes the code read from `reader` (eg. the standard input) until EOF with the supplied options.
+pub fn run_reader<R: Read>(mut reader: R, options: &Options, writer: &mut dyn Write, error_writer: &mut dyn Write) -> Result<(), ExecutionResult>
+{
+   let mut code = String::new();
+   match reader.read_to_string(&mut code) {
+      Ok(_) => {
+         run_with_options(&code, options, writer, error_writer)
       },
       Err(error) => {
-         println!("\nCannot read file: {}\n", error);
-         Err(ExecutionResult::CannotReadFile)
+         Err(write_input_error(error_writer, InputErrorKind::CannotReadInput(error.to_string()), options.error_format))
       },
    }
 }
 
 /// Executes the supplied code.
-pub fn run<T:Write>(code: &str, writer: T) -> Result<(), ExecutionResult>
+///
+/// Program output is written to `writer`, while errors are written to `error_writer`. The result reports the stage of the first error.
+pub fn run<T:Write, E:Write>(code: &str, writer: T, error_writer: E) -> Result<(), ExecutionResult>
+{
+   run_with_options(code, &Options::default(), writer, error_writer)
+}
+
+/// Executes the supplied code with the supplied options.
+pub fn run_with_options<T:Write, E:Write>(code: &str, options: &Options, writer: T, mut error_writer: E) -> Result<(), ExecutionResult>
+{
+   let mut session = Session::new(writer);
+   session.set_script_args(options.script_args.clone());
+   session.set_limits(options.limits.clone());
+   session.set_sandbox(options.sandbox.clone());
+   if let Some(interrupt) = &options.interrupt {
+      session.set_interrupt_handle(Arc::clone(interrupt));
+   }
+   session.run(code).map_err(|errors| {
+      write_errors(&mut error_writer, &errors, options.error_format, session.source_map());
+      ExecutionResult::from(&errors[0])
+   })
+}
+
+/// Writes the supplied errors in the given format: human readable errors show the offending line of the code they were found in and are separated by an empty line, JSON errors take one line each.
+fn write_errors<T: Write + ?Sized>(writer: &mut T, errors: &[LoxError], error_format: ErrorFormat, source_map: &SourceMap)
+{
+   for error in errors {
+      let source = error.span.and_then(|span| source_map.file(span.start.offset)).map_or("", |file| file.source());
+      let _ = match error_format {
+         ErrorFormat::Human => writeln!(writer, "{}", error.render(source)),
+         ErrorFormat::Json  => writeln!(writer, "{}", error.to_json()),
+      };
+   }
+}
+
+/// Writes an error met reading the code of a script, which has no source to show.
+fn write_input_error(error_writer: &mut dyn Write, kind: InputErrorKind, error_format: ErrorFormat) -> ExecutionResult
+{
+   let error = LoxError::input_error(kind);
+   write_errors(error_writer, std::slice::from_ref(&error), error_format, &SourceMap::default());
+   ExecutionResult::from(&error)
+}
+
+/// Writes an error in the command line arguments in the format requested before it, along with a hint on the usage.
+pub fn write_usage_error(usage_error: &UsageError, error_writer: &mut dyn Write) -> ExecutionResult
+{
+   const USAGE: &str = "Usage: `rlox [path/to/script]`. Type `rlox --help` for further info.";
+   let error = LoxError::input_error(InputErrorKind::InvalidUsage(usage_error.message.clone())).with_help(USAGE);
+   let _ = match usage_error.error_format {
+      ErrorFormat::Human => writeln!(error_writer, "{}\n{}", usage_error, USAGE),
+      ErrorFormat::Json  => writeln!(error_writer, "{}", error.to_json()),
+   };
+   ExecutionResult::from(&error)
+}
+
+/// Reads the code of a script from the given source, writing the error in the given format if it cannot be read.
+pub fn read_source(source: &Source, error_format: ErrorFormat, error_writer: &mut dyn Write) -> Result<String, ExecutionResult>
+{
+   match source {
+      Source::File(filepath) => fs::read_to_string(filepath).map_err(|error| write_input_error(error_writer, InputErrorKind::CannotReadFile(error.to_string()), error_format)),
+      Source::Stdin => {
+         let mut code = String::new();
+         io::stdin().read_to_string(&mut code)
+            .map(|_| code)
+            .map_err(|error| write_input_error(error_writer, InputErrorKind::CannotReadInput(error.to_string()), error_format))
+      },
+      Source::Eval(code) => Ok(code.to_owned()),
+   }
+}
+
+/// Writes the requested debugging output for the supplied code (tokens, concrete or abstract syntax tree, resolved variables) instead of running it.
+///
+/// Errors found along the way are written to `error_writer`, the result reports the stage of the first one.
+pub fn dump<T:Write, E:Write>(code: &str, dump: Dump, options: &Options, mut writer: T, mut error_writer: E) -> Result<(), ExecutionResult>
+{
+   let mut session = Session::new(io::sink());
+   let (lines, errors) = match dump {
+      Dump::Tokens  => session.dump_tokens(code),
+      Dump::Cst     => session.dump_cst(code),
+      Dump::Ast     => session.dump_ast(code).map_or_else(|errors| (Vec::new(), errors), |lines| (lines, Vec::new())),
+      Dump::Resolve => session.dump_resolve(code).map_or_else(|errors| (Vec::new(), errors), |lines| (lines, Vec::new())),
+   };
+   for line in lines {
+      let _ = writeln!(writer, "{}", line);
+   }
+   if errors.is_empty() {
+      Ok(())
+   } else {
+      write_errors(&mut error_writer, &errors, options.error_format, session.source_map());
+      Err(ExecutionResult::from(&errors[0]))
+   }
+}
+
+/// Starts an interactive session (read-eval-print loop) reading the code line by line from `reader`.
+///
+/// A single `Session` is kept alive for the whole loop, so declarations made on previous lines stay visible.
+/// The value of bare expression statements is printed back and input is accumulated over multiple lines while there are unclosed braces.
+/// Errors are written to `error_writer` and discard only the offending input.
+pub fn repl<R: BufRead, T: Write, E: Write>(reader: R, writer: T, error_writer: E)
+{
+   repl_with_options(reader, writer, error_writer, &Options::default());
+}
+
+/// Same as `repl`, with the supplied options (the script arguments excepted). Errors are written in the requested format.
+///
+/// Setting the `interrupt` flag (eg. on Ctrl-C) aborts the line being run with an `Interrupted` error and goes back to the prompt.
+/// The flag is cleared before running each line, so an interruption requested at the prompt is ignored.
+pub fn repl_with_options<R: BufRead, T: Write, E: Write>(mut reader: R, writer: T, mut error_writer: E, options: &Options)
 {
-   let stmts: Vec<Stmt>;
-   let mut interner: StringInterner = StringInterner::default();
-   let _ = interner.get_or_intern_static("this");
-   let _ = interner.get_or_intern_static("super");
-   let init_symbol: IdentifierSymbol = interner.get_or_intern_static("init");
    let writer = Rc::new(RefCell::new(writer));
-   {
-      let mut parser: Parser<T> = Parser::new(Rc::clone(&writer), init_symbol);
-      stmts = parser.parse(code, &mut interner)?;
+   let mut session = Session::new_with_writer(Rc::clone(&writer));
+   session.set_script_args(Vec::new());
+   session.set_limits(options.limits.clone());
+   session.set_sandbox(options.sandbox.clone());
+   if let Some(interrupt) = &options.interrupt {
+      session.set_interrupt_handle(Arc::clone(interrupt));
    }
-   let mut interpreter;
-   {
-      let mut resolver: Resolver<T> = Resolver::new(Rc::clone(&writer), &mut interner);
-      let side_table = resolver.resolve(&stmts)?;
-      interpreter = Interpreter::new_with_writer(&mut interner, side_table, Rc::clone(&writer));
+   let interrupt = session.interrupt_handle();
+   session.set_max_chunks(Some(REPL_MAX_CHUNKS));
+   let mut code = String::new();
+   loop {
+      let prompt = if code.is_empty() { "> " } else { "... " };
+      let _ = write!(writer.borrow_mut(), "{}", prompt);
+      let _ = writer.borrow_mut().flush();
+
+      let mut line = String::new();
+      match reader.read_line(&mut line) {
+         Ok(0) | Err(_) => {
+            let _ = writeln!(writer.borrow_mut());
+            return;
+         },
+         Ok(_) => {
+            code.push_str(&line);
+         }
+      }
+      if is_unfinished(&code) {
+         continue;
+      }
+      interrupt.store(false, Ordering::Relaxed);
+      if let Err(errors) = session.eval(&code) {
+         write_errors(&mut error_writer, &errors, options.error_format, session.source_map());
+      }
+      code.clear();
    }
-   interpreter.execute(&stmts)
 }
 
-/// Runs the benchmarks designed by the autor of the language and prints out the results.
-pub fn bench() {
+/// Checks if the user has not finished typing the supplied code: it contains more opening braces than closing ones,
+/// or it ends inside a block comment or a string (eg. a `"""` multi-line string).
+fn is_unfinished(code: &str) -> bool
+{
+   let mut interner: StringInterner = StringInterner::default();
+   let lexer = Lexer::new(code, &mut interner);
+   let mut depth: i32 = 0;
+   let mut is_open = false;
+   for token in lexer {
+      match token.kind {
+         TokenKind::LeftBrace  => { depth += 1; },
+         TokenKind::RightBrace => { depth -= 1; },
+         TokenKind::Error(error) => {
+            is_open = matches!(error.kind, ParserErrorKind::UnterminatedString | ParserErrorKind::UnterminatedComment);
+            continue;
+         },
+         TokenKind::Eof => { break; },
+         _ => {}
+      }
+      is_open = false;
+   }
+   depth > 0 || is_open
+}
+
+/// Runs the benchmarks designed by the autor of the language `iterations` times and prints out the results.
+///
+/// With more than one iteration, the average elapsed time is printed along with its standard deviation.
+pub fn bench(iterations: usize) {
    let benches = [BINARY_TREES_LOX, EQUALITY_LOX, FIB_LOX, INSTANTIATION_LOX, INVOCATION_LOX, METHOD_CALL_LOX, PROPERTIES_LOX, STRING_EQUALITY_LOX, TREES_LOX, ZOO_BATCH_LOX, ZOO_LOX];
    println!("{:<16} ;{:<10}", "TEST TYPE", "ELAPSED");
    for (test_index, bench) in benches.iter().enumerate() {
-      let mut buf_output = Vec::<u8>::new();
-      let _ = run(bench, &mut buf_output);
-      let lines: Vec<&str> = std::str::from_utf8(&buf_output).unwrap().lines().collect();
-      let mut text: &str = "";
-      let mut result: f64 = -1.0;
-      for (index, line) in lines.into_iter().enumerate() {
-         match index {
-            0 => {
-               if !line.contains("elapsed") {
-                  panic!("first line {} do not contains 'elapsed'", line);
-               }
-            },
-            1 => {
-               text = match test_index {
-                  0 => {"BINARY_TREES"},
-                  1 => {"EQUALITY"},
-                  2 => {"FIB"},
-                  3 => {"INSTANTIATION"},
-                  4 => {"INVOCATION"},
-                  5 => {"METHOD_CALL"},
-                  6 => {"PROPERTIES"},
-                  7 => {"STRING_EQUALITY"},
-                  8 => {"TREES"},
-                  9 => {"ZOO_BATCH"},
-                  10 => {"ZOO"},
-                  _ => {panic!("inexpected banch type")}
-               };
-               result = line.to_string().parse::<f64>().unwrap();
-            }
-            _ => {
-               panic!()
+      let text = match test_index {
+         0 => {"BINARY_TREES"},
+         1 => {"EQUALITY"},
+         2 => {"FIB"},
+         3 => {"INSTANTIATION"},
+         4 => {"INVOCATION"},
+         5 => {"METHOD_CALL"},
+         6 => {"PROPERTIES"},
+         7 => {"STRING_EQUALITY"},
+         8 => {"TREES"},
+         9 => {"ZOO_BATCH"},
+         10 => {"ZOO"},
+         _ => {panic!("inexpected banch type")}
+      };
+      let results: Vec<f64> = (0..iterations).map(|_| run_bench(bench)).collect();
+      let average = results.iter().sum::<f64>() / results.len() as f64;
+      if iterations > 1 {
+         let deviation = (results.iter().map(|result| (result - average).powi(2)).sum::<f64>() / results.len() as f64).sqrt();
+         println!("{:<16} ;{:>9} +/- {:.3}", text, format!("{:.3}", average), deviation)
+      } else {
+         println!("{:<16} ;{:>9}", text, format!("{:.3}", average))
+      }
+   }
+}
+
+/// Runs a single benchmark and returns the elapsed time it prints out.
+fn run_bench(bench: &str) -> f64 {
+   let mut buf_output = Vec::<u8>::new();
+   let _ = run(bench, &mut buf_output, io::sink());
+   let lines: Vec<&str> = std::str::from_utf8(&buf_output).unwrap().lines().collect();
+   let mut result: f64 = -1.0;
+   for (index, line) in lines.into_iter().enumerate() {
+      match index {
+         0 => {
+            if !line.contains("elapsed") {
+               panic!("first line {} do not contains 'elapsed'", line);
             }
+         },
+         1 => {
+            result = line.to_string().parse::<f64>().unwrap();
+         }
+         _ => {
+            panic!()
          }
       }
-      println!("{:<16} ;{:>9}", text, format!("{:.3}", result))
    }
-}
\ No newline at end of file
+   result
+}
+
+#[cfg(test)]
+mod tests {
+   use std::{io, sync::{atomic::{AtomicBool, Ordering}, Arc}, thread, time::Duration};
+
+   use crate::{cli, error::{ErrorFormat, ExecutionResult, EXIT_USAGE}, interpreter::limits::Limits};
+
+   use crate::cli::Dump;
+
+   use super::{dump, is_unfinished, repl, repl_with_options, run, run_file, run_file_with_options, run_reader, run_with_options, write_usage_error, Options};
+
+   fn lines(buf: &[u8]) -> Vec<String>
+   {
+      std::str::from_utf8(buf).unwrap().lines().map(|line| line.to_owned()).collect()
+   }
+
+   fn run_repl(input: &str) -> (Vec<String>, Vec<String>)
+   {
+      let mut buf_output = Vec::<u8>::new();
+      let mut buf_error = Vec::<u8>::new();
+      repl(input.as_bytes(), &mut buf_output, &mut buf_error);
+      (lines(&buf_output), lines(&buf_error))
+   }
+
+   #[test]
+   fn test_repl_keeps_state() {
+      let (output, errors) = run_repl("var a = 1;\nfun add(x) {\n  return a + x;\n}\nadd(2);\nvar str = \"shadow\";\nstr;\na = 2;\nadd(2);\nassertEq(a, 2);\n");
+      assert_eq!(output, vec!["> > ... ... > 3", "> > shadow", "> > 4", "> > "]);
+      assert!(errors.is_empty());
+   }
+
+   #[test]
+   fn test_repl_interrupt() {
+      let interrupt = Arc::new(AtomicBool::new(true));
+      let mut buf_output = Vec::<u8>::new();
+      let mut buf_error = Vec::<u8>::new();
+      let handle = Arc::clone(&interrupt);
+      let setter = thread::spawn(move || {
+         thread::sleep(Duration::from_millis(100));
+         handle.store(true, Ordering::Relaxed);
+      });
+      let options = Options { interrupt: Some(interrupt), ..Options::default() };
+      repl_with_options("print 0;\nwhile (true) {}\nprint 1;\n".as_bytes(), &mut buf_output, &mut buf_error, &options);
+      setter.join().unwrap();
+      assert_eq!(lines(&buf_output), vec!["> 0", "> > 1", "> "]);
+      assert_eq!(lines(&buf_error)[0], "error[E0318]: Interrupted.");
+   }
+
+   #[test]
+   fn test_repl_continues_comments_and_strings() {
+      let mut buf_output = Vec::<u8>::new();
+      let mut buf_error = Vec::<u8>::new();
+      let input = "print 1; /* a\ncomment */ print 2;\nprint \"\"\"\n  multi\n  line\n  \"\"\";\n";
+      repl_with_options(input.as_bytes(), &mut buf_output, &mut buf_error, &Options::default());
+      assert_eq!(lines(&buf_output), vec!["> ... 1", "2", "> ... ... ... multi", "line", "> "]);
+      assert!(buf_error.is_empty());
+   }
+
+   #[test]
+   fn test_repl_recovers_from_errors() {
+      let (output, errors) = run_repl("var count = 1;\n1 +;\nprint cout;\nreturn count;\ncount;\n");
+      assert_eq!(output, vec!["> > > > > 1", "> "]);
+      assert_eq!(errors, vec![
+         "error[E0106]: Expect expression",
+         " --> 1:4",
+         "  |",
+         "1 | 1 +;",
+         "  |    ^",
+         "",
+         "error[E0312]: Undefined variable 'cout'.",
+         " --> 1:7",
+         "  |",
+         "1 | print cout;",
+         "  |       ^^^^",
+         "  = help: did you mean `count`?",
+         "",
+         "error[E0203]: Can't return from top-level code.",
+         " --> 1:1",
+         "  |",
+         "1 | return count;",
+         "  | ^^^^^^",
+         ""
+      ]);
+   }
+
+   #[test]
+   fn test_repl_shows_the_line_of_earlier_input() {
+      let (_, errors) = run_repl("fun neg(x) {\n  return -x;\n}\nneg(\"a\");\n");
+      assert_eq!(errors[..5], [
+         "error[E0302]: Operand must be a number.",
+         " --> 2:10",
+         "  |",
+         "2 |   return -x;",
+         "  |          ^^"
+      ]);
+   }
+
+   #[test]
+   fn test_is_unfinished() {
+      assert!(is_unfinished("fun foo() {"));
+      assert!(is_unfinished("class Foo { bar() {}"));
+      assert!(!is_unfinished("fun foo() {}"));
+      assert!(!is_unfinished("print \"{\";"));
+      assert!(!is_unfinished("// {"));
+      assert!(is_unfinished("print 1; /* a\n"));
+      assert!(is_unfinished("print \"\"\"\n  a\n"));
+      assert!(!is_unfinished("print 1; /* a */\n"));
+      assert!(!is_unfinished("print \"\"\"\n  a\n  \"\"\";\n"));
+   }
+
+   #[test]
+   fn test_execution_result() {
+      assert_eq!(run("print 1 +;", io::sink(), io::sink()), Err(ExecutionResult::ParserError));
+      assert_eq!(run("return 1;", io::sink(), io::sink()), Err(ExecutionResult::ResolverError));
+      assert_eq!(run("print -nil;", io::sink(), io::sink()), Err(ExecutionResult::RuntimeError));
+      assert_eq!(run_file("./missing.lox", &mut io::sink(), &mut io::sink()), Err(ExecutionResult::CannotReadFile));
+      assert_eq!(ExecutionResult::ParserError.exit_code(), 65);
+      assert_eq!(ExecutionResult::ResolverError.exit_code(), 65);
+      assert_eq!(ExecutionResult::RuntimeError.exit_code(), 70);
+      assert_eq!(ExecutionResult::CannotReadFile.exit_code(), 66);
+   }
+
+   #[test]
+   fn test_deep_nesting() {
+      let nested = |depth: usize| format!("print {}1{};", "(".repeat(depth), ")".repeat(depth));
+      assert_eq!(run(&nested(200), io::sink(), io::sink()), Ok(()));
+      //neither long else-if chains nor right-recursive expressions are limited
+      let mut buf_output = Vec::<u8>::new();
+      let arms: Vec<String> = (0..300).map(|index| format!("if (x == {}) print {};", index, index)).collect();
+      assert_eq!(run(&format!("var x = 5;\n{} else print -1;", arms.join(" else ")), &mut buf_output, io::sink()), Ok(()));
+      assert_eq!(lines(&buf_output), vec!["5"]);
+      assert_eq!(run(&format!("var a;\n{}1;", "a = ".repeat(10_000)), io::sink(), io::sink()), Ok(()));
+      assert_eq!(run(&nested(100_000), io::sink(), io::sink()), Ok(()));
+      assert_eq!(run(&format!("print {}1;", "-".repeat(100_000)), io::sink(), io::sink()), Ok(()));
+      for kind in [Dump::Cst, Dump::Ast, Dump::Resolve] {
+         assert_eq!(dump(&nested(20_000), kind, &Options::default(), io::sink(), io::sink()), Ok(()));
+      }
+   }
+
+   #[test]
+   fn test_limits() {
+      let mut buf_error = Vec::<u8>::new();
+      let options = Options { limits: Limits { max_instructions: Some(100), ..Limits::default() }, ..Options::default() };
+      assert_eq!(run_with_options("while (true) {}", &options, io::sink(), &mut buf_error), Err(ExecutionResult::RuntimeError));
+      assert_eq!(lines(&buf_error)[0], "error[E0315]: Instruction limit exceeded.");
+
+      // statements are counted without checking the deadline: the counter of this loop steps over every multiple of the check interval
+      let mut buf_error = Vec::<u8>::new();
+      let options = Options { limits: Limits { timeout: Some(Duration::from_millis(100)), ..Limits::default() }, ..Options::default() };
+      assert_eq!(run_with_options("var x = 1; while (true) { x; x; x; }", &options, io::sink(), &mut buf_error), Err(ExecutionResult::RuntimeError));
+      assert_eq!(lines(&buf_error)[0], "error[E0316]: Execution timed out.");
+   }
+
+   #[test]
+   fn test_script_args() {
+      let mut buf_output = Vec::<u8>::new();
+      let options = Options { script_args: vec!["first".to_owned(), "second".to_owned()], ..Options::default() };
+      assert_eq!(run_with_options("print args(); print args(0); print args(1); print args(2);", &options, &mut buf_output, io::sink()), Ok(()));
+      assert_eq!(lines(&buf_output), vec!["2", "first", "second", "nil"]);
+      assert_eq!(run("print args(); args(-1);", io::sink(), io::sink()), Err(ExecutionResult::RuntimeError));
+   }
+
+   #[test]
+   fn test_run_reader() {
+      let mut buf_output = Vec::<u8>::new();
+      let options = Options { script_args: vec!["stdin".to_owned()], ..Options::default() };
+      assert_eq!(run_reader("print args(0);".as_bytes(), &options, &mut buf_output, &mut io::sink()), Ok(()));
+      assert_eq!(lines(&buf_output), vec!["stdin"]);
+   }
+
+   #[test]
+   fn test_dump() {
+      let mut buf_output = Vec::<u8>::new();
+      let mut buf_error = Vec::<u8>::new();
+      assert_eq!(dump("print 1 $ 2;", Dump::Tokens, &Options::default(), &mut buf_output, &mut buf_error), Err(ExecutionResult::ParserError));
+      assert_eq!(lines(&buf_output), vec!["[line 1:1] Print", "[line 1:7] Number(1)", "[line 1:9] Error(\"$\")", "[line 1:11] Number(2)", "[line 1:12] Semicolon", "[line 1:13] EndOfFile"]);
+      assert_eq!(lines(&buf_error)[0], "error[E0101]: Unexpected token '$'.");
+      let mut buf_output = Vec::<u8>::new();
+      assert_eq!(dump("print 1 $ 2;", Dump::Cst, &Options::default(), &mut buf_output, io::sink()), Err(ExecutionResult::ParserError));
+      assert_eq!(lines(&buf_output)[4..6], ["  Whitespace \" \"", "  Skipped \"$\""]);
+      let mut buf_output = Vec::<u8>::new();
+      assert_eq!(dump("print a;", Dump::Ast, &Options::default(), &mut buf_output, io::sink()), Ok(()));
+      assert_eq!(lines(&buf_output), vec!["(print a)"]);
+      let mut buf_output = Vec::<u8>::new();
+      assert_eq!(dump("var a = 1;\nfun f() { print a; }", Dump::Resolve, &Options::default(), &mut buf_output, io::sink()), Ok(()));
+      assert_eq!(lines(&buf_output), vec!["[line 2:17] a -> global"]);
+      let mut buf_output = Vec::<u8>::new();
+      assert_eq!(dump("fun f() { var a = 1; { print a; } }", Dump::Resolve, &Options::default(), &mut buf_output, io::sink()), Ok(()));
+      assert_eq!(lines(&buf_output), vec!["[line 1:30] a -> distance 1"]);
+      let mut buf_output = Vec::<u8>::new();
+      let mut buf_error = Vec::<u8>::new();
+      assert_eq!(dump("return;", Dump::Resolve, &Options::default(), &mut buf_output, &mut buf_error), Err(ExecutionResult::ResolverError));
+      assert!(buf_output.is_empty());
+      assert_eq!(lines(&buf_error)[0], "error[E0203]: Can't return from top-level code.");
+   }
+
+   #[test]
+   fn test_stack_trace() {
+      let mut buf_error = Vec::<u8>::new();
+      let code = "class A {\n  init(x) { this.x = -x; }\n}\nfun make(x) {\n  return A(x);\n}\nmake(\"a\");";
+      assert_eq!(run(code, io::sink(), &mut buf_error), Err(ExecutionResult::RuntimeError));
+      assert_eq!(lines(&buf_error)[5..], ["[line 2] in init()", "[line 5] in make()", "[line 7] in script", ""]);
+   }
+
+   #[test]
+   fn test_usage_errors() {
+      let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<String>>();
+      let mut buf_error = Vec::<u8>::new();
+      let error = cli::parse_args(&args(&["--unknown"])).unwrap_err();
+      assert_eq!(write_usage_error(&error, &mut buf_error), ExecutionResult::InvalidUsage);
+      assert_eq!(lines(&buf_error), vec!["Unknown option '--unknown'.", "Usage: `rlox [path/to/script]`. Type `rlox --help` for further info."]);
+      buf_error.clear();
+      let error = cli::parse_args(&args(&["--error-format=json", "--timeout", "0", "script.lox"])).unwrap_err();
+      assert_eq!(write_usage_error(&error, &mut buf_error).exit_code(), EXIT_USAGE);
+      assert_eq!(lines(&buf_error), vec![
+         r#"{"stage":"input","code":"E0403","message":"Invalid value '0' for '--timeout': expected a positive integer.","line":null,"column":null,"span":null,"help":"Usage: `rlox [path/to/script]`. Type `rlox --help` for further info.","stack_trace":[]}"#
+      ]);
+   }
+
+   #[test]
+   fn test_json_errors() {
+      let mut buf_error = Vec::<u8>::new();
+      let options = Options { error_format: ErrorFormat::Json, ..Options::default() };
+      assert_eq!(run_with_options("print 1 +;\nprint \"a\" - 1;", &options, io::sink(), &mut buf_error), Err(ExecutionResult::ParserError));
+      assert_eq!(lines(&buf_error), vec![
+         r#"{"stage":"parser","code":"E0106","message":"Expect expression","line":1,"column":10,"span":{"start":{"line":1,"column":10,"offset":9},"end":{"line":1,"column":11,"offset":10}},"help":null,"stack_trace":[]}"#
+      ]);
+      buf_error.clear();
+      assert_eq!(run_with_options("assertEq(1, 2);", &options, io::sink(), &mut buf_error), Err(ExecutionResult::RuntimeError));
+      assert_eq!(lines(&buf_error), vec![
+         r#"{"stage":"runtime","code":"E0307","message":"Assertion failure.","line":1,"column":1,"span":{"start":{"line":1,"column":1,"offset":0},"end":{"line":1,"column":15,"offset":14}},"help":null,"stack_trace":[{"function":null,"line":1,"column":1}]}"#
+      ]);
+      buf_error.clear();
+      assert_eq!(run_file_with_options("./missing.lox", &options, &mut io::sink(), &mut buf_error), Err(ExecutionResult::CannotReadFile));
+      assert!(lines(&buf_error)[0].starts_with(r#"{"stage":"input","code":"E0401","message":"Cannot read file: "#));
+      buf_error.clear();
+      let mut buf_output = Vec::<u8>::new();
+      repl_with_options("print cout;\n".as_bytes(), &mut buf_output, &mut buf_error, &options);
+      assert_eq!(lines(&buf_error), vec![
+         r#"{"stage":"runtime","code":"E0312","message":"Undefined variable 'cout'.","line":1,"column":7,"span":{"start":{"line":1,"column":7,"offset":6},"end":{"line":1,"column":11,"offset":10}},"help":null,"stack_trace":[{"function":null,"line":1,"column":7}]}"#
+      ]);
+   }
+}
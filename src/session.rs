@@ -0,0 +1,562 @@
+use std::{cell::RefCell, io::Write, rc::Rc, sync::{atomic::AtomicBool, Arc}};
+
+use string_interner::StringInterner;
+
+use crate::{alias::{IdentifierSymbol, SideTable}, error::{InternalErrorKind, InterpreterErrorKind, LoxError, LoxErrorKind}, interpreter::{interpreter::Interpreter, limits::Limits, native_functions::{Arity, NativeClosure, NativeFunction, ScriptArgs}, sandbox::Sandbox, types::Value}, parser::{cst::SyntaxTree, lexer::Lexer, parser::Parser, printer::Printer, resolver::Resolver, source_map::SourceMap, tokens::{Token, TokenKind}, types::Stmt}};
+
+/// A persistent interpreter session, also the entry point for embedding Lox in a Rust application.
+///
+/// Keeps a single `Interpreter` (with its string interner, global environment and side table) alive between runs,
+/// so that the same program state can be fed with more source code over time (REPL, notebook cells, hot reload).
+///
+/// Program output (`print`) is written to the supplied writer, while errors are returned as `LoxError` values.
+/// Every chunk of code is kept in the `SourceMap` of the session, which resolves the spans of the errors (see `set_max_chunks` to keep only the latest ones).
+pub struct Session<T: Write>
+{
+    interpreter: Interpreter<T>,
+    init_symbol: IdentifierSymbol,
+    source_map: SourceMap,
+    /// Number of chunks registered so far, including the ones removed from the source map.
+    chunk_count: usize,
+    max_chunks: Option<usize>
+}
+
+impl <T: Write> Session<T>
+{
+    pub fn new(writer: T) -> Self
+    {
+        Self::new_with_writer(Rc::new(RefCell::new(writer)))
+    }
+
+    pub fn new_with_writer(writer: Rc<RefCell<T>>) -> Self
+    {
+        let mut interner: StringInterner = StringInterner::default();
+        let _ = interner.get_or_intern_static("this");
+        let _ = interner.get_or_intern_static("super");
+        let init_symbol: IdentifierSymbol = interner.get_or_intern_static("init");
+        Self {
+            interpreter: Interpreter::new_with_writer(interner, SideTable::default(), writer),
+            init_symbol,
+            source_map: SourceMap::default(),
+            chunk_count: 0,
+            max_chunks: None
+        }
+    }
+
+    /// Runs a new chunk of source code in the session.
+    ///
+    /// The code is parsed and resolved on its own (global variables are looked up at runtime, so it can refer to anything declared by the previous chunks),
+    /// then the new entries of the side table are merged into the existing one and only the new statements are executed.
+    ///
+    /// Returns all the errors found while parsing or resolving the code, or the runtime error that stopped the execution.
+    pub fn run(&mut self, code: &str) -> Result<(), Vec<LoxError>>
+    {
+        let stmts = self.parse_and_resolve(code)?;
+        self.interpreter.execute(&stmts).map_err(|error| vec![error])
+    }
+
+    /// Same as `run`, but also prints the value of each top level expression statement (eg. `1 + 2;` prints `3`).
+    ///
+    /// Assignments (`a = 1;`, `obj.field = 1;`) are not echoed, and neither are the calls returning `nil` (eg. `clock();` prints the time, `assertEq(a, 1);` prints nothing).
+    pub fn eval(&mut self, code: &str) -> Result<(), Vec<LoxError>>
+    {
+        let stmts = self.parse_and_resolve(code)?;
+        self.interpreter.execute_echoing(&stmts).map_err(|error| vec![error])
+    }
+
+    /// Defines (or redefines) a global variable.
+    pub fn set_global(&mut self, name: &str, value: Value)
+    {
+        self.interpreter.define_global(name, value);
+    }
+
+    /// Returns the value of a global variable, if defined.
+    pub fn get_global(&self, name: &str) -> Option<Value>
+    {
+        self.interpreter.get_global(name)
+    }
+
+    /// Calls the function (or class) stored in the global variable `name` with the supplied arguments and returns its result.
+    pub fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, LoxError>
+    {
+        match self.interpreter.get_global(name) {
+            Some(callee) => {
+                self.interpreter.call(&callee, args)
+            },
+            None => {
+                Err(LoxError { kind: LoxErrorKind::Interpreter(InterpreterErrorKind::UndefinedVariable(name.to_owned())), span: None, help: None, stack_trace: Vec::new() })
+            }
+        }
+    }
+
+    /// Registers a native function as a global variable named after it, making it callable from Lox code.
+    pub fn register_native<N: NativeFunction + 'static>(&mut self, native: N)
+    {
+        self.interpreter.define_native(Rc::new(native));
+    }
+
+    /// Makes the supplied arguments available to the script through the `args` native function.
+    pub fn set_script_args(&mut self, args: Vec<String>)
+    {
+        self.register_native(ScriptArgs::new(args));
+    }
+
+    /// Sets the resource limits enforced while running code, eg. the maximum call depth.
+    pub fn set_limits(&mut self, limits: Limits)
+    {
+        self.interpreter.set_limits(limits);
+    }
+
+    /// Sets the profile deciding which native functions the code can use, eg. `Sandbox::Pure` for no clock and no IO.
+    ///
+    /// Natives registered afterwards are checked against the same profile. Using a native that is not allowed raises a `NativeNotAllowed` runtime error.
+    pub fn set_sandbox(&mut self, sandbox: Sandbox)
+    {
+        self.interpreter.set_sandbox(sandbox);
+    }
+
+    /// Returns the flag that interrupts the running code when set, eg. from another thread or a Ctrl-C handler.
+    ///
+    /// The code stops at the next loop iteration or call with an `Interrupted` runtime error and the flag is cleared.
+    pub fn interrupt_handle(&self) -> Arc<AtomicBool>
+    {
+        self.interpreter.interrupt_handle()
+    }
+
+    /// Makes the session check the supplied flag for interruptions instead of its own.
+    pub fn set_interrupt_handle(&mut self, interrupt: Arc<AtomicBool>)
+    {
+        self.interpreter.set_interrupt_handle(interrupt);
+    }
+
+    /// Registers a closure as a native function with a fixed number of parameters.
+    ///
+    /// An `Err` returned by `function` is reported as a runtime error with the given message.
+    pub fn define_native<F>(&mut self, name: &str, arity: usize, function: F)
+        where F: Fn(&[Value]) -> Result<Value, String> + 'static
+    {
+        self.register_native(NativeClosure::new(name, Arity::Fixed(arity), function));
+    }
+
+    /// Returns the code run so far, one file per chunk, to resolve the spans of tokens, syntax tree nodes and errors.
+    pub fn source_map(&self) -> &SourceMap
+    {
+        &self.source_map
+    }
+
+    /// Keeps at most `max_chunks` chunks (at least one) in the source map, dropping the oldest ones, so that a long running session (REPL, hot reload) does not keep all the code it has run.
+    /// `None`, the default, keeps every chunk.
+    ///
+    /// Errors raised later by the code of a dropped chunk (eg. by a function declared there) are reported without their source.
+    pub fn set_max_chunks(&mut self, max_chunks: Option<usize>)
+    {
+        self.max_chunks = max_chunks.map(|max_chunks| max_chunks.max(1));
+        if let Some(max_chunks) = self.max_chunks {
+            self.source_map.retain_latest(max_chunks);
+        }
+    }
+
+    /// Converts a value to a string the same way `print` does.
+    pub fn stringify(&self, value: &Value) -> String
+    {
+        value.to_string(self.interpreter.string_interner())
+    }
+
+    /// Debugging aid: returns the tokens of `code`, one per line with their position, along with the lexical errors found.
+    pub fn dump_tokens(&mut self, code: &str) -> (Vec<String>, Vec<LoxError>)
+    {
+        let start_offset = match self.add_chunk(code) {
+            Ok(start_offset) => start_offset,
+            Err(errors) => return (Vec::new(), errors),
+        };
+        let tokens: Vec<Token> = Lexer::new_with_offset(code, start_offset, self.interpreter.string_interner_mut()).collect();
+        let errors = tokens.iter()
+            .filter_map(|token| match &token.kind {
+                TokenKind::Error(error) => Some(LoxError::parser_error(error.kind.clone(), token.span)),
+                _ => None,
+            })
+            .collect();
+        let printer = Printer::new(self.interpreter.string_interner());
+        (tokens.iter().map(|token| printer.print_token(token)).collect(), errors)
+    }
+
+    /// Builds the lossless concrete syntax tree of `code` without running it, as the basis for formatters and refactoring tools.
+    pub fn syntax_tree(&mut self, code: &str) -> Result<SyntaxTree, Vec<LoxError>>
+    {
+        let start_offset = self.add_chunk(code)?;
+        Ok(SyntaxTree::parse(code, start_offset, self.init_symbol, self.interpreter.string_interner_mut()))
+    }
+
+    /// Debugging aid: returns the lossless concrete syntax tree of `code`, one line per node, token and trivia, along with the errors found.
+    pub fn dump_cst(&mut self, code: &str) -> (Vec<String>, Vec<LoxError>)
+    {
+        let tree = match self.syntax_tree(code) {
+            Ok(tree) => tree,
+            Err(errors) => return (Vec::new(), errors),
+        };
+        let printer = Printer::new(self.interpreter.string_interner());
+        (printer.print_syntax_tree(&tree), tree.errors)
+    }
+
+    /// Debugging aid: returns the syntax tree of `code` as S-expressions, one line per top level statement (nested statements are indented).
+    pub fn dump_ast(&mut self, code: &str) -> Result<Vec<String>, Vec<LoxError>>
+    {
+        let stmts = self.parse(code)?;
+        let printer = Printer::new(self.interpreter.string_interner());
+        Ok(stmts.iter().map(|stmt| printer.print_stmt(stmt)).collect())
+    }
+
+    /// Debugging aid: returns each variable expression of `code` with the scope distance recorded by the resolver, in source order.
+    pub fn dump_resolve(&mut self, code: &str) -> Result<Vec<String>, Vec<LoxError>>
+    {
+        let stmts = self.parse(code)?;
+        let side_table = self.resolve(&stmts)?;
+        let printer = Printer::new(self.interpreter.string_interner());
+        Ok(printer.print_resolved(&stmts, &side_table))
+    }
+
+    /// Registers `code` in the source map and returns its start offset, dropping the oldest chunks beyond `max_chunks`.
+    ///
+    /// Fails once the offsets of the code run by the session no longer fit in a `u32`.
+    fn add_chunk(&mut self, code: &str) -> Result<u32, Vec<LoxError>>
+    {
+        self.chunk_count += 1;
+        let name = format!("<chunk {}>", self.chunk_count);
+        let start_offset = self.source_map.add_file(name, code)
+            .map(|file| file.start())
+            .ok_or_else(|| vec![LoxError::internal_error(InternalErrorKind::SourceMapFull)])?;
+        if let Some(max_chunks) = self.max_chunks {
+            self.source_map.retain_latest(max_chunks);
+        }
+        Ok(start_offset)
+    }
+
+    fn parse(&mut self, code: &str) -> Result<Vec<Stmt>, Vec<LoxError>>
+    {
+        let start_offset = self.add_chunk(code)?;
+        let mut parser: Parser = Parser::new(self.init_symbol);
+        parser.parse(code, start_offset, self.interpreter.string_interner_mut())
+    }
+
+    fn resolve(&mut self, stmts: &[Stmt]) -> Result<SideTable, Vec<LoxError>>
+    {
+        let mut resolver: Resolver = Resolver::new(self.interpreter.string_interner_mut());
+        resolver.resolve(stmts)
+    }
+
+    fn parse_and_resolve(&mut self, code: &str) -> Result<Vec<Stmt>, Vec<LoxError>>
+    {
+        let stmts: Vec<Stmt> = self.parse(code)?;
+        let side_table: SideTable = self.resolve(&stmts)?;
+        self.interpreter.extend_side_table(side_table);
+        Ok(stmts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, sync::atomic::Ordering, thread, time::Duration};
+
+    use crate::{error::{ExecutionResult, InterpreterErrorKind, LoxError, LoxErrorKind}, interpreter::{limits::Limits, native_functions::{Arity, NativeClosure, NativeContext, NativeFunction}, sandbox::{Capability, Sandbox}, types::Value}};
+
+    use super::Session;
+
+    fn stage(result: Result<(), Vec<crate::error::LoxError>>) -> Result<(), ExecutionResult>
+    {
+        result.map_err(|errors| ExecutionResult::from(&errors[0]))
+    }
+
+    fn output(buf_output: &[u8]) -> Vec<&str>
+    {
+        std::str::from_utf8(buf_output).unwrap().lines().collect()
+    }
+
+    #[test]
+    fn test_run_multiple_chunks() {
+        let mut buf_output = Vec::<u8>::new();
+        {
+            let mut session = Session::new(&mut buf_output);
+            assert_eq!(session.run("var a = 1; fun counter() { var count = 0; fun inc() { count = count + a; return count; } return inc; }"), Ok(()));
+            assert_eq!(session.run("var inc = counter(); inc(); print inc();"), Ok(()));
+            assert_eq!(session.run("a = 10; print inc();"), Ok(()));
+            assert_eq!(session.run("class Foo { init(x) { this.x = x; } } class Bar < Foo { get() { return this.x; } }"), Ok(()));
+            assert_eq!(session.run("{ var bar = Bar(3); print bar.get(); }"), Ok(()));
+        }
+        assert_eq!(output(&buf_output), vec!["2", "12", "3"]);
+    }
+
+    #[test]
+    fn test_errors_do_not_reset_state() {
+        let mut buf_output = Vec::<u8>::new();
+        {
+            let mut session = Session::new(&mut buf_output);
+            assert_eq!(session.run("var a = \"a\";"), Ok(()));
+            assert_eq!(stage(session.run("print a +;")), Err(ExecutionResult::ParserError));
+            assert_eq!(stage(session.run("return a;")), Err(ExecutionResult::ResolverError));
+            assert_eq!(stage(session.run("a = a + \"b\"; print a - 1;")), Err(ExecutionResult::RuntimeError));
+            assert_eq!(session.run("print a;"), Ok(()));
+        }
+        assert_eq!(output(&buf_output).last(), Some(&"ab"));
+    }
+
+    #[test]
+    fn test_lexical_errors_do_not_cascade() {
+        let mut session = Session::new(Vec::<u8>::new());
+        let messages = |errors: Vec<LoxError>| errors.iter().map(|error| (error.to_string(), error.span.unwrap().start.column)).collect::<Vec<(String, u32)>>();
+        let errors = session.run("print €;\nvar x = 1 # 2;\nprint a€;\nvar y = 0b12;\nprint \"a\\q\" + ;\nprint \"open").unwrap_err();
+        assert_eq!(messages(errors), vec![
+            ("[line 1] Parser error: Unexpected token '€'.".to_owned(), 7),
+            ("[line 2] Parser error: Unexpected token '#'.".to_owned(), 11),
+            ("[line 3] Parser error: Unexpected token '€'.".to_owned(), 8),
+            ("[line 4] Parser error: Invalid number: '2' is not a binary digit.".to_owned(), 12),
+            ("[line 5] Parser error: Invalid escape sequence: unknown escape '\\q'.".to_owned(), 9),
+            ("[line 5] Parser error: Expect expression".to_owned(), 15),
+            ("[line 6] Parser error: Unterminated string.".to_owned(), 7),
+        ]);
+    }
+
+    #[test]
+    fn test_source_map() {
+        let mut session = Session::new(Vec::<u8>::new());
+        assert_eq!(session.run("var a = 1;"), Ok(()));
+        let errors = session.run("print a;\nprint -\"é\";").unwrap_err();
+        let files: Vec<&str> = session.source_map().files().iter().map(|file| file.name()).collect();
+        assert_eq!(files, vec!["<chunk 1>", "<chunk 2>"]);
+        // the spans of the errors point into the chunk they were found in
+        let span = errors[0].span.unwrap();
+        assert_eq!(span.offsets(), 26..31);
+        assert_eq!(session.source_map().text(span), Some("-\"é\""));
+        assert_eq!(session.source_map().position(span.start.offset), Some(span.start));
+
+        // old chunks can be dropped, the offsets of the new ones keep growing
+        session.set_max_chunks(Some(2));
+        assert_eq!(session.run("fun f() { return -\"a\"; }"), Ok(()));
+        assert_eq!(session.run("print 1;"), Ok(()));
+        let files: Vec<&str> = session.source_map().files().iter().map(|file| file.name()).collect();
+        assert_eq!(files, vec!["<chunk 3>", "<chunk 4>"]);
+        assert_eq!(session.source_map().files()[0].start(), 33);
+        // an error raised by the code of a dropped chunk keeps its span, which no longer resolves
+        session.set_max_chunks(Some(1));
+        let errors = session.run("f();").unwrap_err();
+        assert_eq!(session.source_map().files().len(), 1);
+        assert!(session.source_map().file(errors[0].span.unwrap().start.offset).is_none());
+    }
+
+    #[test]
+    fn test_eval_prints_expressions() {
+        let mut buf_output = Vec::<u8>::new();
+        {
+            let mut session = Session::new(&mut buf_output);
+            assert_eq!(session.eval("var str = 1; str + 1; if (true) str;"), Ok(()));
+        }
+        assert_eq!(output(&buf_output), vec!["2"]);
+    }
+
+    #[test]
+    fn test_eval_skips_assignments_and_nil_calls() {
+        let mut buf_output = Vec::<u8>::new();
+        {
+            let mut session = Session::new(&mut buf_output);
+            assert_eq!(session.eval("class A {} var a = A(); var b; b = 1; a.field = 2; fun f() {} fun g() { return b; } f(); g(); a.field; (b = 3); nil;"), Ok(()));
+        }
+        assert_eq!(output(&buf_output), vec!["1", "2", "3", "nil"]);
+    }
+
+    #[test]
+    fn test_globals() {
+        let mut session = Session::new(Vec::<u8>::new());
+        session.set_global("width", Value::from(3.0));
+        assert_eq!(session.run("var area = width * 2;"), Ok(()));
+        assert_eq!(session.get_global("area"), Some(Value::Number(6.0)));
+        assert_eq!(session.get_global("missing"), None);
+    }
+
+    #[test]
+    fn test_call() {
+        let mut session = Session::new(Vec::<u8>::new());
+        assert_eq!(session.run("fun greet(name) { return \"Hello, \" + name; }"), Ok(()));
+        let result = session.call("greet", vec![Value::from("Lox")]).unwrap();
+        assert_eq!(session.stringify(&result), "Hello, Lox");
+
+        let error = session.call("greet", vec![]).unwrap_err();
+        assert_eq!(error.kind, LoxErrorKind::Interpreter(InterpreterErrorKind::WrongArity(1, 0)));
+        let error = session.call("missing", vec![]).unwrap_err();
+        assert_eq!(error.kind, LoxErrorKind::Interpreter(InterpreterErrorKind::UndefinedVariable("missing".to_owned())));
+
+        assert_eq!(session.run("fun fail(x) { return -x; }\nfun outer() { return fail(\"a\"); }"), Ok(()));
+        let error = session.call("outer", vec![]).unwrap_err();
+        assert_eq!(error.kind, LoxErrorKind::Interpreter(InterpreterErrorKind::CheckNumberOperand));
+        let frames: Vec<String> = error.stack_trace.iter().map(|frame| frame.to_string()).collect();
+        assert_eq!(frames, vec!["[line 1] in fail()", "[line 2] in outer()"]);
+    }
+
+    #[test]
+    fn test_define_native() {
+        let mut buf_output = Vec::<u8>::new();
+        {
+            let mut session = Session::new(&mut buf_output);
+            session.define_native("double", 1, |args| {
+                match args[0] {
+                    Value::Number(n) => Ok(Value::Number(n * 2.0)),
+                    _ => Err("Argument must be a number.".to_owned())
+                }
+            });
+            assert_eq!(session.run("print double(21);"), Ok(()));
+            let errors = session.run("double(\"a\");").unwrap_err();
+            assert_eq!(errors[0].kind, LoxErrorKind::Interpreter(InterpreterErrorKind::NativeFunctionError("Argument must be a number.".to_owned())));
+        }
+        assert_eq!(output(&buf_output), vec!["42"]);
+    }
+
+    struct Join;
+
+    impl NativeFunction for Join
+    {
+        fn name(&self) -> &str
+        {
+            "join"
+        }
+
+        fn arity(&self) -> Arity
+        {
+            Arity::Variadic
+        }
+
+        fn call(&self, context: &NativeContext, args: &[Value]) -> Result<Value, InterpreterErrorKind>
+        {
+            let strings: Vec<String> = args.iter().map(|arg| context.stringify(arg)).collect();
+            Ok(Value::from(strings.join(" ")))
+        }
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut session = Session::new(Vec::<u8>::new());
+        session.set_limits(Limits { max_call_depth: 10, ..Limits::default() });
+        assert_eq!(session.run("fun count(n) { if (n > 0) return 1 + count(n - 1); return 0; }"), Ok(()));
+        assert_eq!(session.eval("assertEq(count(9), 9);"), Ok(()));
+        let errors = session.run("count(10);").unwrap_err();
+        assert_eq!(errors[0].kind, LoxErrorKind::Interpreter(InterpreterErrorKind::StackOverflow));
+        assert_eq!(errors[0].stack_trace.len(), 11);
+        // the call depth is reset after the error
+        assert_eq!(session.eval("assertEq(count(9), 9);"), Ok(()));
+    }
+
+    #[test]
+    fn test_resource_limits() {
+        let kind = |result: Result<(), Vec<LoxError>>| result.unwrap_err()[0].kind.clone();
+        let mut session = Session::new(Vec::<u8>::new());
+        session.set_limits(Limits { max_instructions: Some(1000), ..Limits::default() });
+        assert_eq!(session.run("for (var i = 0; i < 10; i = i + 1) {}"), Ok(()));
+        assert_eq!(kind(session.run("while (true) {}")), LoxErrorKind::Interpreter(InterpreterErrorKind::InstructionLimitExceeded));
+        // the counters start again from zero on each run
+        assert_eq!(session.run("for (var i = 0; i < 10; i = i + 1) {}"), Ok(()));
+
+        session.set_limits(Limits { timeout: Some(Duration::from_millis(50)), ..Limits::default() });
+        assert_eq!(kind(session.run("while (true) {}")), LoxErrorKind::Interpreter(InterpreterErrorKind::Timeout));
+
+        session.set_limits(Limits { max_allocated_bytes: Some(1024), ..Limits::default() });
+        assert_eq!(session.run("var s = \"ab\" + \"cd\"; class A {} var a = A(); a.x = 1; a.x = 2;"), Ok(()));
+        assert_eq!(kind(session.run("var s = \"a\"; while (true) s = s + s;")), LoxErrorKind::Interpreter(InterpreterErrorKind::MemoryLimitExceeded));
+        assert_eq!(kind(session.run("var list = nil; while (true) { var node = A(); node.next = list; list = node; }")), LoxErrorKind::Interpreter(InterpreterErrorKind::MemoryLimitExceeded));
+        // strings returned by native functions are charged too
+        assert_eq!(kind(session.run("var i = 0; while (true) { str(i); i = i + 1; }")), LoxErrorKind::Interpreter(InterpreterErrorKind::MemoryLimitExceeded));
+        session.set_sandbox(Sandbox::Full);
+        assert_eq!(session.run("readFile(\"lox_test/bool/not.lox\");"), Ok(()));
+        assert_eq!(kind(session.run("readFile(\"lox_test/field/many.lox\");")), LoxErrorKind::Interpreter(InterpreterErrorKind::MemoryLimitExceeded));
+    }
+
+    #[test]
+    fn test_interrupt() {
+        let mut session = Session::new(Vec::<u8>::new());
+        let interrupt = session.interrupt_handle();
+        let setter = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            interrupt.store(true, Ordering::Relaxed);
+        });
+        let errors = session.run("var i = 0; while (true) { i = i + 1; }").unwrap_err();
+        setter.join().unwrap();
+        assert_eq!(errors[0].kind, LoxErrorKind::Interpreter(InterpreterErrorKind::Interrupted));
+        // the flag is cleared once the interruption is reported
+        assert_eq!(session.run("i = 0; while (i < 10) { i = i + 1; }"), Ok(()));
+
+        session.interrupt_handle().store(true, Ordering::Relaxed);
+        let errors = session.run("fun f() {} f();").unwrap_err();
+        assert_eq!(errors[0].kind, LoxErrorKind::Interpreter(InterpreterErrorKind::Interrupted));
+    }
+
+    #[test]
+    fn test_sandbox() {
+        let kind = |result: Result<(), Vec<LoxError>>| result.unwrap_err()[0].kind.clone();
+        let mut session = Session::new(Vec::<u8>::new());
+        // by default the clock is available, the files are not
+        assert_eq!(session.run("var start = clock();"), Ok(()));
+        assert_eq!(kind(session.run("readFile(\"lox_test/bool/not.lox\");")), LoxErrorKind::Interpreter(InterpreterErrorKind::NativeNotAllowed("readFile".to_owned(), "standard")));
+        session.set_sandbox(Sandbox::Full);
+        assert_eq!(session.run("var text = readFile(\"lox_test/bool/not.lox\"); var str = 5; var tick = clock;"), Ok(()));
+
+        session.set_sandbox(Sandbox::Pure);
+        let errors = session.run("print clock();").unwrap_err();
+        assert_eq!(errors[0].kind, LoxErrorKind::Interpreter(InterpreterErrorKind::NativeNotAllowed("clock".to_owned(), "pure")));
+        assert_eq!(errors[0].help.as_deref(), Some("`clock` needs access to the clock"));
+        assert_eq!(kind(session.run("readFile(\"lox_test/bool/not.lox\");")), LoxErrorKind::Interpreter(InterpreterErrorKind::NativeNotAllowed("readFile".to_owned(), "pure")));
+        // the globals of the script are left alone, even when they hold a native function
+        assert_eq!(session.run("assertEq(str, 5); assertEq(tick == nil, false);"), Ok(()));
+        // the script can still define its own function with the same name
+        assert_eq!(session.run("fun clock() { return 0; } assertEq(clock(), 0);"), Ok(()));
+        // natives registered later are checked too
+        session.register_native(NativeClosure::new("now", Arity::Fixed(0), |_| Ok(Value::Nil)).with_capability(Capability::Clock));
+        assert_eq!(kind(session.run("now();")), LoxErrorKind::Interpreter(InterpreterErrorKind::NativeNotAllowed("now".to_owned(), "pure")));
+
+        session.set_sandbox(Sandbox::ReadOnly(PathBuf::from("lox_test/bool")));
+        assert_eq!(session.run("assertEq(readFile(\"not.lox\"), readFile(\"./not.lox\")); now(); assertEq(clock(), 0); assertEq(str, 5);"), Ok(()));
+        assert!(matches!(kind(session.run("readFile(\"../../Cargo.toml\");")), LoxErrorKind::Interpreter(InterpreterErrorKind::NativeFunctionError(_))));
+        assert!(matches!(kind(session.run("readFile(\"missing.lox\");")), LoxErrorKind::Interpreter(InterpreterErrorKind::NativeFunctionError(_))));
+    }
+
+    #[test]
+    fn test_register_native() {
+        let mut buf_output = Vec::<u8>::new();
+        {
+            let mut session = Session::new(&mut buf_output);
+            session.register_native(Join);
+            assert_eq!(session.run("print join(); print join(1, true, nil, \"a\"); print join;"), Ok(()));
+        }
+        assert_eq!(output(&buf_output), vec!["", "1 true nil a", "<native fn>"]);
+    }
+
+    #[test]
+    fn test_dumps() {
+        let mut session = Session::new(Vec::<u8>::new());
+        let (tokens, errors) = session.dump_tokens("var a = \"s\";\n a;");
+        assert_eq!(tokens, vec![
+            "[line 1:1] Var", "[line 1:5] Identifier(a)", "[line 1:7] Equal", "[line 1:9] String(\"s\")", "[line 1:12] Semicolon",
+            "[line 2:2] Identifier(a)", "[line 2:3] Semicolon", "[line 2:4] EndOfFile"
+        ]);
+        assert!(errors.is_empty());
+
+        // the second chunk starts at offset 17 of the source map
+        let (cst, errors) = session.dump_cst("-a; // b\n");
+        assert_eq!(cst, vec![
+            "Program@17..26", "  ExprStmt@17..20", "    Unary@17..19", "      Minus \"-\"", "      Variable@18..19", "        Identifier(a) \"a\"",
+            "    Semicolon \";\"", "  Whitespace \" \"", "  LineComment \"// b\"", "  Newline \"\\n\"", "  EndOfFile \"\""
+        ]);
+        assert!(errors.is_empty());
+
+        let ast = session.dump_ast("print -1 * (2 + a.b); fun f(x) { if (x) return x; } class A < B { m() { this.c = super.m(nil); } } print \"a ${b + 1} c${d}\";").unwrap();
+        assert_eq!(ast, vec![
+            "(print (* (- 1) (group (+ 2 (. a b)))))",
+            "(fun f(x)\n  (if x\n    (return x)))",
+            "(class A < B\n  (fun m()\n    (; (= this c (call (super m) nil)))))",
+            "(print (interpolate \"a \" (+ b 1) \" c\" d))"
+        ]);
+
+        let resolved = session.dump_resolve("var a = 1; { var b = a; fun f() { b = b + a; } }").unwrap();
+        assert_eq!(resolved, vec![
+            "[line 1:22] a -> global",
+            "[line 1:35] b (assignment) -> distance 1",
+            "[line 1:39] b -> distance 1",
+            "[line 1:43] a -> global"
+        ]);
+    }
+}
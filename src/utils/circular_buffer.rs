@@ -85,39 +85,39 @@ mod tests
 
         assert_eq!(buffer.dequeue(), None);
 
-        assert_eq!(buffer.is_empty(), true);
-        assert_eq!(buffer.is_full(), false);
+        assert!(buffer.is_empty());
+        assert!(!buffer.is_full());
         buffer.enqueue(1);
-        assert_eq!(buffer.is_empty(), false);
-        assert_eq!(buffer.is_full(), false);
+        assert!(!buffer.is_empty());
+        assert!(!buffer.is_full());
         buffer.enqueue(2);
-        assert_eq!(buffer.is_empty(), false);
-        assert_eq!(buffer.is_full(), false);
+        assert!(!buffer.is_empty());
+        assert!(!buffer.is_full());
         buffer.enqueue(3);
-        assert_eq!(buffer.is_empty(), false);
-        assert_eq!(buffer.is_full(), false);
+        assert!(!buffer.is_empty());
+        assert!(!buffer.is_full());
         buffer.enqueue(4);
-        assert_eq!(buffer.is_empty(), false);
-        assert_eq!(buffer.is_full(), false);
+        assert!(!buffer.is_empty());
+        assert!(!buffer.is_full());
         buffer.enqueue(5);
-        assert_eq!(buffer.is_empty(), false);
-        assert_eq!(buffer.is_full(), true);
+        assert!(!buffer.is_empty());
+        assert!(buffer.is_full());
 
         assert_eq!(buffer.dequeue(), Some(1));
-        assert_eq!(buffer.is_empty(), false);
-        assert_eq!(buffer.is_full(), false);
+        assert!(!buffer.is_empty());
+        assert!(!buffer.is_full());
         assert_eq!(buffer.dequeue(), Some(2));
-        assert_eq!(buffer.is_empty(), false);
-        assert_eq!(buffer.is_full(), false);
+        assert!(!buffer.is_empty());
+        assert!(!buffer.is_full());
         assert_eq!(buffer.dequeue(), Some(3));
-        assert_eq!(buffer.is_empty(), false);
-        assert_eq!(buffer.is_full(), false);
+        assert!(!buffer.is_empty());
+        assert!(!buffer.is_full());
         assert_eq!(buffer.dequeue(), Some(4));
-        assert_eq!(buffer.is_empty(), false);
-        assert_eq!(buffer.is_full(), false);
+        assert!(!buffer.is_empty());
+        assert!(!buffer.is_full());
         assert_eq!(buffer.dequeue(), Some(5));
-        assert_eq!(buffer.is_empty(), true);
-        assert_eq!(buffer.is_full(), false);
+        assert!(buffer.is_empty());
+        assert!(!buffer.is_full());
         assert_eq!(buffer.dequeue(), None);
     }
 
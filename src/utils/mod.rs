@@ -1,4 +1,6 @@
 pub mod circular_buffer;
 pub mod stack;
 pub mod peekable_iterator;
-pub mod rc_cache;
\ No newline at end of file
+pub mod rc_cache;
+pub mod native_stack;
+pub mod suggest;
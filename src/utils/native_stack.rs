@@ -0,0 +1,14 @@
+/// Minimum amount of native stack that must be left before entering a recursive function.
+const RED_ZONE: usize = 128 * 1024;
+
+/// Size of the new stack segment allocated when the red zone is reached.
+const STACK_SEGMENT_SIZE: usize = 2 * 1024 * 1024;
+
+/// Runs `f`, first moving to a new stack segment allocated on the heap if the native stack is about to run out.
+///
+/// Wraps the recursive functions of the parser, the resolver and the interpreter, so that deeply nested code cannot overflow the native stack of the host.
+#[inline]
+pub fn ensure_sufficient_stack<R>(f: impl FnOnce() -> R) -> R
+{
+    stacker::maybe_grow(RED_ZONE, STACK_SEGMENT_SIZE, f)
+}
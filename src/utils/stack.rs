@@ -38,8 +38,8 @@ impl <T> Stack<T>
         Some(&self.vec[len-1])
     }
 
-    pub fn iter(&self) -> Iter<T> {
-        return self.vec.iter();
+    pub fn iter(&self) -> Iter<'_, T> {
+        self.vec.iter()
     }
 
     pub fn len(&self) -> usize {
@@ -55,7 +55,7 @@ mod tests
     #[test]
     fn test_stack() {
         let mut stack = Stack::<i32>::new();
-        assert_eq!(stack.is_empty(), true);
+        assert!(stack.is_empty());
         assert_eq!(stack.len(), 0);
         assert_eq!(stack.peek(), None);
         assert_eq!(stack.peek_mut(), None);
@@ -66,16 +66,16 @@ mod tests
         stack.push(1);
         assert_eq!(stack.peek().cloned(), Some(1));
         assert_eq!(stack.peek_mut().cloned(), Some(1));
-        assert_eq!(stack.is_empty(), false);
+        assert!(!stack.is_empty());
         assert_eq!(stack.len(), 1);
         assert_eq!(stack.pop(), Some(1));
-        assert_eq!(stack.is_empty(), true);
+        assert!(stack.is_empty());
         assert_eq!(stack.len(), 0);
 
         stack.push(1);
         stack.push(2);
         stack.push(3);
-        assert_eq!(stack.is_empty(), false);
+        assert!(!stack.is_empty());
         assert_eq!(stack.len(), 3);
     }
 }
\ No newline at end of file
@@ -0,0 +1,55 @@
+/// Returns a help note suggesting the candidate closest to `name`, eg. "did you mean `init`?".
+///
+/// Only candidates within an edit distance of a third of the length of `name` are suggested, so names shorter than 3 chars get no suggestion.
+pub fn did_you_mean<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<String>
+{
+    let max_distance = name.chars().count() / 3;
+    let mut best: Option<(usize, &str)> = None;
+    for candidate in candidates {
+        if candidate == name {
+            continue;
+        }
+        let distance = edit_distance(name, candidate);
+        let is_better = match best {
+            Some((best_distance, best_candidate)) => distance < best_distance || distance == best_distance && candidate < best_candidate,
+            None => true,
+        };
+        if distance <= max_distance && is_better {
+            best = Some((distance, candidate));
+        }
+    }
+    best.map(|(_, candidate)| format!("did you mean `{}`?", candidate))
+}
+
+/// Levenshtein distance between two strings, counted in chars.
+fn edit_distance(a: &str, b: &str) -> usize
+{
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current: Vec<usize> = vec![0; b.len() + 1];
+    for (i, ch_a) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, ch_b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ch_a != *ch_b);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests
+{
+    use crate::utils::suggest::{did_you_mean, edit_distance};
+
+    #[test]
+    fn test_did_you_mean() {
+        assert_eq!(edit_distance("init", "int"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(did_you_mean("int", ["init", "print", "x"]), Some("did you mean `init`?".to_owned()));
+        assert_eq!(did_you_mean("conut", ["count", "amount"]), None);
+        assert_eq!(did_you_mean("counter", ["countr", "count"]), Some("did you mean `countr`?".to_owned()));
+        assert_eq!(did_you_mean("a", ["a", "b"]), None);
+    }
+}